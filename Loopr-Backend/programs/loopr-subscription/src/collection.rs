@@ -0,0 +1,355 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, oracle, transfer, events::*};
+
+/// Accounts an autopay collection needs, borrowed from `automated_payment` or
+/// the keeper crank `collect_due_payment`
+pub struct AutopayCollection<'a, 'info> {
+    pub user_subscription: &'a mut Account<'info, UserSubscription>,
+    pub subscription_plan: &'a mut Account<'info, SubscriptionPlan>,
+    pub merchant: &'a mut Account<'info, Merchant>,
+    pub payment_ledger: &'a mut Account<'info, PaymentLedger>,
+    pub user_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub plan_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub treasury_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub price_feed: Option<&'a UncheckedAccount<'info>>,
+    pub fee_stats: &'a mut Account<'info, FeeStats>,
+    pub fee_stats_bump: u8,
+    pub autopay_delegate: &'a UncheckedAccount<'info>,
+    pub autopay_delegate_bump: u8,
+    pub global_state: &'a mut Account<'info, GlobalState>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+    /// Keeper's account in the payment mint; a bounty is only paid when set
+    pub keeper_token_account: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+}
+
+/// Pull a due payment through the autopay delegate, paying the merchant (or
+/// escrow), the protocol fee and any keeper bounty.
+///
+/// An unfunded collection is recorded as a failed attempt on the subscription
+/// rather than reverting, so it moves through PastDue to Suspended, or expires
+/// if the subscription never paid. Nothing was paid, so the attempt is left
+/// out of the payment ledger.
+pub fn collect<'info>(
+    accounts: AutopayCollection<'_, 'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    require!(!accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let AutopayCollection {
+        user_subscription,
+        subscription_plan,
+        merchant,
+        payment_ledger,
+        user_token_account,
+        plan_token_account,
+        mint,
+        treasury_token_account,
+        escrow_vault,
+        price_feed,
+        fee_stats,
+        fee_stats_bump,
+        autopay_delegate,
+        autopay_delegate_bump,
+        global_state,
+        token_program,
+        keeper_token_account,
+    } = accounts;
+    let clock = Clock::get()?;
+
+    // Bring dunning state up to date; past the grace period only a manual payment can recover
+    user_subscription.refresh_status(clock.unix_timestamp, subscription_plan);
+    require!(user_subscription.status.is_open(), LooprError::SubscriptionEnded);
+    require!(
+        user_subscription.status != SubscriptionStatus::Suspended,
+        LooprError::PaymentOverdue
+    );
+
+    // Check if payment (or the next retry) is due
+    require!(
+        clock.unix_timestamp >= user_subscription.next_collection_at(subscription_plan),
+        LooprError::PaymentNotDue
+    );
+
+    // Pick up a new plan price once it is allowed to reach this subscriber
+    user_subscription.sync_price(clock.unix_timestamp, subscription_plan);
+    let price = user_subscription.effective_price(subscription_plan.period_price(user_subscription)?);
+    let amount = oracle::to_settlement_amount(
+        subscription_plan,
+        price_feed.map(|feed| feed.as_ref()),
+        &global_state.price_oracle,
+        user_subscription.max_slippage_bps,
+        user_subscription.amount_due(price),
+        clock.unix_timestamp,
+    )?;
+
+    fee_stats.mint = mint.key();
+    fee_stats.bump = fee_stats_bump;
+    fee_stats.version = ACCOUNT_VERSION;
+
+    let funded = user_subscription.autopay_allowance >= amount
+        && transfer::can_pull(user_token_account, &autopay_delegate.key(), amount);
+    if !funded {
+        user_subscription.record_failed_payment(clock.unix_timestamp, subscription_plan);
+
+        emit!(PaymentFailed {
+            subscription: user_subscription.key(),
+            user: user_subscription.user,
+            plan: subscription_plan.key(),
+            mint: mint.key(),
+            amount,
+            failed_payment_attempts: user_subscription.failed_payment_attempts,
+            status: user_subscription.status,
+        });
+
+        msg!(
+            "Autopay collection failed for subscription {}: attempt {} of {}, status {:?}",
+            user_subscription.get_subscription_id(),
+            user_subscription.failed_payment_attempts,
+            subscription_plan.max_retry_attempts,
+            user_subscription.status
+        );
+        return Ok(());
+    }
+
+    user_subscription.consume_autopay_allowance(amount)?;
+
+    // The bounty and protocol fee come out of the merchant's share, not on top of the price
+    let bounty = if keeper_token_account.is_some() { global_state.keeper_bounty(amount) } else { 0 };
+    let protocol_fee = global_state.protocol_fee(amount);
+    let merchant_amount = amount.checked_sub(bounty).unwrap().checked_sub(protocol_fee).unwrap();
+
+    // Remaining accounts carry the split recipients' token accounts, then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(remaining_accounts, subscription_plan.revenue_split.len())?;
+
+    let delegate_seeds: &[&[u8]] = &[b"autopay_delegate", &[autopay_delegate_bump]];
+    let signer_seeds = &[delegate_seeds];
+
+    let net_amount = transfer::pay_merchant(
+        &token_program.to_account_info(),
+        &user_token_account.to_account_info(),
+        mint,
+        subscription_plan,
+        &plan_token_account.to_account_info(),
+        escrow_vault.map(|vault| vault.to_account_info()),
+        &autopay_delegate.to_account_info(),
+        recipient_accounts,
+        extra_accounts,
+        merchant_amount,
+        signer_seeds,
+    )?;
+
+    if let Some(keeper_token_account) = keeper_token_account.filter(|_| bounty > 0) {
+        transfer::transfer_checked(
+            &token_program.to_account_info(),
+            &user_token_account.to_account_info(),
+            mint,
+            &keeper_token_account.to_account_info(),
+            &autopay_delegate.to_account_info(),
+            extra_accounts,
+            bounty,
+            signer_seeds,
+        )?;
+    }
+
+    if protocol_fee > 0 {
+        transfer::transfer_checked(
+            &token_program.to_account_info(),
+            &user_token_account.to_account_info(),
+            mint,
+            &treasury_token_account.to_account_info(),
+            &autopay_delegate.to_account_info(),
+            extra_accounts,
+            protocol_fee,
+            signer_seeds,
+        )?;
+    }
+
+    // Update subscription
+    let first_payment = user_subscription.status == SubscriptionStatus::Incomplete;
+    user_subscription.apply_credit(price);
+    user_subscription.consume_discount_period();
+    user_subscription.record_payment(clock.unix_timestamp);
+    if first_payment {
+        subscription_plan.add_subscriber()?;
+        merchant.current_subscribers = merchant.current_subscribers.checked_add(1).unwrap();
+    }
+
+    // Record the payment in the subscription's ledger
+    let mut entry = PaymentEntry::from_fields(
+        amount,
+        mint.key(),
+        mint.decimals,
+        merchant_amount.checked_sub(net_amount).unwrap(),
+        protocol_fee,
+        net_amount,
+        clock.unix_timestamp,
+        PaymentMethod::AutoPay,
+        PaymentStatus::Completed,
+    );
+    if subscription_plan.holds_in_escrow() {
+        entry.hold_in_escrow(net_amount, clock.unix_timestamp + subscription_plan.dispute_window);
+        user_subscription.escrowed_payments = user_subscription.escrowed_payments.checked_add(1).unwrap();
+    }
+    let sequence = payment_ledger.append(entry)?;
+
+    // Update global state
+    global_state.total_payments_processed = global_state.total_payments_processed.checked_add(1).unwrap();
+    global_state.total_volume = global_state.total_volume.checked_add(amount).unwrap();
+    merchant.record_revenue(amount);
+
+    fee_stats.record(amount, protocol_fee);
+
+    emit!(PaymentCollected {
+        subscription: user_subscription.key(),
+        user: user_subscription.user,
+        plan: subscription_plan.key(),
+        sequence,
+        mint: mint.key(),
+        amount,
+        protocol_fee,
+        keeper_bounty: bounty,
+        net_amount,
+        payment_method: PaymentMethod::AutoPay,
+        escrowed: subscription_plan.holds_in_escrow(),
+        next_payment_due: user_subscription.next_payment_due,
+    });
+
+    msg!(
+        "Autopay payment collected: {} of mint {} for subscription {} (keeper bounty {}, {} allowance left)",
+        subscription_plan.format_amount(amount),
+        mint.key(),
+        user_subscription.get_subscription_id(),
+        subscription_plan.format_amount(bounty),
+        subscription_plan.format_amount(user_subscription.autopay_allowance)
+    );
+    Ok(())
+}
+
+/// Accounts a prorated mid-period charge needs, borrowed from `change_plan`
+/// or `change_quantity`; the subscriber signs for it directly
+pub struct ProratedCharge<'a, 'info> {
+    pub user_subscription: &'a mut Account<'info, UserSubscription>,
+    pub subscription_plan: &'a Account<'info, SubscriptionPlan>,
+    pub merchant: &'a mut Account<'info, Merchant>,
+    pub payment_ledger: &'a mut Account<'info, PaymentLedger>,
+    pub user: &'a Signer<'info>,
+    pub user_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub plan_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub mint: &'a InterfaceAccount<'info, Mint>,
+    pub treasury_token_account: &'a InterfaceAccount<'info, TokenAccount>,
+    pub escrow_vault: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    pub fee_stats: &'a mut Account<'info, FeeStats>,
+    pub fee_stats_bump: u8,
+    pub global_state: &'a mut Account<'info, GlobalState>,
+    pub token_program: &'a Interface<'info, TokenInterface>,
+}
+
+/// Charge `amount`, already in the settlement mint, for a mid-period change.
+///
+/// It goes the way a manual payment does: protocol fee to the treasury, the
+/// rest to the plan's payees or escrow, and an entry in the payment ledger.
+/// The subscription's billing date and payment count are left alone.
+pub fn charge_proration<'info>(
+    accounts: ProratedCharge<'_, 'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let ProratedCharge {
+        user_subscription,
+        subscription_plan,
+        merchant,
+        payment_ledger,
+        user,
+        user_token_account,
+        plan_token_account,
+        mint,
+        treasury_token_account,
+        escrow_vault,
+        fee_stats,
+        fee_stats_bump,
+        global_state,
+        token_program,
+    } = accounts;
+    let clock = Clock::get()?;
+
+    // The protocol fee comes out of the merchant's share
+    let protocol_fee = global_state.protocol_fee(amount);
+    let merchant_amount = amount.checked_sub(protocol_fee).unwrap();
+
+    // Remaining accounts carry the split recipients' token accounts, then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(remaining_accounts, subscription_plan.revenue_split.len())?;
+
+    let net_amount = transfer::pay_merchant(
+        &token_program.to_account_info(),
+        &user_token_account.to_account_info(),
+        mint,
+        subscription_plan,
+        &plan_token_account.to_account_info(),
+        escrow_vault.map(|vault| vault.to_account_info()),
+        &user.to_account_info(),
+        recipient_accounts,
+        extra_accounts,
+        merchant_amount,
+        &[],
+    )?;
+
+    if protocol_fee > 0 {
+        transfer::transfer_checked(
+            &token_program.to_account_info(),
+            &user_token_account.to_account_info(),
+            mint,
+            &treasury_token_account.to_account_info(),
+            &user.to_account_info(),
+            extra_accounts,
+            protocol_fee,
+            &[],
+        )?;
+    }
+
+    let mut entry = PaymentEntry::from_fields(
+        amount,
+        mint.key(),
+        mint.decimals,
+        merchant_amount.checked_sub(net_amount).unwrap(),
+        protocol_fee,
+        net_amount,
+        clock.unix_timestamp,
+        PaymentMethod::Manual,
+        PaymentStatus::Completed,
+    );
+    if subscription_plan.holds_in_escrow() {
+        entry.hold_in_escrow(net_amount, clock.unix_timestamp + subscription_plan.dispute_window);
+        user_subscription.escrowed_payments = user_subscription.escrowed_payments.checked_add(1).unwrap();
+    }
+    let sequence = payment_ledger.append(entry)?;
+
+    global_state.total_payments_processed = global_state.total_payments_processed.checked_add(1).unwrap();
+    global_state.total_volume = global_state.total_volume.checked_add(amount).unwrap();
+    merchant.record_revenue(amount);
+
+    fee_stats.mint = mint.key();
+    fee_stats.bump = fee_stats_bump;
+    fee_stats.version = ACCOUNT_VERSION;
+    fee_stats.record(amount, protocol_fee);
+
+    emit!(PaymentCollected {
+        subscription: user_subscription.key(),
+        user: user_subscription.user,
+        plan: subscription_plan.key(),
+        sequence,
+        mint: mint.key(),
+        amount,
+        protocol_fee,
+        keeper_bounty: 0,
+        net_amount,
+        payment_method: PaymentMethod::Manual,
+        escrowed: subscription_plan.holds_in_escrow(),
+        next_payment_due: user_subscription.next_payment_due,
+    });
+    Ok(())
+}
@@ -26,9 +26,6 @@ pub enum LooprError {
     #[msg("Auto-pay is not enabled")]
     AutoPayNotEnabled,
     
-    #[msg("Thread authority mismatch")]
-    ThreadAuthorityMismatch,
-    
     #[msg("Plan ID too long")]
     PlanIdTooLong,
     
@@ -67,4 +64,247 @@ pub enum LooprError {
     
     #[msg("Intent ID too long")]
     IntentIdTooLong,
+    
+    #[msg("Autopay allowance must be greater than zero")]
+    InvalidAutopayAllowance,
+    
+    #[msg("Autopay allowance exhausted")]
+    AutopayAllowanceExceeded,
+    
+    #[msg("Token account does not match the autopay mandate")]
+    AutopayTokenAccountMismatch,
+    
+    #[msg("Keeper bounty exceeds the allowed maximum")]
+    KeeperBountyTooHigh,
+    
+    #[msg("Mint does not match the plan's accepted mint")]
+    InvalidMint,
+    
+    #[msg("Token accounts are required for SPL token plans")]
+    MissingTokenAccounts,
+    
+    #[msg("Invalid grace period")]
+    InvalidGracePeriod,
+    
+    #[msg("Subscription has ended")]
+    SubscriptionEnded,
+    
+    #[msg("Subscription is paused")]
+    SubscriptionPaused,
+    
+    #[msg("Subscription is not paused")]
+    SubscriptionNotPaused,
+    
+    #[msg("Pause limit for this year reached")]
+    PauseLimitReached,
+    
+    #[msg("Invalid pause duration")]
+    InvalidPauseDuration,
+    
+    #[msg("Cannot change to this plan")]
+    InvalidPlanChange,
+    
+    #[msg("Invalid trial duration")]
+    InvalidTrialDuration,
+    
+    #[msg("Coupon code too long")]
+    CouponCodeTooLong,
+    
+    #[msg("Invalid coupon discount")]
+    InvalidDiscount,
+    
+    #[msg("Coupon is not valid for this plan")]
+    InvalidCoupon,
+    
+    #[msg("Coupon has expired")]
+    CouponExpired,
+    
+    #[msg("Coupon redemption limit reached")]
+    CouponRedemptionLimitReached,
+    
+    #[msg("A coupon is already applied to this subscription")]
+    CouponAlreadyApplied,
+    
+    #[msg("Protocol fee exceeds the allowed maximum")]
+    ProtocolFeeTooHigh,
+    
+    #[msg("Account does not match the protocol treasury")]
+    InvalidTreasury,
+    
+    #[msg("Too many revenue split recipients")]
+    TooManySplitRecipients,
+    
+    #[msg("Invalid revenue split shares")]
+    InvalidSplitShares,
+    
+    #[msg("Account does not match the revenue split recipient")]
+    InvalidSplitRecipient,
+    
+    #[msg("Revenue split recipient accounts are missing")]
+    MissingSplitRecipients,
+    
+    #[msg("Plan is not metered")]
+    PlanNotMetered,
+    
+    #[msg("Invalid unit price")]
+    InvalidUnitPrice,
+    
+    #[msg("Usage must be at least one unit")]
+    InvalidUsage,
+    
+    #[msg("Invalid price tiers for the pricing model")]
+    InvalidPriceTiers,
+    
+    #[msg("Quantity must be at least one")]
+    InvalidQuantity,
+    
+    #[msg("Notice period cannot be negative")]
+    InvalidNoticePeriod,
+    
+    #[msg("Subscription is already on the plan's current price")]
+    NoPendingPriceChange,
+    
+    #[msg("Plan price has changed since it was reviewed")]
+    PriceVersionMismatch,
+    
+    #[msg("Only completed payments can be refunded")]
+    PaymentNotRefundable,
+    
+    #[msg("Refund must be more than zero and no more than the amount left to refund")]
+    InvalidRefundAmount,
+    
+    #[msg("Payment is held in escrow; settle it through a dispute")]
+    PaymentInEscrow,
+    
+    #[msg("Arbiter cannot be the default key or the plan authority")]
+    InvalidArbiter,
+    
+    #[msg("Dispute window must be between zero and 90 days")]
+    InvalidDisputeWindow,
+    
+    #[msg("Plan holds payments in escrow but no escrow vault was provided")]
+    MissingEscrowVault,
+    
+    #[msg("Payment is not held in escrow")]
+    PaymentNotEscrowed,
+    
+    #[msg("Payment is under dispute")]
+    PaymentDisputed,
+    
+    #[msg("Payment is not under dispute")]
+    PaymentNotDisputed,
+    
+    #[msg("Dispute window has closed")]
+    DisputeWindowClosed,
+    
+    #[msg("Escrow cannot be released until the dispute window closes")]
+    EscrowLocked,
+    
+    #[msg("Subscription is paid from a prepaid stream")]
+    SubscriptionStreaming,
+    
+    #[msg("Subscription is not paid from a prepaid stream")]
+    SubscriptionNotStreaming,
+    
+    #[msg("Stream has nothing accrued to withdraw")]
+    NothingAccrued,
+    
+    #[msg("Invalid USD pricing terms")]
+    InvalidUsdPricing,
+    
+    #[msg("Plan's pricing cannot change while it has subscribers")]
+    PlanHasSubscribers,
+    
+    #[msg("USD-priced plans need their price feed account")]
+    MissingPriceFeed,
+    
+    #[msg("Price feed account does not match the plan's")]
+    InvalidPriceFeed,
+    
+    #[msg("Price feed has not been updated recently enough")]
+    StalePrice,
+    
+    #[msg("Price feed confidence interval is too wide")]
+    PriceTooUncertain,
+    
+    #[msg("Price moved further than the subscriber's slippage cap")]
+    SlippageExceeded,
+    
+    #[msg("Slippage cap cannot exceed 10000 basis points")]
+    InvalidSlippage,
+    
+    #[msg("USD-priced plans cannot be paid from a stream")]
+    UsdPricedStream,
+    
+    #[msg("No authority transfer is pending for this signer")]
+    NoPendingAuthority,
+    
+    #[msg("Invalid authority")]
+    InvalidAuthority,
+    
+    #[msg("Merchant name too long")]
+    MerchantNameTooLong,
+    
+    #[msg("Logo URI too long")]
+    LogoUriTooLong,
+    
+    #[msg("Support contact too long")]
+    SupportContactTooLong,
+    
+    #[msg("Merchant is not active")]
+    MerchantNotActive,
+    
+    #[msg("Merchant does not match the plan's")]
+    InvalidMerchant,
+    
+    #[msg("Too many payout wallets")]
+    TooManyPayoutWallets,
+    
+    #[msg("Invalid payout wallet")]
+    InvalidPayoutWallet,
+    
+    #[msg("Subscription has payments still held in escrow")]
+    EscrowOutstanding,
+    
+    #[msg("Subscription still has a stream balance to settle")]
+    StreamNotSettled,
+    
+    #[msg("Subscription must be cancelled or expired before it is closed")]
+    SubscriptionStillOpen,
+    
+    #[msg("Plan must be deactivated with no subscribers before it is closed")]
+    PlanNotRetired,
+    
+    #[msg("Payment intent can still be paid")]
+    PaymentIntentStillOpen,
+    
+    #[msg("Account must be empty before it is closed")]
+    AccountNotEmpty,
+    
+    #[msg("Payment has rolled out of the subscription's ledger")]
+    PaymentNotFound,
+    
+    #[msg("Oldest ledger entry is still held in escrow and can't be overwritten")]
+    LedgerEntryEscrowed,
+    
+    #[msg("Account is not one of the program's or has an unrecognised layout")]
+    UnknownAccountLayout,
+    
+    #[msg("Account already has the current layout")]
+    AccountAlreadyMigrated,
+    
+    #[msg("Only forever coupons can discount a stream")]
+    CouponNotStreamable,
+    
+    #[msg("Migration needs the account's plan and, for a subscription, its new payment ledger")]
+    InvalidMigrationAccounts,
+    
+    #[msg("Quantity is above the most seats a subscription can hold")]
+    QuantityTooLarge,
+    
+    #[msg("Price is too large to charge")]
+    PriceOverflow,
+    
+    #[msg("Price oracle cannot be the default key")]
+    InvalidPriceOracle,
 }
\ No newline at end of file
@@ -0,0 +1,375 @@
+use anchor_lang::prelude::*;
+use crate::state::{DiscountType, MerchantStatus, PaymentMethod, PaymentStatus, SubscriptionPlan, SubscriptionStatus};
+
+#[event]
+pub struct GlobalStateInitialized {
+    pub global_state: Pubkey,
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub arbiter: Pubkey,
+    pub price_oracle: Pubkey,
+    pub keeper_bounty_bps: u16,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct GlobalConfigUpdated {
+    pub global_state: Pubkey,
+    pub treasury: Pubkey,
+    pub arbiter: Pubkey,
+    pub price_oracle: Pubkey,
+    pub keeper_bounty_bps: u16,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct ProgramPauseChanged {
+    pub global_state: Pubkey,
+    pub is_paused: bool,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub global_state: Pubkey,
+    pub authority: Pubkey,
+    /// `None` withdraws an earlier proposal
+    pub pending_authority: Option<Pubkey>,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub global_state: Pubkey,
+    pub previous_authority: Pubkey,
+    pub authority: Pubkey,
+}
+
+/// An account upgraded in place to the current layout
+#[event]
+pub struct AccountMigrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+    /// Rent the payer added for the account's new size
+    pub rent_paid: u64,
+}
+
+#[event]
+pub struct MerchantRegistered {
+    pub merchant: Pubkey,
+    pub authority: Pubkey,
+    pub name: String,
+}
+
+#[event]
+pub struct MerchantUpdated {
+    pub merchant: Pubkey,
+    pub name: String,
+    pub logo_uri: String,
+    pub support_contact: String,
+}
+
+#[event]
+pub struct PayoutWalletSet {
+    pub merchant: Pubkey,
+    pub mint: Pubkey,
+    /// `None` sends the mint's payouts back to the merchant authority
+    pub wallet: Option<Pubkey>,
+}
+
+#[event]
+pub struct MerchantStatusChanged {
+    pub merchant: Pubkey,
+    pub status: MerchantStatus,
+}
+
+#[event]
+pub struct PlanCreated {
+    pub plan: Pubkey,
+    pub plan_id: String,
+    pub merchant: Pubkey,
+    pub authority: Pubkey,
+    pub accepted_mint: Pubkey,
+    pub mint_decimals: u8,
+    pub price_per_period: u64,
+    pub period_duration: i64,
+}
+
+/// Emitted by every instruction that edits a plan, with its pricing after the edit
+#[event]
+pub struct PlanUpdated {
+    pub plan: Pubkey,
+    pub plan_id: String,
+    pub price_per_period: u64,
+    pub period_duration: i64,
+    pub price_version: u32,
+    pub is_active: bool,
+}
+
+impl PlanUpdated {
+    pub fn new(plan: Pubkey, subscription_plan: &SubscriptionPlan) -> Self {
+        Self {
+            plan,
+            plan_id: subscription_plan.get_plan_id(),
+            price_per_period: subscription_plan.price_per_period,
+            period_duration: subscription_plan.period_duration,
+            price_version: subscription_plan.price_version,
+            is_active: subscription_plan.is_active,
+        }
+    }
+}
+
+#[event]
+pub struct PlanClosed {
+    pub plan: Pubkey,
+    pub plan_id: String,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct CouponCreated {
+    pub coupon: Pubkey,
+    pub code: String,
+    pub authority: Pubkey,
+    pub discount_type: DiscountType,
+    pub discount_value: u64,
+    pub plan: Option<Pubkey>,
+    pub mint: Pubkey,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub subscription: Pubkey,
+    pub subscription_id: String,
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub status: SubscriptionStatus,
+    pub next_payment_due: i64,
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub mint: Pubkey,
+    /// Unspent stream balance returned to the subscriber
+    pub refunded_amount: u64,
+}
+
+#[event]
+pub struct SubscriptionPaused {
+    pub subscription: Pubkey,
+    pub paused_at: i64,
+    /// When billing resumes on its own if the subscriber doesn't resume first
+    pub resumes_at: i64,
+}
+
+#[event]
+pub struct SubscriptionResumed {
+    pub subscription: Pubkey,
+    pub next_payment_due: i64,
+}
+
+#[event]
+pub struct SubscriptionStatusChanged {
+    pub subscription: Pubkey,
+    pub previous_status: SubscriptionStatus,
+    pub status: SubscriptionStatus,
+}
+
+#[event]
+pub struct SubscriptionClosed {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+}
+
+/// A subscription moved from its legacy id-keyed address to its canonical one
+#[event]
+pub struct SubscriptionMigrated {
+    pub subscription: Pubkey,
+    pub previous_subscription: Pubkey,
+    pub user: Pubkey,
+    pub plan: Pubkey,
+}
+
+#[event]
+pub struct PlanChanged {
+    /// The subscription re-keyed under the new plan
+    pub subscription: Pubkey,
+    pub previous_subscription: Pubkey,
+    pub old_plan: Pubkey,
+    pub new_plan: Pubkey,
+    pub mint: Pubkey,
+    /// Prorated difference charged immediately on an upgrade
+    pub charged_amount: u64,
+    /// Credit carried into the next payments on a downgrade
+    pub credit_balance: u64,
+}
+
+#[event]
+pub struct QuantityChanged {
+    pub subscription: Pubkey,
+    pub previous_quantity: u32,
+    pub quantity: u32,
+    pub mint: Pubkey,
+    pub charged_amount: u64,
+    pub credit_balance: u64,
+}
+
+#[event]
+pub struct PriceChangeAccepted {
+    pub subscription: Pubkey,
+    pub plan: Pubkey,
+    pub price_version: u32,
+}
+
+#[event]
+pub struct CouponApplied {
+    pub subscription: Pubkey,
+    pub coupon: Pubkey,
+    pub code: String,
+}
+
+#[event]
+pub struct UsageReported {
+    pub subscription: Pubkey,
+    pub report_id: u64,
+    pub units: u64,
+    pub period_usage: u64,
+}
+
+#[event]
+pub struct AutopayEnabled {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub token_account: Pubkey,
+    pub allowance: u64,
+}
+
+#[event]
+pub struct AutopayDisabled {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct MaxSlippageSet {
+    pub subscription: Pubkey,
+    pub max_slippage_bps: u16,
+}
+
+#[event]
+pub struct PaymentCollected {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    /// Position of the payment in the subscription's ledger
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub protocol_fee: u64,
+    /// Paid to the keeper that collected the payment, if any
+    pub keeper_bounty: u64,
+    /// Reached the merchant, or is held in escrow for them
+    pub net_amount: u64,
+    pub payment_method: PaymentMethod,
+    pub escrowed: bool,
+    pub next_payment_due: i64,
+}
+
+#[event]
+pub struct PaymentFailed {
+    pub subscription: Pubkey,
+    pub user: Pubkey,
+    pub plan: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub failed_payment_attempts: u8,
+    pub status: SubscriptionStatus,
+}
+
+#[event]
+pub struct PaymentRefunded {
+    pub subscription: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// Refunded so far across every refund of this payment
+    pub refunded_amount: u64,
+    pub status: PaymentStatus,
+}
+
+#[event]
+pub struct DisputeOpened {
+    pub subscription: Pubkey,
+    pub sequence: u64,
+    pub user: Pubkey,
+}
+
+#[event]
+pub struct EscrowReleased {
+    pub subscription: Pubkey,
+    pub sequence: u64,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct DisputeResolved {
+    pub subscription: Pubkey,
+    pub sequence: u64,
+    pub arbiter: Pubkey,
+    pub mint: Pubkey,
+    pub refunded_amount: u64,
+    pub released_amount: u64,
+}
+
+#[event]
+pub struct StreamDeposited {
+    pub subscription: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// When the vault's balance runs out at the subscription's current price
+    pub paid_through: i64,
+}
+
+#[event]
+pub struct StreamWithdrawn {
+    pub subscription: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct IntentCreated {
+    pub intent: Pubkey,
+    pub intent_id: String,
+    pub plan: Pubkey,
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct IntentFulfilled {
+    pub intent: Pubkey,
+    pub intent_id: String,
+    pub payer: Pubkey,
+    pub subscription: Pubkey,
+    /// Charged in the plan's settlement mint; 0 if the subscription opened with a trial
+    pub amount: u64,
+}
+
+#[event]
+pub struct PaymentConfirmed {
+    pub intent: Pubkey,
+    pub subscription: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct IntentClosed {
+    pub intent: Pubkey,
+    pub authority: Pubkey,
+}
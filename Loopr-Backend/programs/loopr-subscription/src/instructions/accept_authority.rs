@@ -0,0 +1,32 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        constraint = global_state.pending_authority == Some(new_authority.key()) @ LooprError::NoPendingAuthority
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub new_authority: Signer<'info>,
+}
+
+pub fn handler(ctx: Context<AcceptAuthority>) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    let previous_authority = global_state.authority;
+    global_state.authority = ctx.accounts.new_authority.key();
+    global_state.pending_authority = None;
+
+    emit!(AuthorityTransferred {
+        global_state: global_state.key(),
+        previous_authority,
+        authority: global_state.authority,
+    });
+
+    msg!("Authority transferred from {} to {}", previous_authority, global_state.authority);
+    
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct AcceptPriceChange<'info> {
+    #[account(
+        mut,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.key() == user_subscription.subscription_plan
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<AcceptPriceChange>, price_version: u32) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &ctx.accounts.subscription_plan;
+
+    require!(
+        user_subscription.price_version != subscription_plan.price_version,
+        LooprError::NoPendingPriceChange
+    );
+    // Consent covers the exact version the subscriber saw, not a later one
+    require!(
+        price_version == subscription_plan.price_version,
+        LooprError::PriceVersionMismatch
+    );
+
+    // Consenting waives the notice period: the new price applies from the next payment
+    user_subscription.pin_price(subscription_plan);
+    user_subscription.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PriceChangeAccepted {
+        subscription: user_subscription.key(),
+        plan: subscription_plan.key(),
+        price_version,
+    });
+
+    msg!(
+        "Subscription {} accepted price version {}: {} every {}s",
+        user_subscription.get_subscription_id(),
+        price_version,
+        subscription_plan.format_amount(user_subscription.price_per_period),
+        user_subscription.period_duration
+    );
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct ApplyCoupon<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded,
+        constraint = user_subscription.discount.is_none() @ LooprError::CouponAlreadyApplied,
+        // A stream has accrued at its current price up to now, which a new
+        // discount would silently reprice
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    #[account(
+        mut,
+        seeds = [b"coupon", coupon.authority.as_ref(), coupon.get_code().as_bytes()],
+        bump = coupon.bump,
+        constraint = coupon.authority == subscription_plan.authority @ LooprError::InvalidCoupon
+    )]
+    pub coupon: Account<'info, Coupon>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<ApplyCoupon>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let coupon = &mut ctx.accounts.coupon;
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let clock = Clock::get()?;
+
+    coupon.check_redeemable(&ctx.accounts.subscription_plan, clock.unix_timestamp)?;
+    coupon.times_redeemed = coupon.times_redeemed.checked_add(1).unwrap();
+
+    user_subscription.discount = Some(coupon.to_applied(coupon.key()));
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    emit!(CouponApplied {
+        subscription: user_subscription.key(),
+        coupon: coupon.key(),
+        code: coupon.get_code(),
+    });
+
+    msg!(
+        "Coupon {} applied to subscription {}: next payment {}",
+        coupon.get_code(),
+        user_subscription.get_subscription_id(),
+        ctx.accounts.subscription_plan.format_amount(user_subscription.effective_price(
+            ctx.accounts.subscription_plan.period_price(user_subscription)?
+        ))
+    );
+    
+    Ok(())
+}
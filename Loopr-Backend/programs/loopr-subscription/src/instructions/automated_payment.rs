@@ -1,33 +1,40 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-use crate::{state::*, errors::*};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, collection::{self, AutopayCollection}, pda::*};
 
 #[derive(Accounts)]
 pub struct AutomatedPayment<'info> {
     #[account(
         mut,
-        seeds = [b"user_subscription", user_subscription.user.as_ref(), user_subscription.subscription_plan.as_ref()],
+        seeds = [USER_SUBSCRIPTION_SEED, user_subscription.user.as_ref(), user_subscription.subscription_plan.as_ref()],
         bump = user_subscription.bump,
-        constraint = user_subscription.is_active @ LooprError::SubscriptionNotActive,
-        constraint = user_subscription.auto_pay_enabled @ LooprError::AutoPayNotEnabled
+        constraint = user_subscription.status.is_collectable() @ LooprError::SubscriptionNotActive,
+        constraint = user_subscription.auto_pay_enabled @ LooprError::AutoPayNotEnabled,
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming
     )]
     pub user_subscription: Account<'info, UserSubscription>,
 
     #[account(
-        seeds = [b"subscription_plan", subscription_plan.plan_id.as_bytes()],
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
         bump = subscription_plan.bump,
         constraint = subscription_plan.is_active @ LooprError::PlanNotActive
     )]
     pub subscription_plan: Account<'info, SubscriptionPlan>,
 
     #[account(
-        init,
-        payer = payer,
-        space = PaymentRecord::LEN,
-        seeds = [b"payment_record", user_subscription.key().as_ref(), &Clock::get()?.unix_timestamp.to_le_bytes()],
-        bump
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
     )]
-    pub payment_record: Account<'info, PaymentRecord>,
+    pub payment_ledger: Account<'info, PaymentLedger>,
 
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -35,19 +42,57 @@ pub struct AutomatedPayment<'info> {
     #[account(
         mut,
         constraint = user_token_account.owner == user_subscription.user,
-        constraint = user_token_account.mint == native_mint.key()
+        constraint = user_token_account.mint == mint.key(),
+        constraint = user_subscription.autopay_token_account == Some(user_token_account.key()) @ LooprError::AutopayTokenAccountMismatch
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == mint.key()
+    )]
+    pub plan_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
+    /// Treasury's account in the payment mint, receiving the protocol fee
     #[account(
         mut,
-        constraint = plan_token_account.owner == subscription_plan.authority,
-        constraint = plan_token_account.mint == native_mint.key()
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == mint.key() @ LooprError::InvalidMint
     )]
-    pub plan_token_account: Account<'info, TokenAccount>,
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    /// CHECK: This is the native mint for SOL
-    pub native_mint: AccountInfo<'info>,
+    /// Plan's escrow vault; required when the plan holds payments for a dispute window
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Pyth-format price account, checked against the plan's USD pricing; only needed for USD-priced plans
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", mint.key().as_ref()],
+        bump
+    )]
+    pub fee_stats: Account<'info, FeeStats>,
+
+    /// CHECK: Program-owned PDA the user approved as delegate on `user_token_account`
+    #[account(
+        seeds = [b"autopay_delegate"],
+        bump
+    )]
+    pub autopay_delegate: UncheckedAccount<'info>,
 
     #[account(
         mut,
@@ -56,58 +101,33 @@ pub struct AutomatedPayment<'info> {
     )]
     pub global_state: Account<'info, GlobalState>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<AutomatedPayment>) -> Result<()> {
-    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
-    
-    let user_subscription = &mut ctx.accounts.user_subscription;
-    let subscription_plan = &ctx.accounts.subscription_plan;
-    let clock = Clock::get()?;
-
-    // Check if payment is due
-    require!(
-        clock.unix_timestamp >= user_subscription.next_payment_due,
-        LooprError::PaymentNotDue
-    );
-
-    let amount = subscription_plan.price_per_period;
-
-    // Transfer tokens from user to plan authority
-    let transfer_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.plan_token_account.to_account_info(),
-            authority: ctx.accounts.thread.to_account_info(),
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, AutomatedPayment<'info>>) -> Result<()> {
+    let accounts = ctx.accounts;
+    collection::collect(
+        AutopayCollection {
+            user_subscription: &mut accounts.user_subscription,
+            subscription_plan: &mut accounts.subscription_plan,
+            merchant: &mut accounts.merchant,
+            payment_ledger: &mut accounts.payment_ledger,
+            user_token_account: &accounts.user_token_account,
+            plan_token_account: &accounts.plan_token_account,
+            mint: &accounts.mint,
+            treasury_token_account: &accounts.treasury_token_account,
+            escrow_vault: accounts.escrow_vault.as_ref(),
+            price_feed: accounts.price_feed.as_ref(),
+            fee_stats: &mut accounts.fee_stats,
+            fee_stats_bump: ctx.bumps.fee_stats,
+            autopay_delegate: &accounts.autopay_delegate,
+            autopay_delegate_bump: ctx.bumps.autopay_delegate,
+            global_state: &mut accounts.global_state,
+            token_program: &accounts.token_program,
+            // The payer cranks its own collection, so no bounty is paid
+            keeper_token_account: None,
         },
-    );
-    token::transfer(transfer_ctx, amount)?;
-
-    // Update subscription
-    user_subscription.last_payment_date = Some(clock.unix_timestamp);
-    user_subscription.next_payment_due = clock.unix_timestamp + subscription_plan.period_duration;
-    user_subscription.total_payments_made = user_subscription.total_payments_made.checked_add(1).unwrap();
-    user_subscription.updated_at = clock.unix_timestamp;
-
-    // Create payment record
-    let payment_record = &mut ctx.accounts.payment_record;
-    payment_record.user = user_subscription.user;
-    payment_record.subscription = user_subscription.key();
-    payment_record.amount = amount;
-    payment_record.payment_date = clock.unix_timestamp;
-    payment_record.transaction_signature = "".to_string();
-    payment_record.payment_method = PaymentMethod::AutoPay;
-    payment_record.status = PaymentStatus::Completed;
-    payment_record.bump = ctx.bumps.payment_record;
-
-    // Update global state
-    let global_state = &mut ctx.accounts.global_state;
-    global_state.total_payments_processed = global_state.total_payments_processed.checked_add(1).unwrap();
-    global_state.total_volume = global_state.total_volume.checked_add(amount).unwrap();
-
-    msg!("Automated payment processed: {} SOL for subscription {}", amount, user_subscription.subscription_id);
-    Ok(())
-}
\ No newline at end of file
+        ctx.remaining_accounts,
+    )
+}
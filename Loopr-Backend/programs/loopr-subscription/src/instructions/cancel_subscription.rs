@@ -1 +1,175 @@
-use anchor_lang::prelude::*;use crate::{state::*, errors::*};#[derive(Accounts)]pub struct CancelSubscription<'info> {    #[account(        mut,        seeds = [b"user_subscription", user.key().as_ref(), user_subscription.subscription_plan.as_ref()],        bump = user_subscription.bump,        constraint = user_subscription.user == user.key(),        constraint = user_subscription.is_active @ LooprError::SubscriptionNotActive    )]    pub user_subscription: Account<'info, UserSubscription>,        #[account(        mut,        seeds = [b"subscription_plan", subscription_plan.plan_id.as_bytes()],        bump = subscription_plan.bump    )]    pub subscription_plan: Account<'info, SubscriptionPlan>,        #[account(mut)]    pub user: Signer<'info>,        #[account(        seeds = [b"global_state"],        bump = global_state.bump    )]    pub global_state: Account<'info, GlobalState>,}pub fn handler(ctx: Context<CancelSubscription>) -> Result<()> {    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);    let user_subscription = &mut ctx.accounts.user_subscription;    let subscription_plan = &mut ctx.accounts.subscription_plan;    let clock = Clock::get()?;    // Deactivate subscription    user_subscription.is_active = false;    user_subscription.auto_pay_enabled = false;    user_subscription.updated_at = clock.unix_timestamp;    // Update subscription plan count    subscription_plan.current_subscribers = subscription_plan.current_subscribers.checked_sub(1).unwrap();    msg!("Subscription cancelled: {}", user_subscription.subscription_id);    Ok(())}
\ No newline at end of file
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    /// Accounts below are only needed to refund a streaming subscription's vault
+    /// or to release an autopay approval
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub user_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// CHECK: Program-owned PDA that owns every stream vault
+    #[account(
+        seeds = [b"stream_authority"],
+        bump
+    )]
+    pub stream_authority: Option<UncheckedAccount<'info>>,
+    
+    #[account(
+        mut,
+        seeds = [b"stream_vault", user_subscription.key().as_ref()],
+        bump
+    )]
+    pub stream_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// CHECK: Program-owned PDA holding the user's autopay approval
+    #[account(
+        seeds = [b"autopay_delegate"],
+        bump
+    )]
+    pub autopay_delegate: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, CancelSubscription<'info>>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    // Close out any pause first so the paused time still extends the term
+    user_subscription.resume(clock.unix_timestamp, subscription_plan);
+
+    // A stream stops on cancel: the merchant keeps what has accrued and the
+    // rest of the vault goes straight back to the subscriber
+    let mut refunded_amount = 0;
+    if let Some(mut stream) = user_subscription.stream {
+        let (Some(mint), Some(user_token_account), Some(stream_authority), Some(stream_vault), Some(token_program)) = (
+            &ctx.accounts.mint,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.stream_authority,
+            &ctx.accounts.stream_vault,
+            &ctx.accounts.token_program,
+        ) else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+
+        let price = user_subscription.stream_price(subscription_plan)?;
+        stream.accrue(clock.unix_timestamp, price, user_subscription.period_duration, stream_vault.amount);
+        let refund = stream_vault.amount.checked_sub(stream.accrued).unwrap();
+
+        if refund > 0 {
+            let stream_seeds: &[&[u8]] = &[b"stream_authority", &[ctx.bumps.stream_authority]];
+            transfer::transfer_checked(
+                &token_program.to_account_info(),
+                &stream_vault.to_account_info(),
+                mint,
+                &user_token_account.to_account_info(),
+                &stream_authority.to_account_info(),
+                ctx.remaining_accounts,
+                refund,
+                &[stream_seeds],
+            )?;
+        }
+
+        user_subscription.stream = Some(stream);
+        user_subscription.next_payment_due = clock.unix_timestamp;
+        refunded_amount = refund;
+
+        msg!("Refunded {} unstreamed", subscription_plan.format_amount(refund));
+    }
+
+    // Cancelling ends the mandate, so hand back its share of the delegate's
+    // approval instead of leaving the subscriber's tokens pullable
+    if user_subscription.auto_pay_enabled {
+        let (Some(user_token_account), Some(autopay_delegate), Some(token_program)) = (
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.autopay_delegate,
+            &ctx.accounts.token_program,
+        ) else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+        require!(
+            user_subscription.autopay_token_account == Some(user_token_account.key()),
+            LooprError::AutopayTokenAccountMismatch
+        );
+
+        transfer::release_autopay_approval(
+            &token_program.to_account_info(),
+            user_token_account,
+            &autopay_delegate.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            user_subscription.autopay_allowance,
+        )?;
+    }
+
+    // An incomplete subscription never took a place on the plan
+    let counted = user_subscription.status != SubscriptionStatus::Incomplete;
+
+    // Access runs until next_payment_due, after which refresh_subscription_status expires it
+    user_subscription.status = SubscriptionStatus::Cancelled;
+    user_subscription.clear_autopay();
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    // Update subscription plan count
+    if counted {
+        subscription_plan.current_subscribers = subscription_plan.current_subscribers.saturating_sub(1);
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.current_subscribers = merchant.current_subscribers.saturating_sub(1);
+    }
+    subscription_plan.updated_at = clock.unix_timestamp;
+
+    emit!(SubscriptionCancelled {
+        subscription: user_subscription.key(),
+        user: user_subscription.user,
+        plan: subscription_plan.key(),
+        mint: subscription_plan.settlement_mint(),
+        refunded_amount,
+    });
+
+    msg!("Subscription cancelled: {}", user_subscription.get_subscription_id());
+    
+    Ok(())
+}
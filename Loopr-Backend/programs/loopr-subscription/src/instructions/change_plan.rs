@@ -0,0 +1,281 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, oracle, events::*, pda::*, collection::{self, ProratedCharge}};
+
+#[derive(Accounts)]
+pub struct ChangePlan<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), current_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming,
+        constraint = user_subscription.escrowed_payments == 0 @ LooprError::EscrowOutstanding
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    /// The subscription carried over, re-keyed to the new plan
+    #[account(
+        init,
+        payer = user,
+        space = UserSubscription::LEN,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), new_plan.key().as_ref()],
+        bump
+    )]
+    pub new_user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+
+    /// The payment history carried over to the re-keyed subscription
+    #[account(
+        init,
+        payer = user,
+        space = PaymentLedger::LEN,
+        seeds = [PAYMENT_LEDGER_SEED, new_user_subscription.key().as_ref()],
+        bump
+    )]
+    pub new_payment_ledger: Account<'info, PaymentLedger>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", current_plan.get_plan_id().as_bytes()],
+        bump = current_plan.bump
+    )]
+    pub current_plan: Account<'info, SubscriptionPlan>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", new_plan.get_plan_id().as_bytes()],
+        bump = new_plan.bump,
+        constraint = new_plan.is_active @ LooprError::PlanNotActive,
+        constraint = new_plan.key() != current_plan.key() @ LooprError::InvalidPlanChange,
+        constraint = new_plan.accepted_mint == current_plan.accepted_mint @ LooprError::InvalidMint,
+        constraint = new_plan.is_usd_priced() == current_plan.is_usd_priced() @ LooprError::InvalidPlanChange,
+        constraint = new_plan.merchant == current_plan.merchant @ LooprError::InvalidPlanChange
+    )]
+    pub new_plan: Account<'info, SubscriptionPlan>,
+
+    /// Both plans' merchant; a change never moves a subscriber to another merchant
+    #[account(
+        mut,
+        address = new_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Token accounts below are only needed when the change costs more than the credit
+    #[account(
+        constraint = mint.key() == new_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == new_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub user_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&new_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == new_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub plan_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Treasury's account in the payment mint, receiving the protocol fee on a charge
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == new_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// New plan's escrow vault; required for a charge when the plan holds payments for a dispute window
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", new_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", new_plan.settlement_mint().as_ref()],
+        bump
+    )]
+    pub fee_stats: Option<Account<'info, FeeStats>>,
+
+    /// CHECK: Pyth-format price account, checked against the plan's USD pricing; only needed for USD-priced plans
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, ChangePlan<'info>>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let current_plan = &mut ctx.accounts.current_plan;
+    let new_plan = &mut ctx.accounts.new_plan;
+    let clock = Clock::get()?;
+
+    // Only a paid-up subscription has unused time to prorate
+    user_subscription.refresh_status(clock.unix_timestamp, current_plan);
+    require!(
+        user_subscription.status == SubscriptionStatus::Active,
+        LooprError::SubscriptionNotActive
+    );
+
+    if let Some(max_subscribers) = new_plan.max_subscribers {
+        require!(
+            new_plan.current_subscribers < max_subscribers,
+            LooprError::MaxSubscribersReached
+        );
+    }
+
+    // A coupon only carries over to a plan it could have been redeemed against
+    let keeps_discount = new_plan.authority == current_plan.authority
+        && user_subscription.discount.as_ref().map_or(true, |discount| discount.covers(new_plan));
+    let quantity = user_subscription.quantity;
+    let new_price = if keeps_discount {
+        user_subscription.effective_price(new_plan.seat_price(quantity)?)
+    } else {
+        new_plan.seat_price(quantity)?
+    };
+
+    // The billing date is kept: the unused part of the current period is
+    // credited and the same stretch of time on the new plan is charged
+    let remaining = user_subscription.next_payment_due - clock.unix_timestamp;
+    let unused_credit = user_subscription.prorate(
+        user_subscription.effective_price(
+            current_plan.seat_price_at(user_subscription.price_per_period, quantity)?,
+        ),
+        remaining,
+    );
+    let new_cost = new_plan.prorate(new_price, remaining);
+
+    let available_credit = user_subscription.credit_balance.checked_add(unused_credit).unwrap();
+    let charge = new_cost.saturating_sub(available_credit);
+    let credit_balance = available_credit.saturating_sub(new_cost);
+
+    // Settle the charge in the plan's mint before anything moves
+    let charged_amount = if charge > 0 {
+        oracle::to_settlement_amount(
+            new_plan,
+            ctx.accounts.price_feed.as_ref().map(|feed| feed.as_ref()),
+            &ctx.accounts.global_state.price_oracle,
+            user_subscription.max_slippage_bps,
+            charge,
+            clock.unix_timestamp,
+        )?
+    } else {
+        0
+    };
+
+    // Carry the subscription, its history and any autopay mandate over to the new plan
+    let mut migrated = (**user_subscription).clone();
+    migrated.subscription_plan = new_plan.key();
+    migrated.pin_price(new_plan);
+    migrated.credit_balance = credit_balance;
+    if !keeps_discount {
+        migrated.discount = None;
+    }
+    migrated.updated_at = clock.unix_timestamp;
+    migrated.bump = ctx.bumps.new_user_subscription;
+    ctx.accounts.new_user_subscription.set_inner(migrated);
+    let ledger = ctx
+        .accounts
+        .payment_ledger
+        .rekeyed(ctx.accounts.new_user_subscription.key(), ctx.bumps.new_payment_ledger);
+    ctx.accounts.new_payment_ledger.set_inner(ledger);
+
+    // The charge lands in the carried-over ledger, against the new plan
+    if charged_amount > 0 {
+        let (
+            Some(mint),
+            Some(user_token_account),
+            Some(plan_token_account),
+            Some(treasury_token_account),
+            Some(fee_stats),
+            Some(token_program),
+        ) = (
+            &ctx.accounts.mint,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.plan_token_account,
+            &ctx.accounts.treasury_token_account,
+            ctx.accounts.fee_stats.as_mut(),
+            &ctx.accounts.token_program,
+        ) else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+
+        collection::charge_proration(
+            ProratedCharge {
+                user_subscription: &mut ctx.accounts.new_user_subscription,
+                subscription_plan: new_plan,
+                merchant: &mut ctx.accounts.merchant,
+                payment_ledger: &mut ctx.accounts.new_payment_ledger,
+                user: &ctx.accounts.user,
+                user_token_account,
+                plan_token_account,
+                mint,
+                treasury_token_account,
+                escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+                fee_stats,
+                fee_stats_bump: ctx.bumps.fee_stats,
+                global_state: &mut ctx.accounts.global_state,
+                token_program,
+            },
+            ctx.remaining_accounts,
+            charged_amount,
+        )?;
+    }
+
+    // Move the subscriber between plans
+    current_plan.current_subscribers = current_plan.current_subscribers.saturating_sub(1);
+    current_plan.updated_at = clock.unix_timestamp;
+    new_plan.current_subscribers = new_plan.current_subscribers.checked_add(1).unwrap();
+    new_plan.updated_at = clock.unix_timestamp;
+
+    emit!(PlanChanged {
+        subscription: ctx.accounts.new_user_subscription.key(),
+        previous_subscription: user_subscription.key(),
+        old_plan: current_plan.key(),
+        new_plan: new_plan.key(),
+        mint: new_plan.settlement_mint(),
+        charged_amount,
+        credit_balance,
+    });
+
+    msg!(
+        "Subscription {} moved from plan {} to {}: charged {}, credit balance {}",
+        user_subscription.get_subscription_id(),
+        current_plan.get_plan_id(),
+        new_plan.get_plan_id(),
+        new_plan.format_amount(charged_amount),
+        new_plan.format_amount(credit_balance)
+    );
+
+    Ok(())
+}
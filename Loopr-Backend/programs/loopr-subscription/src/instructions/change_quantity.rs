@@ -0,0 +1,211 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, oracle, events::*, pda::*, collection::{self, ProratedCharge}};
+
+#[derive(Accounts)]
+pub struct ChangeQuantity<'info> {
+    #[account(
+        mut,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.key() == user_subscription.subscription_plan,
+        constraint = subscription_plan.is_active @ LooprError::PlanNotActive
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Token accounts below are only needed when adding seats costs more than the credit
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub user_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub plan_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Treasury's account in the payment mint, receiving the protocol fee on a charge
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Plan's escrow vault; required for a charge when the plan holds payments for a dispute window
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", subscription_plan.settlement_mint().as_ref()],
+        bump
+    )]
+    pub fee_stats: Option<Account<'info, FeeStats>>,
+
+    /// CHECK: Pyth-format price account, checked against the plan's USD pricing; only needed for USD-priced plans
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ChangeQuantity<'info>>,
+    quantity: u32,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(quantity > 0, LooprError::InvalidQuantity);
+    require!(quantity <= SubscriptionPlan::MAX_QUANTITY, LooprError::QuantityTooLarge);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    // Only a paid-up subscription has unused time to prorate
+    user_subscription.refresh_status(clock.unix_timestamp, subscription_plan);
+    require!(
+        user_subscription.status == SubscriptionStatus::Active,
+        LooprError::SubscriptionNotActive
+    );
+
+    // As with plan changes, the billing date is kept: the unused part of the
+    // period is credited at the old quantity and charged at the new one
+    let remaining = user_subscription.next_payment_due - clock.unix_timestamp;
+    let price_per_period = user_subscription.price_per_period;
+    let unused_credit = user_subscription.prorate(
+        user_subscription.effective_price(
+            subscription_plan.seat_price_at(price_per_period, user_subscription.quantity)?,
+        ),
+        remaining,
+    );
+    let new_cost = user_subscription.prorate(
+        user_subscription.effective_price(subscription_plan.seat_price_at(price_per_period, quantity)?),
+        remaining,
+    );
+
+    let available_credit = user_subscription.credit_balance.checked_add(unused_credit).unwrap();
+    let charge = new_cost.saturating_sub(available_credit);
+    let credit_balance = available_credit.saturating_sub(new_cost);
+
+    let mut charged_amount = 0;
+    if charge > 0 {
+        let (
+            Some(mint),
+            Some(user_token_account),
+            Some(plan_token_account),
+            Some(treasury_token_account),
+            Some(fee_stats),
+            Some(token_program),
+        ) = (
+            &ctx.accounts.mint,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.plan_token_account,
+            &ctx.accounts.treasury_token_account,
+            ctx.accounts.fee_stats.as_mut(),
+            &ctx.accounts.token_program,
+        ) else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+        let charge = oracle::to_settlement_amount(
+            subscription_plan,
+            ctx.accounts.price_feed.as_ref().map(|feed| feed.as_ref()),
+            &ctx.accounts.global_state.price_oracle,
+            user_subscription.max_slippage_bps,
+            charge,
+            clock.unix_timestamp,
+        )?;
+
+        collection::charge_proration(
+            ProratedCharge {
+                user_subscription,
+                subscription_plan,
+                merchant: &mut ctx.accounts.merchant,
+                payment_ledger: &mut ctx.accounts.payment_ledger,
+                user: &ctx.accounts.user,
+                user_token_account,
+                plan_token_account,
+                mint,
+                treasury_token_account,
+                escrow_vault: ctx.accounts.escrow_vault.as_ref(),
+                fee_stats,
+                fee_stats_bump: ctx.bumps.fee_stats,
+                global_state: &mut ctx.accounts.global_state,
+                token_program,
+            },
+            ctx.remaining_accounts,
+            charge,
+        )?;
+        charged_amount = charge;
+    }
+
+    let previous_quantity = user_subscription.quantity;
+    user_subscription.quantity = quantity;
+    user_subscription.credit_balance = credit_balance;
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    emit!(QuantityChanged {
+        subscription: user_subscription.key(),
+        previous_quantity,
+        quantity,
+        mint: subscription_plan.settlement_mint(),
+        charged_amount,
+        credit_balance,
+    });
+
+    msg!(
+        "Subscription {} changed from {} to {} seats: charged {}, credit balance {}",
+        user_subscription.get_subscription_id(),
+        previous_quantity,
+        quantity,
+        subscription_plan.format_amount(charged_amount),
+        subscription_plan.format_amount(credit_balance)
+    );
+
+    Ok(())
+}
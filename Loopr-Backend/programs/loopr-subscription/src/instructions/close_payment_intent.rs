@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct ClosePaymentIntent<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"payment_intent", payment_intent.get_intent_id().as_bytes()],
+        bump = payment_intent.bump,
+        constraint = payment_intent.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub payment_intent: Account<'info, PaymentIntent>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<ClosePaymentIntent>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let payment_intent = &ctx.accounts.payment_intent;
+    let clock = Clock::get()?;
+
+    require!(
+        payment_intent.is_closable(clock.unix_timestamp),
+        LooprError::PaymentIntentStillOpen
+    );
+
+    emit!(IntentClosed {
+        intent: payment_intent.key(),
+        authority: ctx.accounts.authority.key(),
+    });
+
+    msg!("Payment intent {} closed", payment_intent.get_intent_id());
+
+    Ok(())
+}
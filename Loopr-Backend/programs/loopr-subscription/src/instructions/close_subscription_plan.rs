@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*};
+
+#[derive(Accounts)]
+pub struct CloseSubscriptionPlan<'info> {
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized,
+        constraint = subscription_plan.is_closable() @ LooprError::PlanNotRetired
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Plan's escrow vault, closed along with the plan when it has one
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Program-owned PDA that owns every escrow vault
+    #[account(
+        seeds = [b"escrow_authority"],
+        bump
+    )]
+    pub escrow_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+pub fn handler(ctx: Context<CloseSubscriptionPlan>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    if let Some(escrow_vault) = &ctx.accounts.escrow_vault {
+        let (Some(escrow_authority), Some(token_program)) =
+            (&ctx.accounts.escrow_authority, &ctx.accounts.token_program)
+        else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+
+        let escrow_seeds: &[&[u8]] = &[b"escrow_authority", &[ctx.bumps.escrow_authority]];
+        transfer::close_vault(
+            &token_program.to_account_info(),
+            escrow_vault,
+            &ctx.accounts.authority.to_account_info(),
+            &escrow_authority.to_account_info(),
+            &[escrow_seeds],
+        )?;
+    }
+
+    emit!(PlanClosed {
+        plan: ctx.accounts.subscription_plan.key(),
+        plan_id: ctx.accounts.subscription_plan.get_plan_id(),
+        authority: ctx.accounts.authority.key(),
+    });
+
+    msg!(
+        "Subscription plan {} closed",
+        ctx.accounts.subscription_plan.get_plan_id()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct CloseUserSubscription<'info> {
+    #[account(
+        mut,
+        close = user,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = !user_subscription.status.is_open() @ LooprError::SubscriptionStillOpen,
+        constraint = user_subscription.escrowed_payments == 0 @ LooprError::EscrowOutstanding,
+        constraint = user_subscription.stream.map_or(true, |stream| stream.accrued == 0) @ LooprError::StreamNotSettled
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// Accounts below are only needed to close a streaming subscription's vault
+    /// CHECK: Program-owned PDA that owns every stream vault
+    #[account(
+        seeds = [b"stream_authority"],
+        bump
+    )]
+    pub stream_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        seeds = [b"stream_vault", user_subscription.key().as_ref()],
+        bump
+    )]
+    pub stream_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+}
+
+pub fn handler(ctx: Context<CloseUserSubscription>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    // Cancelling already refunded the unaccrued balance, so the vault only
+    // has rent left once the merchant has withdrawn what accrued
+    if ctx.accounts.user_subscription.stream.is_some() {
+        let (Some(stream_authority), Some(stream_vault), Some(token_program)) = (
+            &ctx.accounts.stream_authority,
+            &ctx.accounts.stream_vault,
+            &ctx.accounts.token_program,
+        ) else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+
+        let stream_seeds: &[&[u8]] = &[b"stream_authority", &[ctx.bumps.stream_authority]];
+        transfer::close_vault(
+            &token_program.to_account_info(),
+            stream_vault,
+            &ctx.accounts.user.to_account_info(),
+            &stream_authority.to_account_info(),
+            &[stream_seeds],
+        )?;
+    }
+
+    emit!(SubscriptionClosed {
+        subscription: ctx.accounts.user_subscription.key(),
+        user: ctx.accounts.user.key(),
+    });
+
+    msg!(
+        "Subscription {} closed",
+        ctx.accounts.user_subscription.get_subscription_id()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, collection::{self, AutopayCollection}, pda::*};
+
+#[derive(Accounts)]
+pub struct CollectDuePayment<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user_subscription.user.as_ref(), user_subscription.subscription_plan.as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.status.is_collectable() @ LooprError::SubscriptionNotActive,
+        constraint = user_subscription.auto_pay_enabled @ LooprError::AutoPayNotEnabled,
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.is_active @ LooprError::PlanNotActive
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+
+    /// Anyone may crank a due payment and earn the bounty
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = keeper_token_account.owner == keeper.key(),
+        constraint = keeper_token_account.mint == mint.key()
+    )]
+    pub keeper_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user_subscription.user,
+        constraint = user_token_account.mint == mint.key(),
+        constraint = user_subscription.autopay_token_account == Some(user_token_account.key()) @ LooprError::AutopayTokenAccountMismatch
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == mint.key()
+    )]
+    pub plan_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Treasury's account in the payment mint, receiving the protocol fee
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == mint.key() @ LooprError::InvalidMint
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Plan's escrow vault; required when the plan holds payments for a dispute window
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: Pyth-format price account, checked against the plan's USD pricing; only needed for USD-priced plans
+    pub price_feed: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = keeper,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", mint.key().as_ref()],
+        bump
+    )]
+    pub fee_stats: Account<'info, FeeStats>,
+
+    /// CHECK: Program-owned PDA the user approved as delegate on `user_token_account`
+    #[account(
+        seeds = [b"autopay_delegate"],
+        bump
+    )]
+    pub autopay_delegate: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, CollectDuePayment<'info>>) -> Result<()> {
+    let accounts = ctx.accounts;
+    collection::collect(
+        AutopayCollection {
+            user_subscription: &mut accounts.user_subscription,
+            subscription_plan: &mut accounts.subscription_plan,
+            merchant: &mut accounts.merchant,
+            payment_ledger: &mut accounts.payment_ledger,
+            user_token_account: &accounts.user_token_account,
+            plan_token_account: &accounts.plan_token_account,
+            mint: &accounts.mint,
+            treasury_token_account: &accounts.treasury_token_account,
+            escrow_vault: accounts.escrow_vault.as_ref(),
+            price_feed: accounts.price_feed.as_ref(),
+            fee_stats: &mut accounts.fee_stats,
+            fee_stats_bump: ctx.bumps.fee_stats,
+            autopay_delegate: &accounts.autopay_delegate,
+            autopay_delegate_bump: ctx.bumps.autopay_delegate,
+            global_state: &mut accounts.global_state,
+            token_program: &accounts.token_program,
+            keeper_token_account: Some(&accounts.keeper_token_account),
+        },
+        ctx.remaining_accounts,
+    )
+}
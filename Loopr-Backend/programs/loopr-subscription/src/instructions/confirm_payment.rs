@@ -1 +1,63 @@
-use anchor_lang::prelude::*;use crate::{state::*, errors::*};#[derive(Accounts)]pub struct ConfirmPayment<'info> {    #[account(        mut,        seeds = [b"payment_intent", payment_intent.intent_id.as_bytes()],        bump = payment_intent.bump,        constraint = payment_intent.status == PaymentIntentStatus::Pending @ LooprError::InvalidPaymentIntentStatus    )]    pub payment_intent: Account<'info, PaymentIntent>,        #[account(        mut,        seeds = [b"user_subscription", user_subscription.user.as_ref(), user_subscription.subscription_plan.as_ref()],        bump = user_subscription.bump,        constraint = payment_intent.subscription == Some(user_subscription.key()) @ LooprError::PaymentIntentNotFound    )]    pub user_subscription: Account<'info, UserSubscription>,        #[account(        seeds = [b"global_state"],        bump = global_state.bump    )]    pub global_state: Account<'info, GlobalState>,        /// CHECK: Can be any signer for confirmation    pub confirmer: Signer<'info>,}pub fn handler(ctx: Context<ConfirmPayment>) -> Result<()> {    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);    let payment_intent = &mut ctx.accounts.payment_intent;    let user_subscription = &mut ctx.accounts.user_subscription;    let clock = Clock::get()?;    // Check if payment intent is still valid    require!(        clock.unix_timestamp <= payment_intent.expires_at,        LooprError::PaymentIntentExpired    );    // Update payment intent status    payment_intent.status = PaymentIntentStatus::Completed;    payment_intent.fulfilled_at = Some(clock.unix_timestamp);    // Activate subscription if not already active    if !user_subscription.is_active {        user_subscription.is_active = true;        user_subscription.updated_at = clock.unix_timestamp;    }    msg!("Payment confirmed for intent: {}", payment_intent.intent_id);    Ok(())}
\ No newline at end of file
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct ConfirmPayment<'info> {
+    #[account(
+        seeds = [b"payment_intent", payment_intent.get_intent_id().as_bytes()],
+        bump = payment_intent.bump,
+        constraint = payment_intent.status == PaymentIntentStatus::Completed @ LooprError::InvalidPaymentIntentStatus
+    )]
+    pub payment_intent: Account<'info, PaymentIntent>,
+    
+    #[account(
+        seeds = [b"subscription_plan", payment_intent.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    #[account(
+        mut,
+        constraint = payment_intent.subscription == Some(user_subscription.key()) @ LooprError::PaymentIntentNotFound,
+        constraint = user_subscription.subscription_plan == subscription_plan.key(),
+        constraint = matches!(
+            user_subscription.status,
+            SubscriptionStatus::Trialing | SubscriptionStatus::Active
+        ) @ LooprError::SubscriptionNotActive
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<ConfirmPayment>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let clock = Clock::get()?;
+
+    // Confirmation only acknowledges the intent; status changes are left to
+    // the payment instructions so this can't revive a lapsed subscription
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    emit!(PaymentConfirmed {
+        intent: ctx.accounts.payment_intent.key(),
+        subscription: user_subscription.key(),
+        authority: ctx.accounts.authority.key(),
+    });
+
+    msg!(
+        "Payment confirmed: intent {} for subscription {}",
+        ctx.accounts.payment_intent.get_intent_id(),
+        user_subscription.get_subscription_id()
+    );
+    
+    Ok(())
+}
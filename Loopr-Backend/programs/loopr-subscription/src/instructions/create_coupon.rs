@@ -0,0 +1,83 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+#[instruction(code: String)]
+pub struct CreateCoupon<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Coupon::LEN,
+        seeds = [b"coupon", authority.key().as_ref(), code.as_bytes()],
+        bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<CreateCoupon>,
+    code: String,
+    discount_type: DiscountType,
+    discount_value: u64,
+    duration: CouponDuration,
+    max_redemptions: Option<u32>,
+    expires_at: Option<i64>,
+    plan: Option<Pubkey>,
+    mint: Pubkey,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(code.len() <= 32, LooprError::CouponCodeTooLong);
+    require!(discount_value > 0, LooprError::InvalidDiscount);
+    if discount_type == DiscountType::Percent {
+        require!(discount_value <= 10_000, LooprError::InvalidDiscount);
+    }
+    if let CouponDuration::Repeating(periods) = duration {
+        require!(periods > 0, LooprError::InvalidDiscount);
+    }
+
+    let coupon = &mut ctx.accounts.coupon;
+    let clock = Clock::get()?;
+    if let Some(expires_at) = expires_at {
+        require!(expires_at > clock.unix_timestamp, LooprError::CouponExpired);
+    }
+
+    coupon.authority = ctx.accounts.authority.key();
+    coupon.set_code(&code);
+    coupon.discount_type = discount_type;
+    coupon.discount_value = discount_value;
+    coupon.duration = duration;
+    coupon.max_redemptions = max_redemptions;
+    coupon.times_redeemed = 0;
+    coupon.expires_at = expires_at;
+    coupon.plan = plan;
+    coupon.mint = mint;
+    coupon.is_active = true;
+    coupon.created_at = clock.unix_timestamp;
+    coupon.bump = ctx.bumps.coupon;
+    coupon.version = ACCOUNT_VERSION;
+
+    emit!(CouponCreated {
+        coupon: coupon.key(),
+        code: coupon.get_code(),
+        authority: coupon.authority,
+        discount_type: coupon.discount_type,
+        discount_value: coupon.discount_value,
+        plan: coupon.plan,
+        mint: coupon.mint,
+    });
+
+    msg!("Coupon created: {}", coupon.get_code());
+    
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use crate::{state::*, errors::*};
+use crate::{state::*, errors::*, events::*};
 
 #[derive(Accounts)]
 #[instruction(intent_id: String, plan_id: String)]
@@ -20,6 +20,14 @@ pub struct CreatePaymentIntent<'info> {
     )]
     pub subscription_plan: Account<'info, SubscriptionPlan>,
     
+    /// Optional coupon discounting the intent's amount
+    #[account(
+        seeds = [b"coupon", coupon.authority.as_ref(), coupon.get_code().as_bytes()],
+        bump = coupon.bump,
+        constraint = coupon.authority == subscription_plan.authority @ LooprError::InvalidCoupon
+    )]
+    pub coupon: Option<Account<'info, Coupon>>,
+    
     #[account(mut)]
     pub authority: Signer<'info>,
     
@@ -41,17 +49,25 @@ pub fn handler(
 ) -> Result<()> {
     require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
     require!(intent_id.len() <= 64, LooprError::IntentIdTooLong);
-    require!(
-        amount == ctx.accounts.subscription_plan.price_per_period,
-        LooprError::InvalidPaymentAmount
-    );
 
     let payment_intent = &mut ctx.accounts.payment_intent;
     let clock = Clock::get()?;
     require!(expires_at > clock.unix_timestamp, LooprError::PaymentIntentExpired);
 
+    // The intent must ask for a single seat, discounted by the coupon if one is given
+    let price = ctx.accounts.subscription_plan.seat_price(1)?;
+    let expected_amount = match &ctx.accounts.coupon {
+        Some(coupon) => {
+            coupon.check_redeemable(&ctx.accounts.subscription_plan, clock.unix_timestamp)?;
+            coupon.to_applied(coupon.key()).apply(price)
+        }
+        None => price,
+    };
+    require!(amount == expected_amount, LooprError::InvalidPaymentAmount);
+
     payment_intent.set_intent_id(&intent_id);
     payment_intent.set_plan_id(&plan_id);
+    payment_intent.authority = ctx.accounts.authority.key();
     payment_intent.payer = None;
     payment_intent.amount = amount;
     payment_intent.status = PaymentIntentStatus::Created;
@@ -59,9 +75,25 @@ pub fn handler(
     payment_intent.expires_at = expires_at;
     payment_intent.fulfilled_at = None;
     payment_intent.subscription = None;
+    payment_intent.coupon = ctx.accounts.coupon.as_ref().map(|coupon| coupon.key());
     payment_intent.bump = ctx.bumps.payment_intent;
+    payment_intent.version = ACCOUNT_VERSION;
+
+    emit!(IntentCreated {
+        intent: payment_intent.key(),
+        intent_id: payment_intent.get_intent_id(),
+        plan: ctx.accounts.subscription_plan.key(),
+        authority: payment_intent.authority,
+        amount,
+        expires_at,
+    });
 
-    msg!("Payment intent created: {} for {} SOL", payment_intent.get_intent_id(), amount);
+    msg!(
+        "Payment intent created: {} for {} of mint {}",
+        payment_intent.get_intent_id(),
+        ctx.accounts.subscription_plan.format_amount(amount),
+        ctx.accounts.subscription_plan.accepted_mint
+    );
     
     Ok(())
 }
\ No newline at end of file
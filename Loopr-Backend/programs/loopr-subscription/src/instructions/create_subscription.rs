@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use crate::{state::*, errors::*};
+use crate::{state::*, errors::*, events::*, pda::*};
 
 #[derive(Accounts)]
 #[instruction(subscription_id: String)]
@@ -8,7 +8,7 @@ pub struct CreateSubscription<'info> {
         init,
         payer = user,
         space = UserSubscription::LEN,
-        seeds = [b"user_subscription", user.key().as_ref(), subscription_plan.key().as_ref()],
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
         bump
     )]
     pub user_subscription: Account<'info, UserSubscription>,
@@ -24,6 +24,34 @@ pub struct CreateSubscription<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     
+    /// History of the subscription's payments, paid for by the subscriber
+    #[account(
+        init,
+        payer = user,
+        space = PaymentLedger::LEN,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+    
+    /// Merchants barred by the protocol can't take on new subscribers
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    /// Remembers this wallet's trial on the plan so it is only granted once
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = TrialRecord::LEN,
+        seeds = [b"trial_record", subscription_plan.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub trial_record: Account<'info, TrialRecord>,
+    
     #[account(
         mut,
         seeds = [b"global_state"],
@@ -54,27 +82,84 @@ pub fn handler(
     let user_subscription = &mut ctx.accounts.user_subscription;
     let clock = Clock::get()?;
 
+    let trial_record = &mut ctx.accounts.trial_record;
+    trial_record.user = ctx.accounts.user.key();
+    trial_record.subscription_plan = subscription_plan.key();
+    trial_record.bump = ctx.bumps.trial_record;
+    trial_record.version = ACCOUNT_VERSION;
+    let trial_end = trial_record.claim(subscription_plan, clock.unix_timestamp);
+
     user_subscription.user = ctx.accounts.user.key();
     user_subscription.subscription_plan = subscription_plan.key();
     user_subscription.set_subscription_id(&subscription_id);
-    user_subscription.is_active = false; // Will be activated after first payment
-    user_subscription.next_payment_due = clock.unix_timestamp + subscription_plan.period_duration;
+    match trial_end {
+        Some(trial_end) => {
+            user_subscription.status = SubscriptionStatus::Trialing;
+            user_subscription.next_payment_due = trial_end;
+        }
+        None => {
+            // Active once the first period, payable immediately, is paid
+            user_subscription.status = SubscriptionStatus::Incomplete;
+            user_subscription.next_payment_due = clock.unix_timestamp;
+        }
+    }
     user_subscription.last_payment_date = None;
+    user_subscription.failed_payment_attempts = 0;
+    user_subscription.last_failed_attempt = None;
+    user_subscription.paused_at = None;
+    user_subscription.pause_window_start = 0;
+    user_subscription.pauses_in_window = 0;
     user_subscription.auto_pay_enabled = false;
-    user_subscription.payment_thread = None;
+    user_subscription.autopay_token_account = None;
+    user_subscription.autopay_allowance = 0;
+    user_subscription.credit_balance = 0;
+    user_subscription.discount = None;
+    user_subscription.period_usage = 0;
+    user_subscription.quantity = 1;
+    user_subscription.pin_price(subscription_plan);
+    user_subscription.stream = None;
+    user_subscription.max_slippage_bps = UserSubscription::DEFAULT_MAX_SLIPPAGE_BPS;
+    user_subscription.escrowed_payments = 0;
     user_subscription.total_payments_made = 0;
     user_subscription.created_at = clock.unix_timestamp;
     user_subscription.updated_at = clock.unix_timestamp;
     user_subscription.bump = ctx.bumps.user_subscription;
+    user_subscription.version = ACCOUNT_VERSION;
 
-    // Update subscription plan count
-    subscription_plan.current_subscribers = subscription_plan.current_subscribers.checked_add(1).unwrap();
+    let payment_ledger = &mut ctx.accounts.payment_ledger;
+    payment_ledger.subscription = user_subscription.key();
+    payment_ledger.next_sequence = 0;
+    payment_ledger.history_hash = [0u8; 32];
+    payment_ledger.entries = Vec::new();
+    payment_ledger.bump = ctx.bumps.payment_ledger;
+    payment_ledger.version = ACCOUNT_VERSION;
+
+    // A trial takes a place on the plan now; anyone else takes it with their first payment
+    if user_subscription.status == SubscriptionStatus::Trialing {
+        subscription_plan.add_subscriber()?;
+        let merchant = &mut ctx.accounts.merchant;
+        merchant.current_subscribers = merchant.current_subscribers.checked_add(1).unwrap();
+    }
 
     // Update global state
     let global_state = &mut ctx.accounts.global_state;
     global_state.total_subscriptions = global_state.total_subscriptions.checked_add(1).unwrap();
 
-    msg!("User subscription created: {}", user_subscription.get_subscription_id());
+    emit!(SubscriptionCreated {
+        subscription: user_subscription.key(),
+        subscription_id: user_subscription.get_subscription_id(),
+        user: user_subscription.user,
+        plan: user_subscription.subscription_plan,
+        status: user_subscription.status,
+        next_payment_due: user_subscription.next_payment_due,
+    });
+
+    msg!(
+        "User subscription created: {} ({:?}, first payment due {})",
+        user_subscription.get_subscription_id(),
+        user_subscription.status,
+        user_subscription.next_payment_due
+    );
     
     Ok(())
 }
@@ -0,0 +1,144 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*};
+
+#[derive(Accounts)]
+pub struct DepositStream<'info> {
+    #[account(
+        mut,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded,
+        constraint = user_subscription.status != SubscriptionStatus::Paused @ LooprError::SubscriptionPaused,
+        constraint = !user_subscription.has_limited_discount() @ LooprError::CouponNotStreamable
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.key() == user_subscription.subscription_plan,
+        constraint = subscription_plan.is_active @ LooprError::PlanNotActive,
+        constraint = !subscription_plan.is_usd_priced() @ LooprError::UsdPricedStream
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    /// Counts the subscriber once a first deposit activates the subscription
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == mint.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: Program-owned PDA that owns every stream vault
+    #[account(
+        seeds = [b"stream_authority"],
+        bump
+    )]
+    pub stream_authority: UncheckedAccount<'info>,
+
+    /// Vault the subscription's stream is paid out of
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"stream_vault", user_subscription.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = stream_authority,
+        token::token_program = token_program
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositStream<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(amount > 0, LooprError::InvalidPaymentAmount);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    let price = user_subscription.stream_price(subscription_plan)?;
+    require!(price > 0, LooprError::InvalidPaymentAmount);
+
+    // A new stream starts accruing once the time already paid for (or the
+    // trial) runs out; an existing one is settled up to now first, so the
+    // deposit only pays for time from here on
+    let balance = ctx.accounts.stream_vault.amount;
+    let mut stream = user_subscription.stream.unwrap_or(Stream {
+        accrued_at: clock.unix_timestamp.max(user_subscription.next_payment_due),
+        accrued: 0,
+    });
+    stream.accrue(clock.unix_timestamp, price, user_subscription.period_duration, balance);
+
+    let received = transfer::transfer_checked(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.user_token_account.to_account_info(),
+        &ctx.accounts.mint,
+        &ctx.accounts.stream_vault.to_account_info(),
+        &ctx.accounts.user.to_account_info(),
+        ctx.remaining_accounts,
+        amount,
+        &[],
+    )?;
+    let balance = balance.checked_add(received).unwrap();
+
+    user_subscription.stream = Some(stream);
+    user_subscription.next_payment_due =
+        stream.paid_through(price, user_subscription.period_duration, balance);
+    // Funding the stream brings a lapsed subscription back into good standing,
+    // and activates one that was waiting for its first payment
+    if user_subscription.status == SubscriptionStatus::Incomplete {
+        subscription_plan.add_subscriber()?;
+        ctx.accounts.merchant.current_subscribers = ctx.accounts.merchant.current_subscribers.checked_add(1).unwrap();
+    }
+    if user_subscription.status != SubscriptionStatus::Trialing {
+        user_subscription.status = SubscriptionStatus::Active;
+    }
+    user_subscription.failed_payment_attempts = 0;
+    user_subscription.last_failed_attempt = None;
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    emit!(StreamDeposited {
+        subscription: user_subscription.key(),
+        mint: ctx.accounts.mint.key(),
+        amount: received,
+        paid_through: user_subscription.next_payment_due,
+    });
+
+    msg!(
+        "Deposited {} into stream for subscription {}: paid through {}",
+        subscription_plan.format_amount(received),
+        user_subscription.get_subscription_id(),
+        user_subscription.next_payment_due
+    );
+
+    Ok(())
+}
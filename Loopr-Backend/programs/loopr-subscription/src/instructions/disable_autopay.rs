@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct DisableAutopay<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), user_subscription.subscription_plan.as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.auto_pay_enabled @ LooprError::AutoPayNotEnabled
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = user_subscription.autopay_token_account == Some(user_token_account.key()) @ LooprError::AutopayTokenAccountMismatch
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Program-owned PDA approved as SPL delegate; holds no data
+    #[account(
+        seeds = [b"autopay_delegate"],
+        bump
+    )]
+    pub autopay_delegate: UncheckedAccount<'info>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handler(ctx: Context<DisableAutopay>) -> Result<()> {
+    let user_subscription = &mut ctx.accounts.user_subscription;
+
+    transfer::release_autopay_approval(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.user_token_account,
+        &ctx.accounts.autopay_delegate.to_account_info(),
+        &ctx.accounts.user.to_account_info(),
+        user_subscription.autopay_allowance,
+    )?;
+
+    user_subscription.clear_autopay();
+    user_subscription.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(AutopayDisabled {
+        subscription: user_subscription.key(),
+        user: user_subscription.user,
+    });
+
+    msg!("Autopay disabled for subscription {}", user_subscription.get_subscription_id());
+    
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Approve, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct EnableAutopay<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), user_subscription.subscription_plan.as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        constraint = subscription_plan.key() == user_subscription.subscription_plan
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ LooprError::Unauthorized,
+        constraint = user_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// CHECK: Program-owned PDA approved as SPL delegate; holds no data
+    #[account(
+        seeds = [b"autopay_delegate"],
+        bump
+    )]
+    pub autopay_delegate: UncheckedAccount<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handler(ctx: Context<EnableAutopay>, allowance: u64) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(allowance > 0, LooprError::InvalidAutopayAllowance);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let user_token_account = &ctx.accounts.user_token_account;
+    let delegate_key = ctx.accounts.autopay_delegate.key();
+
+    // The delegate is shared by every subscription paying from this token
+    // account, so keep the other mandates' allowance in the approval.
+    let mut delegated = if user_token_account.delegate == Some(delegate_key).into() {
+        user_token_account.delegated_amount
+    } else {
+        0
+    };
+    if user_subscription.autopay_token_account == Some(user_token_account.key()) {
+        delegated = delegated.saturating_sub(user_subscription.autopay_allowance);
+    }
+    let approved = delegated.checked_add(allowance).unwrap();
+
+    let approve_ctx = CpiContext::new(
+        ctx.accounts.token_program.to_account_info(),
+        Approve {
+            to: user_token_account.to_account_info(),
+            delegate: ctx.accounts.autopay_delegate.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        },
+    );
+    token_interface::approve(approve_ctx, approved)?;
+
+    user_subscription.auto_pay_enabled = true;
+    user_subscription.autopay_token_account = Some(user_token_account.key());
+    user_subscription.autopay_allowance = allowance;
+    user_subscription.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(AutopayEnabled {
+        subscription: user_subscription.key(),
+        user: user_subscription.user,
+        token_account: user_token_account.key(),
+        allowance,
+    });
+
+    msg!(
+        "Autopay enabled for subscription {} with allowance {}",
+        user_subscription.get_subscription_id(),
+        allowance
+    );
+    
+    Ok(())
+}
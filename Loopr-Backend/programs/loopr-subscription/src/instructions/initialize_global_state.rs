@@ -1 +1,53 @@
-use anchor_lang::prelude::*;use crate::state::*;#[derive(Accounts)]pub struct InitializeGlobalState<'info> {    #[account(        init,        payer = authority,        space = GlobalState::LEN,        seeds = [b"global_state"],        bump    )]    pub global_state: Account<'info, GlobalState>,        #[account(mut)]    pub authority: Signer<'info>,        pub system_program: Program<'info, System>,}pub fn handler(ctx: Context<InitializeGlobalState>) -> Result<()> {    let global_state = &mut ctx.accounts.global_state;    global_state.authority = ctx.accounts.authority.key();    global_state.total_plans = 0;    global_state.total_subscriptions = 0;    global_state.total_payments_processed = 0;    global_state.total_volume = 0;    global_state.is_paused = false;    global_state.bump = ctx.bumps.global_state;    msg!("Global state initialized");    Ok(())}
\ No newline at end of file
+use anchor_lang::prelude::*;
+use crate::{state::*, events::*, oracle};
+
+#[derive(Accounts)]
+pub struct InitializeGlobalState<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = GlobalState::LEN,
+        seeds = [b"global_state"],
+        bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<InitializeGlobalState>) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+
+    global_state.authority = ctx.accounts.authority.key();
+    global_state.total_plans = 0;
+    global_state.total_subscriptions = 0;
+    global_state.total_payments_processed = 0;
+    global_state.total_volume = 0;
+    global_state.total_refunded = 0;
+    global_state.is_paused = false;
+    global_state.keeper_bounty_bps = GlobalState::DEFAULT_KEEPER_BOUNTY_BPS;
+    global_state.fee_bps = 0;
+    global_state.treasury = ctx.accounts.authority.key();
+    global_state.arbiter = ctx.accounts.authority.key();
+    global_state.price_oracle = oracle::pyth_oracle::ID;
+    global_state.pending_authority = None;
+    global_state.bump = ctx.bumps.global_state;
+    global_state.version = ACCOUNT_VERSION;
+
+    emit!(GlobalStateInitialized {
+        global_state: global_state.key(),
+        authority: global_state.authority,
+        treasury: global_state.treasury,
+        arbiter: global_state.arbiter,
+        price_oracle: global_state.price_oracle,
+        keeper_bounty_bps: global_state.keeper_bounty_bps,
+        fee_bps: global_state.fee_bps,
+    });
+
+    msg!("Global state initialized with authority: {}", global_state.authority);
+    
+    Ok(())
+}
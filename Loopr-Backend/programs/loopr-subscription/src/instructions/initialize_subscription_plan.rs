@@ -1,5 +1,6 @@
 use anchor_lang::prelude::*;
-use crate::{state::*, errors::*};
+use anchor_spl::token_interface::Mint;
+use crate::{state::*, errors::*, events::*};
 
 #[derive(Accounts)]
 #[instruction(plan_id: String)]
@@ -16,6 +17,18 @@ pub struct InitializeSubscriptionPlan<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
     
+    /// Plans can only be published by a registered merchant in good standing
+    #[account(
+        mut,
+        seeds = [b"merchant", authority.key().as_ref()],
+        bump = merchant.bump,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    /// Mint the plan is priced in; omit to bill in native SOL
+    pub accepted_mint: Option<InterfaceAccount<'info, Mint>>,
+    
     #[account(
         mut,
         seeds = [b"global_state"],
@@ -45,22 +58,64 @@ pub fn handler(
     let clock = Clock::get()?;
 
     subscription_plan.authority = ctx.accounts.authority.key();
+    subscription_plan.merchant = ctx.accounts.merchant.key();
     subscription_plan.set_plan_id(&plan_id);
     subscription_plan.set_name(&name);
     subscription_plan.set_description(&description);
     subscription_plan.price_per_period = price_per_period;
     subscription_plan.period_duration = period_duration;
+    match &ctx.accounts.accepted_mint {
+        Some(mint) => {
+            subscription_plan.accepted_mint = mint.key();
+            subscription_plan.mint_decimals = mint.decimals;
+        }
+        None => {
+            subscription_plan.accepted_mint = NATIVE_SOL_MINT;
+            subscription_plan.mint_decimals = NATIVE_SOL_DECIMALS;
+        }
+    }
+    subscription_plan.trial_duration = 0;
+    subscription_plan.grace_period = SubscriptionPlan::DEFAULT_GRACE_PERIOD;
+    subscription_plan.max_retry_attempts = SubscriptionPlan::DEFAULT_MAX_RETRY_ATTEMPTS;
+    subscription_plan.max_pause_duration = SubscriptionPlan::DEFAULT_MAX_PAUSE_DURATION;
+    subscription_plan.max_pauses_per_year = SubscriptionPlan::DEFAULT_MAX_PAUSES_PER_YEAR;
+    subscription_plan.revenue_split = Vec::new();
+    subscription_plan.metering = None;
+    subscription_plan.pricing_model = PricingModel::Flat;
+    subscription_plan.price_tiers = Vec::new();
+    subscription_plan.price_version = 1;
+    subscription_plan.price_changed_at = clock.unix_timestamp;
+    subscription_plan.price_notice_period = Some(SubscriptionPlan::DEFAULT_PRICE_NOTICE_PERIOD);
+    subscription_plan.price_consent_threshold_bps = SubscriptionPlan::DEFAULT_PRICE_CONSENT_THRESHOLD_BPS;
+    subscription_plan.dispute_window = 0;
+    subscription_plan.arbiter = None;
+    subscription_plan.usd_pricing = None;
     subscription_plan.max_subscribers = max_subscribers;
     subscription_plan.current_subscribers = 0;
     subscription_plan.is_active = true;
     subscription_plan.created_at = clock.unix_timestamp;
     subscription_plan.updated_at = clock.unix_timestamp;
     subscription_plan.bump = ctx.bumps.subscription_plan;
+    subscription_plan.version = ACCOUNT_VERSION;
+
+    let merchant = &mut ctx.accounts.merchant;
+    merchant.total_plans = merchant.total_plans.checked_add(1).unwrap();
 
     // Update global state
     let global_state = &mut ctx.accounts.global_state;
     global_state.total_plans = global_state.total_plans.checked_add(1).unwrap();
 
+    emit!(PlanCreated {
+        plan: subscription_plan.key(),
+        plan_id: subscription_plan.get_plan_id(),
+        merchant: subscription_plan.merchant,
+        authority: subscription_plan.authority,
+        accepted_mint: subscription_plan.accepted_mint,
+        mint_decimals: subscription_plan.mint_decimals,
+        price_per_period: subscription_plan.price_per_period,
+        period_duration: subscription_plan.period_duration,
+    });
+
     msg!("Subscription plan created: {}", subscription_plan.get_plan_id());
     
     Ok(())
@@ -0,0 +1,152 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::{state::*, errors::*, events::*, pda::*, legacy::*};
+
+#[derive(Accounts)]
+pub struct MigrateAccount<'info> {
+    /// CHECK: Any of the program's accounts; its discriminator picks the layout
+    /// it is upgraded to and its size tells which version it is at
+    #[account(
+        mut,
+        owner = crate::ID @ LooprError::UnknownAccountLayout
+    )]
+    pub account: UncheckedAccount<'info>,
+
+    /// Plan a subscription or payment intent belongs to, already migrated;
+    /// the v1 layouts lack fields that are taken from it
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Option<Account<'info, SubscriptionPlan>>,
+
+    /// V1 subscriptions kept their payments in separate records, so a
+    /// migrated subscription starts a new, empty ledger
+    #[account(
+        init,
+        payer = payer,
+        space = PaymentLedger::LEN,
+        seeds = [PAYMENT_LEDGER_SEED, account.key().as_ref()],
+        bump
+    )]
+    pub payment_ledger: Option<Account<'info, PaymentLedger>>,
+
+    /// Pays the rent for the account's extra space
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<MigrateAccount>) -> Result<()> {
+    // Deliberately not gated on the global pause or any authority: the
+    // fields carry over and new ones get the defaults the program creates
+    // accounts with, and `GlobalState` may itself be waiting to be migrated
+    let discriminator: [u8; 8] = ctx
+        .accounts
+        .account
+        .try_borrow_data()?
+        .get(..8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| error!(LooprError::UnknownAccountLayout))?;
+    let account = ctx.accounts.account.to_account_info();
+
+    let rent_paid = match discriminator {
+        GlobalState::DISCRIMINATOR => {
+            let upgraded = GlobalStateV1::read(&account)?.upgrade();
+            rewrite(ctx.accounts, &upgraded, GlobalState::LEN)?
+        }
+        SubscriptionPlan::DISCRIMINATOR => {
+            let upgraded = SubscriptionPlanV1::read(&account)?.upgrade();
+            rewrite(ctx.accounts, &upgraded, SubscriptionPlan::LEN)?
+        }
+        UserSubscription::DISCRIMINATOR => {
+            let legacy = UserSubscriptionV1::read(&account)?;
+            let plan = ctx
+                .accounts
+                .subscription_plan
+                .as_ref()
+                .filter(|plan| plan.key() == legacy.subscription_plan)
+                .ok_or_else(|| error!(LooprError::InvalidMigrationAccounts))?;
+
+            // Subscriptions `subscribe_and_pay` keyed by their id change
+            // address too, with `migrate_user_subscription`
+            let (address, bump) = user_subscription_address(&legacy.user, &plan.key());
+            require_keys_eq!(address, account.key(), ErrorCode::ConstraintSeeds);
+            let upgraded = legacy.upgrade(plan, bump);
+
+            let payment_ledger = ctx
+                .accounts
+                .payment_ledger
+                .as_mut()
+                .ok_or_else(|| error!(LooprError::InvalidMigrationAccounts))?;
+            payment_ledger.subscription = address;
+            payment_ledger.next_sequence = 0;
+            payment_ledger.history_hash = [0u8; 32];
+            payment_ledger.entries = Vec::new();
+            payment_ledger.bump = ctx.bumps.payment_ledger;
+            payment_ledger.version = ACCOUNT_VERSION;
+
+            rewrite(ctx.accounts, &upgraded, UserSubscription::LEN)?
+        }
+        PaymentIntent::DISCRIMINATOR => {
+            let legacy = PaymentIntentV1::read(&account)?;
+            let plan = ctx
+                .accounts
+                .subscription_plan
+                .as_ref()
+                .filter(|plan| plan.plan_id == legacy.plan_id)
+                .ok_or_else(|| error!(LooprError::InvalidMigrationAccounts))?;
+            let upgraded = legacy.upgrade(plan);
+            rewrite(ctx.accounts, &upgraded, PaymentIntent::LEN)?
+        }
+        // Added after v1, so always created in the current layout
+        Merchant::DISCRIMINATOR
+        | PaymentLedger::DISCRIMINATOR
+        | UsageRecord::DISCRIMINATOR
+        | TrialRecord::DISCRIMINATOR
+        | Coupon::DISCRIMINATOR
+        | FeeStats::DISCRIMINATOR => return err!(LooprError::AccountAlreadyMigrated),
+        _ => return err!(LooprError::UnknownAccountLayout),
+    };
+
+    emit!(AccountMigrated {
+        account: ctx.accounts.account.key(),
+        from_version: 1,
+        to_version: ACCOUNT_VERSION,
+        rent_paid,
+    });
+
+    msg!(
+        "Account {} migrated to layout version {}",
+        ctx.accounts.account.key(),
+        ACCOUNT_VERSION
+    );
+
+    Ok(())
+}
+
+/// Resize the account to `len` and write `upgraded` over it, returning the
+/// rent the payer topped it up with
+fn rewrite<T: AccountSerialize>(accounts: &MigrateAccount, upgraded: &T, len: usize) -> Result<u64> {
+    let account = accounts.account.to_account_info();
+
+    let rent_paid = Rent::get()?
+        .minimum_balance(len)
+        .saturating_sub(account.lamports());
+    if rent_paid > 0 {
+        let cpi_ctx = CpiContext::new(
+            accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: accounts.payer.to_account_info(),
+                to: account.clone(),
+            },
+        );
+        anchor_lang::system_program::transfer(cpi_ctx, rent_paid)?;
+    }
+
+    account.realloc(len, true)?;
+    upgraded.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+
+    Ok(rent_paid)
+}
@@ -0,0 +1,103 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*, pda::*, legacy::UserSubscriptionV1};
+
+#[derive(Accounts)]
+pub struct MigrateUserSubscription<'info> {
+    /// CHECK: Subscription the first `subscribe_and_pay` keyed by its
+    /// subscription id, in the v1 layout; read and checked in the handler
+    #[account(
+        mut,
+        owner = crate::ID @ LooprError::UnknownAccountLayout
+    )]
+    pub legacy_subscription: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    /// The same subscription at its canonical address
+    #[account(
+        init,
+        payer = user,
+        space = UserSubscription::LEN,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    /// V1 subscriptions kept their payments in separate records, so the
+    /// ledger starts empty
+    #[account(
+        init,
+        payer = user,
+        space = PaymentLedger::LEN,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<MigrateUserSubscription>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let legacy = UserSubscriptionV1::read(&ctx.accounts.legacy_subscription)?;
+    require_keys_eq!(legacy.user, ctx.accounts.user.key(), LooprError::Unauthorized);
+    require_keys_eq!(legacy.subscription_plan, ctx.accounts.subscription_plan.key());
+
+    let (legacy_address, _) = legacy_user_subscription_address(&legacy.user, &legacy.get_subscription_id());
+    require_keys_eq!(
+        legacy_address,
+        ctx.accounts.legacy_subscription.key(),
+        ErrorCode::ConstraintSeeds
+    );
+
+    // Only the address and layout change: the plan and global counters
+    // already count this subscriber
+    let subscription = legacy.upgrade(&ctx.accounts.subscription_plan, ctx.bumps.user_subscription);
+    ctx.accounts.user_subscription.set_inner(subscription);
+
+    let payment_ledger = &mut ctx.accounts.payment_ledger;
+    payment_ledger.subscription = ctx.accounts.user_subscription.key();
+    payment_ledger.next_sequence = 0;
+    payment_ledger.history_hash = [0u8; 32];
+    payment_ledger.entries = Vec::new();
+    payment_ledger.bump = ctx.bumps.payment_ledger;
+    payment_ledger.version = ACCOUNT_VERSION;
+
+    // Close the legacy account, returning its rent to the subscriber who paid it
+    let legacy_info = ctx.accounts.legacy_subscription.to_account_info();
+    let user_info = ctx.accounts.user.to_account_info();
+    **user_info.try_borrow_mut_lamports()? = user_info.lamports().checked_add(legacy_info.lamports()).unwrap();
+    **legacy_info.try_borrow_mut_lamports()? = 0;
+    legacy_info.assign(&System::id());
+    legacy_info.realloc(0, false)?;
+
+    emit!(SubscriptionMigrated {
+        subscription: ctx.accounts.user_subscription.key(),
+        previous_subscription: ctx.accounts.legacy_subscription.key(),
+        user: ctx.accounts.user.key(),
+        plan: ctx.accounts.user_subscription.subscription_plan,
+    });
+
+    msg!(
+        "Subscription {} migrated from {} to {}",
+        ctx.accounts.user_subscription.get_subscription_id(),
+        ctx.accounts.legacy_subscription.key(),
+        ctx.accounts.user_subscription.key()
+    );
+
+    Ok(())
+}
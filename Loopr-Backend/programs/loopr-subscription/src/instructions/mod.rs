@@ -1,23 +1,101 @@
+#![allow(ambiguous_glob_reexports)]
+
+pub mod register_merchant;
+pub mod update_merchant;
+pub mod set_payout_wallet;
+pub mod set_merchant_status;
 pub mod initialize_subscription_plan;
 pub mod create_subscription;
 pub mod process_payment;
 pub mod cancel_subscription;
 pub mod update_subscription_plan;
 pub mod automated_payment;
-pub mod initialize_payment_thread;
 pub mod initialize_global_state;
 pub mod create_payment_intent;
 pub mod subscribe_and_pay;
 pub mod confirm_payment;
+pub mod enable_autopay;
+pub mod disable_autopay;
+pub mod collect_due_payment;
+pub mod set_paused;
+pub mod propose_authority;
+pub mod accept_authority;
+pub mod update_global_config;
+pub mod refresh_subscription_status;
+pub mod pause_subscription;
+pub mod resume_subscription;
+pub mod change_plan;
+pub mod create_coupon;
+pub mod apply_coupon;
+pub mod set_revenue_split;
+pub mod set_metering;
+pub mod report_usage;
+pub mod set_pricing_model;
+pub mod change_quantity;
+pub mod set_price_change_policy;
+pub mod accept_price_change;
+pub mod refund_payment;
+pub mod set_plan_arbiter;
+pub mod set_dispute_window;
+pub mod open_dispute;
+pub mod release_escrow;
+pub mod resolve_dispute;
+pub mod deposit_stream;
+pub mod withdraw_stream;
+pub mod set_usd_pricing;
+pub mod set_max_slippage;
+pub mod close_subscription_plan;
+pub mod close_user_subscription;
+pub mod close_payment_intent;
+pub mod migrate_user_subscription;
+pub mod migrate_account;
 
+pub use register_merchant::*;
+pub use update_merchant::*;
+pub use set_payout_wallet::*;
+pub use set_merchant_status::*;
 pub use initialize_subscription_plan::*;
 pub use create_subscription::*;
 pub use process_payment::*;
 pub use cancel_subscription::*;
 pub use update_subscription_plan::*;
 pub use automated_payment::*;
-pub use initialize_payment_thread::*;
 pub use initialize_global_state::*;
 pub use create_payment_intent::*;
 pub use subscribe_and_pay::*;
-pub use confirm_payment::*;
\ No newline at end of file
+pub use confirm_payment::*;
+pub use enable_autopay::*;
+pub use disable_autopay::*;
+pub use collect_due_payment::*;
+pub use set_paused::*;
+pub use propose_authority::*;
+pub use accept_authority::*;
+pub use update_global_config::*;
+pub use refresh_subscription_status::*;
+pub use pause_subscription::*;
+pub use resume_subscription::*;
+pub use change_plan::*;
+pub use create_coupon::*;
+pub use apply_coupon::*;
+pub use set_revenue_split::*;
+pub use set_metering::*;
+pub use report_usage::*;
+pub use set_pricing_model::*;
+pub use change_quantity::*;
+pub use set_price_change_policy::*;
+pub use accept_price_change::*;
+pub use refund_payment::*;
+pub use set_plan_arbiter::*;
+pub use set_dispute_window::*;
+pub use open_dispute::*;
+pub use release_escrow::*;
+pub use resolve_dispute::*;
+pub use deposit_stream::*;
+pub use withdraw_stream::*;
+pub use set_usd_pricing::*;
+pub use set_max_slippage::*;
+pub use close_subscription_plan::*;
+pub use close_user_subscription::*;
+pub use close_payment_intent::*;
+pub use migrate_user_subscription::*;
+pub use migrate_account::*;
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct OpenDispute<'info> {
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+    
+    #[account(
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<OpenDispute>, sequence: u64) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let payment = ctx.accounts.payment_ledger.entry_mut(sequence)?;
+    let Some(release_at) = payment.release_at else {
+        return err!(LooprError::PaymentNotEscrowed);
+    };
+    require!(
+        payment.status == PaymentStatus::Completed,
+        LooprError::PaymentDisputed
+    );
+    require!(
+        Clock::get()?.unix_timestamp < release_at,
+        LooprError::DisputeWindowClosed
+    );
+
+    // The escrowed share stays locked until the arbiter resolves the dispute
+    payment.status = PaymentStatus::Disputed;
+
+    emit!(DisputeOpened {
+        subscription: ctx.accounts.user_subscription.key(),
+        sequence,
+        user: ctx.accounts.user.key(),
+    });
+
+    msg!(
+        "Dispute opened on payment {} of subscription {}",
+        sequence,
+        ctx.accounts.user_subscription.get_subscription_id()
+    );
+    
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct PauseSubscription<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<PauseSubscription>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    // Only a subscription in good standing can be paused; an overdue one has to pay first
+    user_subscription.refresh_status(clock.unix_timestamp, subscription_plan);
+    require!(
+        user_subscription.status == SubscriptionStatus::Active,
+        LooprError::SubscriptionNotActive
+    );
+
+    user_subscription.pause(clock.unix_timestamp, subscription_plan)?;
+
+    emit!(SubscriptionPaused {
+        subscription: user_subscription.key(),
+        paused_at: clock.unix_timestamp,
+        resumes_at: clock.unix_timestamp + subscription_plan.max_pause_duration,
+    });
+
+    msg!(
+        "Subscription paused: {} ({} of {} pauses used this year)",
+        user_subscription.get_subscription_id(),
+        user_subscription.pauses_in_window,
+        subscription_plan.max_pauses_per_year
+    );
+    
+    Ok(())
+}
@@ -1,18 +1,22 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
-use crate::{state::*, errors::*};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, oracle, transfer, events::*, pda::*};
 
 #[derive(Accounts)]
 pub struct ProcessPayment<'info> {
     #[account(
         mut,
-        seeds = [b"user_subscription", user.key().as_ref(), user_subscription.subscription_plan.as_ref()],
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), user_subscription.subscription_plan.as_ref()],
         bump = user_subscription.bump,
-        constraint = user_subscription.user == user.key()
+        constraint = user_subscription.user == user.key(),
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded,
+        constraint = user_subscription.status != SubscriptionStatus::Paused @ LooprError::SubscriptionPaused,
+        constraint = user_subscription.stream.is_none() @ LooprError::SubscriptionStreaming
     )]
     pub user_subscription: Account<'info, UserSubscription>,
     
     #[account(
+        mut,
         seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
         bump = subscription_plan.bump,
         constraint = subscription_plan.is_active @ LooprError::PlanNotActive
@@ -20,13 +24,18 @@ pub struct ProcessPayment<'info> {
     pub subscription_plan: Account<'info, SubscriptionPlan>,
     
     #[account(
-        init,
-        payer = user,
-        space = PaymentRecord::LEN,
-        seeds = [b"payment_record", user_subscription.key().as_ref(), &Clock::get()?.unix_timestamp.to_le_bytes()],
-        bump
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
     )]
-    pub payment_record: Account<'info, PaymentRecord>,
+    pub merchant: Account<'info, Merchant>,
+    
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
     
     #[account(mut)]
     pub user: Signer<'info>,
@@ -34,19 +43,49 @@ pub struct ProcessPayment<'info> {
     #[account(
         mut,
         constraint = user_token_account.owner == user.key(),
-        constraint = user_token_account.mint == native_mint.key()
+        constraint = user_token_account.mint == mint.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == mint.key()
+    )]
+    pub plan_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// Treasury's account in the payment mint, receiving the protocol fee
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == mint.key() @ LooprError::InvalidMint
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
     
+    /// Plan's escrow vault; required when the plan holds payments for a dispute window
     #[account(
         mut,
-        constraint = plan_token_account.owner == subscription_plan.authority,
-        constraint = plan_token_account.mint == native_mint.key()
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
     )]
-    pub plan_token_account: Account<'info, TokenAccount>,
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
     
-    /// CHECK: This is the native mint for SOL
-    pub native_mint: AccountInfo<'info>,
+    /// CHECK: Pyth-format price account, checked against the plan's USD pricing; only needed for USD-priced plans
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", mint.key().as_ref()],
+        bump
+    )]
+    pub fee_stats: Account<'info, FeeStats>,
     
     #[account(
         mut,
@@ -55,56 +94,144 @@ pub struct ProcessPayment<'info> {
     )]
     pub global_state: Account<'info, GlobalState>,
     
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(ctx: Context<ProcessPayment>, amount: u64) -> Result<()> {
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ProcessPayment<'info>>,
+    amount: u64,
+) -> Result<()> {
     require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
-    require!(
-        amount == ctx.accounts.subscription_plan.price_per_period,
-        LooprError::InvalidPaymentAmount
-    );
 
     let user_subscription = &mut ctx.accounts.user_subscription;
-    let subscription_plan = &ctx.accounts.subscription_plan;
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
     let clock = Clock::get()?;
 
-    // Transfer tokens from user to plan authority
-    let transfer_ctx = CpiContext::new(
-        ctx.accounts.token_program.to_account_info(),
-        Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.plan_token_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        },
-    );
-    token::transfer(transfer_ctx, amount)?;
+    // Pick up a new plan price once it is allowed to reach this subscriber
+    user_subscription.sync_price(clock.unix_timestamp, subscription_plan);
 
-    // Update subscription
-    user_subscription.is_active = true;
-    user_subscription.last_payment_date = Some(clock.unix_timestamp);
-    user_subscription.next_payment_due = clock.unix_timestamp + subscription_plan.period_duration;
-    user_subscription.total_payments_made = user_subscription.total_payments_made.checked_add(1).unwrap();
-    user_subscription.updated_at = clock.unix_timestamp;
+    // The price of the subscription's seats and any metered usage after a
+    // coupon, less credit left from a plan change
+    let price = user_subscription.effective_price(subscription_plan.period_price(user_subscription)?);
+    let amount = if subscription_plan.is_usd_priced() {
+        let charge = oracle::to_settlement_amount(
+            subscription_plan,
+            ctx.accounts.price_feed.as_ref().map(|feed| feed.as_ref()),
+            &ctx.accounts.global_state.price_oracle,
+            user_subscription.max_slippage_bps,
+            user_subscription.amount_due(price),
+            clock.unix_timestamp,
+        )?;
+        // The subscriber can't know the exact rate ahead of time, so `amount`
+        // is the most they are willing to pay
+        require!(charge <= amount, LooprError::SlippageExceeded);
+        charge
+    } else {
+        require!(
+            amount == user_subscription.amount_due(price),
+            LooprError::InvalidPaymentAmount
+        );
+        amount
+    };
 
-    // Create payment record
-    let payment_record = &mut ctx.accounts.payment_record;
-    payment_record.user = ctx.accounts.user.key();
-    payment_record.subscription = user_subscription.key();
-    payment_record.amount = amount;
-    payment_record.payment_date = clock.unix_timestamp;
-    payment_record.set_transaction_signature(""); // Will be filled by client
-    payment_record.payment_method = PaymentMethod::Manual;
-    payment_record.status = PaymentStatus::Completed;
-    payment_record.bump = ctx.bumps.payment_record;
+    // The protocol fee comes out of the merchant's share
+    let protocol_fee = ctx.accounts.global_state.protocol_fee(amount);
+    let merchant_amount = amount.checked_sub(protocol_fee).unwrap();
+
+    // Remaining accounts carry the split recipients' token accounts, then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(ctx.remaining_accounts, subscription_plan.revenue_split.len())?;
+
+    // Pay the plan authority and any revenue split partners, or escrow their share
+    let net_amount = transfer::pay_merchant(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.user_token_account.to_account_info(),
+        &ctx.accounts.mint,
+        subscription_plan,
+        &ctx.accounts.plan_token_account.to_account_info(),
+        ctx.accounts.escrow_vault.as_ref().map(|vault| vault.to_account_info()),
+        &ctx.accounts.user.to_account_info(),
+        recipient_accounts,
+        extra_accounts,
+        merchant_amount,
+        &[],
+    )?;
+
+    if protocol_fee > 0 {
+        transfer::transfer_checked(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.treasury_token_account.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            extra_accounts,
+            protocol_fee,
+            &[],
+        )?;
+    }
+
+    // Update subscription; a manual payment also recovers a past-due or suspended subscription
+    let first_payment = user_subscription.status == SubscriptionStatus::Incomplete;
+    user_subscription.apply_credit(price);
+    user_subscription.consume_discount_period();
+    user_subscription.record_payment(clock.unix_timestamp);
+    if first_payment {
+        subscription_plan.add_subscriber()?;
+        ctx.accounts.merchant.current_subscribers = ctx.accounts.merchant.current_subscribers.checked_add(1).unwrap();
+    }
+
+    // Record the payment in the subscription's ledger
+    let mut entry = PaymentEntry::from_fields(
+        amount,
+        ctx.accounts.mint.key(),
+        ctx.accounts.mint.decimals,
+        merchant_amount.checked_sub(net_amount).unwrap(),
+        protocol_fee,
+        net_amount,
+        clock.unix_timestamp,
+        PaymentMethod::Manual,
+        PaymentStatus::Completed,
+    );
+    if subscription_plan.holds_in_escrow() {
+        entry.hold_in_escrow(net_amount, clock.unix_timestamp + subscription_plan.dispute_window);
+        user_subscription.escrowed_payments = user_subscription.escrowed_payments.checked_add(1).unwrap();
+    }
+    let sequence = ctx.accounts.payment_ledger.append(entry)?;
 
     // Update global state
     let global_state = &mut ctx.accounts.global_state;
     global_state.total_payments_processed = global_state.total_payments_processed.checked_add(1).unwrap();
     global_state.total_volume = global_state.total_volume.checked_add(amount).unwrap();
+    ctx.accounts.merchant.record_revenue(amount);
+
+    let fee_stats = &mut ctx.accounts.fee_stats;
+    fee_stats.mint = ctx.accounts.mint.key();
+    fee_stats.bump = ctx.bumps.fee_stats;
+    fee_stats.version = ACCOUNT_VERSION;
+    fee_stats.record(amount, protocol_fee);
 
-    msg!("Payment processed: {} SOL for subscription {}", amount, user_subscription.get_subscription_id());
+    emit!(PaymentCollected {
+        subscription: user_subscription.key(),
+        user: user_subscription.user,
+        plan: subscription_plan.key(),
+        sequence,
+        mint: ctx.accounts.mint.key(),
+        amount,
+        protocol_fee,
+        keeper_bounty: 0,
+        net_amount,
+        payment_method: PaymentMethod::Manual,
+        escrowed: subscription_plan.holds_in_escrow(),
+        next_payment_due: user_subscription.next_payment_due,
+    });
+
+    msg!(
+        "Payment processed: {} of mint {} for subscription {}",
+        subscription_plan.format_amount(amount),
+        ctx.accounts.mint.key(),
+        user_subscription.get_subscription_id()
+    );
     
     Ok(())
 }
\ No newline at end of file
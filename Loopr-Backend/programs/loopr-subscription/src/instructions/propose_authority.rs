@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        constraint = global_state.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub authority: Signer<'info>,
+}
+
+pub fn handler(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+    require!(new_authority != Pubkey::default(), LooprError::InvalidAuthority);
+
+    // The transfer only completes once the new authority signs for it, so a
+    // mistyped key can be replaced by proposing again
+    let global_state = &mut ctx.accounts.global_state;
+    global_state.pending_authority = Some(new_authority);
+
+    emit!(AuthorityProposed {
+        global_state: global_state.key(),
+        authority: global_state.authority,
+        pending_authority: global_state.pending_authority,
+    });
+
+    msg!("Authority transfer to {} proposed", new_authority);
+    
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct RefreshSubscriptionStatus<'info> {
+    #[account(mut)]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        constraint = subscription_plan.key() == user_subscription.subscription_plan
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<RefreshSubscriptionStatus>) -> Result<()> {
+    // Subscribers can't pay while the program is paused, so dunning waits too
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let clock = Clock::get()?;
+
+    let previous_status = user_subscription.status;
+    user_subscription.refresh_status(clock.unix_timestamp, &ctx.accounts.subscription_plan);
+
+    if user_subscription.status != previous_status {
+        emit!(SubscriptionStatusChanged {
+            subscription: user_subscription.key(),
+            previous_status,
+            status: user_subscription.status,
+        });
+    }
+
+    msg!(
+        "Subscription {} status: {:?}",
+        user_subscription.get_subscription_id(),
+        user_subscription.status
+    );
+
+    Ok(())
+}
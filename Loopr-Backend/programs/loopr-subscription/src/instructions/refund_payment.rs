@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct RefundPayment<'info> {
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+    
+    #[account(
+        constraint = user_subscription.subscription_plan == subscription_plan.key()
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    /// Wallet the merchant is paid out to in the plan's mint, which funds the refund
+    #[account(
+        address = merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet
+    )]
+    pub payout_wallet: Signer<'info>,
+    
+    /// Payout wallet's account the refund is paid from
+    #[account(
+        mut,
+        constraint = payout_token_account.owner == payout_wallet.key() @ LooprError::InvalidPayoutWallet,
+        constraint = payout_token_account.mint == mint.key()
+    )]
+    pub payout_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Payer's account the refund is returned to
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user_subscription.user @ LooprError::Unauthorized,
+        constraint = user_token_account.mint == mint.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, RefundPayment<'info>>,
+    sequence: u64,
+    amount: u64,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let payment = ctx.accounts.payment_ledger.entry_mut(sequence)?;
+    require_keys_eq!(ctx.accounts.mint.key(), payment.mint, LooprError::InvalidMint);
+    require!(
+        matches!(
+            payment.status,
+            PaymentStatus::Completed | PaymentStatus::PartiallyRefunded
+        ),
+        LooprError::PaymentNotRefundable
+    );
+    require!(!payment.is_escrowed(), LooprError::PaymentInEscrow);
+    require!(
+        amount > 0 && amount <= payment.refundable(),
+        LooprError::InvalidRefundAmount
+    );
+
+    // Refunds are capped at what reached the merchant; the protocol fee and
+    // any transfer fee withheld along the way are not clawed back
+    transfer::transfer_checked(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.payout_token_account.to_account_info(),
+        &ctx.accounts.mint,
+        &ctx.accounts.user_token_account.to_account_info(),
+        &ctx.accounts.payout_wallet.to_account_info(),
+        ctx.remaining_accounts,
+        amount,
+        &[],
+    )?;
+
+    payment.record_refund(amount);
+
+    // Volume is kept net of refunds
+    let global_state = &mut ctx.accounts.global_state;
+    global_state.total_volume = global_state.total_volume.saturating_sub(amount);
+    global_state.total_refunded = global_state.total_refunded.checked_add(amount).unwrap();
+    ctx.accounts.merchant.record_refund(amount);
+
+    emit!(PaymentRefunded {
+        subscription: ctx.accounts.user_subscription.key(),
+        sequence,
+        mint: payment.mint,
+        amount,
+        refunded_amount: payment.refunded_amount,
+        status: payment.status,
+    });
+
+    msg!(
+        "Refunded {} of payment {} on subscription {}: {} of {} returned, status {:?}",
+        ctx.accounts.subscription_plan.format_amount(amount),
+        sequence,
+        ctx.accounts.user_subscription.get_subscription_id(),
+        ctx.accounts.subscription_plan.format_amount(payment.refunded_amount),
+        ctx.accounts.subscription_plan.format_amount(payment.amount),
+        payment.status
+    );
+
+    Ok(())
+}
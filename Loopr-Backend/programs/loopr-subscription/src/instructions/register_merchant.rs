@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct RegisterMerchant<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = Merchant::LEN,
+        seeds = [b"merchant", authority.key().as_ref()],
+        bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(
+    ctx: Context<RegisterMerchant>,
+    name: String,
+    logo_uri: String,
+    support_contact: String,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(name.len() <= 64, LooprError::MerchantNameTooLong);
+    require!(logo_uri.len() <= 128, LooprError::LogoUriTooLong);
+    require!(support_contact.len() <= 64, LooprError::SupportContactTooLong);
+
+    let merchant = &mut ctx.accounts.merchant;
+    let clock = Clock::get()?;
+
+    merchant.authority = ctx.accounts.authority.key();
+    merchant.set_name(&name);
+    merchant.set_logo_uri(&logo_uri);
+    merchant.set_support_contact(&support_contact);
+    merchant.payout_wallets = Vec::new();
+    merchant.status = MerchantStatus::Active;
+    merchant.total_plans = 0;
+    merchant.current_subscribers = 0;
+    merchant.total_revenue = 0;
+    merchant.created_at = clock.unix_timestamp;
+    merchant.updated_at = clock.unix_timestamp;
+    merchant.bump = ctx.bumps.merchant;
+    merchant.version = ACCOUNT_VERSION;
+
+    emit!(MerchantRegistered {
+        merchant: merchant.key(),
+        authority: merchant.authority,
+        name: merchant.get_name(),
+    });
+
+    msg!("Merchant registered: {}", merchant.get_name());
+    
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct ReleaseEscrow<'info> {
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+    
+    #[account(
+        mut,
+        constraint = user_subscription.subscription_plan == subscription_plan.key()
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    #[account(
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    /// CHECK: Program-owned PDA that owns every escrow vault
+    #[account(
+        seeds = [b"escrow_authority"],
+        bump
+    )]
+    pub escrow_authority: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == mint.key()
+    )]
+    pub plan_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Permissionless: once the dispute window has passed undisputed, anyone may
+/// pay the escrowed share out to the merchant and split partners
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, ReleaseEscrow<'info>>, sequence: u64) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let payment = ctx.accounts.payment_ledger.entry_mut(sequence)?;
+    require_keys_eq!(ctx.accounts.mint.key(), payment.mint, LooprError::InvalidMint);
+    let subscription_plan = &ctx.accounts.subscription_plan;
+    let Some(release_at) = payment.release_at else {
+        return err!(LooprError::PaymentNotEscrowed);
+    };
+    require!(
+        payment.status != PaymentStatus::Disputed,
+        LooprError::PaymentDisputed
+    );
+    require!(
+        Clock::get()?.unix_timestamp >= release_at,
+        LooprError::EscrowLocked
+    );
+
+    // Remaining accounts carry the split recipients' token accounts, then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(ctx.remaining_accounts, subscription_plan.revenue_split.len())?;
+
+    let escrow_seeds: &[&[u8]] = &[b"escrow_authority", &[ctx.bumps.escrow_authority]];
+    let amount = payment.escrowed_amount;
+    let net_amount = transfer::pay_out(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.escrow_vault.to_account_info(),
+        &ctx.accounts.mint,
+        &ctx.accounts.plan_token_account.to_account_info(),
+        &ctx.accounts.escrow_authority.to_account_info(),
+        &subscription_plan.revenue_split,
+        &subscription_plan.split_shares(amount),
+        recipient_accounts,
+        extra_accounts,
+        amount,
+        &[escrow_seeds],
+    )?;
+
+    payment.release_escrow(net_amount);
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    user_subscription.escrowed_payments = user_subscription.escrowed_payments.saturating_sub(1);
+
+    emit!(EscrowReleased {
+        subscription: user_subscription.key(),
+        sequence,
+        mint: payment.mint,
+        amount,
+    });
+
+    msg!(
+        "Released {} from escrow for payment {} of subscription {}",
+        subscription_plan.format_amount(amount),
+        sequence,
+        user_subscription.get_subscription_id()
+    );
+    
+    Ok(())
+}
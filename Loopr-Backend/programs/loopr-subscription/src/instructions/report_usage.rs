@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+#[instruction(report_id: u64)]
+pub struct ReportUsage<'info> {
+    #[account(
+        mut,
+        constraint = user_subscription.status.is_active() @ LooprError::SubscriptionNotActive
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.key() == user_subscription.subscription_plan,
+        constraint = subscription_plan.is_usage_reporter(&reporter.key()) @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    /// One record per report id, so a retried report is never counted twice
+    #[account(
+        init,
+        payer = reporter,
+        space = UsageRecord::LEN,
+        seeds = [b"usage_record", user_subscription.key().as_ref(), &report_id.to_le_bytes()],
+        bump
+    )]
+    pub usage_record: Account<'info, UsageRecord>,
+    
+    /// The plan authority or its delegated usage reporter
+    #[account(mut)]
+    pub reporter: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<ReportUsage>, report_id: u64, units: u64) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(ctx.accounts.subscription_plan.metering.is_some(), LooprError::PlanNotMetered);
+    require!(units > 0, LooprError::InvalidUsage);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let clock = Clock::get()?;
+
+    user_subscription.period_usage = user_subscription.period_usage.checked_add(units).unwrap();
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    let usage_record = &mut ctx.accounts.usage_record;
+    usage_record.subscription = user_subscription.key();
+    usage_record.reporter = ctx.accounts.reporter.key();
+    usage_record.report_id = report_id;
+    usage_record.units = units;
+    usage_record.reported_at = clock.unix_timestamp;
+    usage_record.bump = ctx.bumps.usage_record;
+    usage_record.version = ACCOUNT_VERSION;
+
+    emit!(UsageReported {
+        subscription: user_subscription.key(),
+        report_id,
+        units,
+        period_usage: user_subscription.period_usage,
+    });
+
+    msg!(
+        "Usage reported for subscription {}: {} units ({} this period)",
+        user_subscription.get_subscription_id(),
+        units,
+        user_subscription.period_usage
+    );
+
+    Ok(())
+}
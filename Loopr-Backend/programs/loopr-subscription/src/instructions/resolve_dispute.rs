@@ -0,0 +1,172 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    #[account(
+        mut,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = payment_ledger.bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+    
+    #[account(
+        mut,
+        constraint = user_subscription.subscription_plan == subscription_plan.key()
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    #[account(
+        constraint = arbiter.key() == subscription_plan.arbiter(&global_state) @ LooprError::Unauthorized
+    )]
+    pub arbiter: Signer<'info>,
+    
+    /// CHECK: Program-owned PDA that owns every escrow vault
+    #[account(
+        seeds = [b"escrow_authority"],
+        bump
+    )]
+    pub escrow_authority: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    /// Payer's account any refunded part is returned to
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user_subscription.user @ LooprError::Unauthorized,
+        constraint = user_token_account.mint == mint.key()
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == mint.key()
+    )]
+    pub plan_token_account: InterfaceAccount<'info, TokenAccount>,
+    
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Settle a disputed payment: `refund_amount` of the escrowed share goes back
+/// to the subscriber and the rest is released to the merchant
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, ResolveDispute<'info>>,
+    sequence: u64,
+    refund_amount: u64,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let payment = ctx.accounts.payment_ledger.entry_mut(sequence)?;
+    require_keys_eq!(ctx.accounts.mint.key(), payment.mint, LooprError::InvalidMint);
+    let subscription_plan = &ctx.accounts.subscription_plan;
+    require!(
+        payment.status == PaymentStatus::Disputed,
+        LooprError::PaymentNotDisputed
+    );
+    let escrowed = payment.escrowed_amount;
+    require!(refund_amount <= escrowed, LooprError::InvalidRefundAmount);
+
+    // Remaining accounts carry the split recipients' token accounts, then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(ctx.remaining_accounts, subscription_plan.revenue_split.len())?;
+
+    let escrow_seeds: &[&[u8]] = &[b"escrow_authority", &[ctx.bumps.escrow_authority]];
+    let signer_seeds = &[escrow_seeds];
+
+    if refund_amount > 0 {
+        transfer::transfer_checked(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.escrow_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.escrow_authority.to_account_info(),
+            extra_accounts,
+            refund_amount,
+            signer_seeds,
+        )?;
+    }
+
+    let release_amount = escrowed - refund_amount;
+    let net_amount = if release_amount > 0 {
+        transfer::pay_out(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.escrow_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.plan_token_account.to_account_info(),
+            &ctx.accounts.escrow_authority.to_account_info(),
+            &subscription_plan.revenue_split,
+            &subscription_plan.split_shares(release_amount),
+            recipient_accounts,
+            extra_accounts,
+            release_amount,
+            signer_seeds,
+        )?
+    } else {
+        0
+    };
+
+    // The refunded part counts as released to the merchant and handed straight back
+    payment.release_escrow(net_amount.checked_add(refund_amount).unwrap());
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    user_subscription.escrowed_payments = user_subscription.escrowed_payments.saturating_sub(1);
+
+    if refund_amount > 0 {
+        payment.record_refund(refund_amount);
+
+        // Volume is kept net of refunds
+        let global_state = &mut ctx.accounts.global_state;
+        global_state.total_volume = global_state.total_volume.saturating_sub(refund_amount);
+        global_state.total_refunded = global_state.total_refunded.checked_add(refund_amount).unwrap();
+        ctx.accounts.merchant.record_refund(refund_amount);
+    } else {
+        payment.status = PaymentStatus::Completed;
+    }
+
+    emit!(DisputeResolved {
+        subscription: ctx.accounts.user_subscription.key(),
+        sequence,
+        arbiter: ctx.accounts.arbiter.key(),
+        mint: payment.mint,
+        refunded_amount: refund_amount,
+        released_amount: release_amount,
+    });
+
+    msg!(
+        "Dispute on payment {} of subscription {} resolved: {} refunded, {} released, status {:?}",
+        sequence,
+        ctx.accounts.user_subscription.get_subscription_id(),
+        subscription_plan.format_amount(refund_amount),
+        subscription_plan.format_amount(release_amount),
+        payment.status
+    );
+    
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*, pda::*};
+
+#[derive(Accounts)]
+pub struct ResumeSubscription<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status == SubscriptionStatus::Paused @ LooprError::SubscriptionNotPaused
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<ResumeSubscription>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    // Paused time beyond the plan's maximum is not credited
+    user_subscription.resume(clock.unix_timestamp, subscription_plan);
+    user_subscription.refresh_status(clock.unix_timestamp, subscription_plan);
+
+    emit!(SubscriptionResumed {
+        subscription: user_subscription.key(),
+        next_payment_due: user_subscription.next_payment_due,
+    });
+
+    msg!(
+        "Subscription resumed: {}, next payment due {}",
+        user_subscription.get_subscription_id(),
+        user_subscription.next_payment_due
+    );
+    
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetDisputeWindow<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+    
+    /// CHECK: Program-owned PDA that owns every escrow vault
+    #[account(
+        seeds = [b"escrow_authority"],
+        bump
+    )]
+    pub escrow_authority: UncheckedAccount<'info>,
+    
+    /// Token account holding the plan's escrowed payments
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = escrow_authority,
+        token::token_program = token_program
+    )]
+    pub escrow_vault: InterfaceAccount<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler(ctx: Context<SetDisputeWindow>, dispute_window: i64) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(
+        (0..=SubscriptionPlan::MAX_DISPUTE_WINDOW).contains(&dispute_window),
+        LooprError::InvalidDisputeWindow
+    );
+
+    // Payments already in escrow keep the release time they were given
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    subscription_plan.dispute_window = dispute_window;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    msg!(
+        "Plan {} holds payments in escrow {} for {}s",
+        subscription_plan.get_plan_id(),
+        ctx.accounts.escrow_vault.key(),
+        dispute_window
+    );
+    
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetMaxSlippage<'info> {
+    #[account(
+        mut,
+        constraint = user_subscription.user == user.key() @ LooprError::Unauthorized,
+        constraint = user_subscription.status.is_open() @ LooprError::SubscriptionEnded
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    
+    pub user: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<SetMaxSlippage>, max_slippage_bps: u16) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(max_slippage_bps <= 10_000, LooprError::InvalidSlippage);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    user_subscription.max_slippage_bps = max_slippage_bps;
+    user_subscription.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(MaxSlippageSet {
+        subscription: user_subscription.key(),
+        max_slippage_bps,
+    });
+
+    msg!(
+        "Subscription {} slippage cap set to {} bps",
+        user_subscription.get_subscription_id(),
+        max_slippage_bps
+    );
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetMerchantStatus<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.authority.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    /// Merchants are suspended by the protocol, not by themselves
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        constraint = global_state.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub authority: Signer<'info>,
+}
+
+pub fn handler(ctx: Context<SetMerchantStatus>, status: MerchantStatus) -> Result<()> {
+    let merchant = &mut ctx.accounts.merchant;
+    merchant.status = status;
+    merchant.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(MerchantStatusChanged {
+        merchant: merchant.key(),
+        status,
+    });
+
+    msg!("Merchant {} is now {:?}", merchant.get_name(), merchant.status);
+    
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetMetering<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<SetMetering>, metering: Option<Metering>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    if let Some(metering) = &metering {
+        require!(metering.unit_price > 0, LooprError::InvalidUnitPrice);
+    }
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    // Usage is billed at the plan's current rates, so only the reporter may
+    // change once anyone subscribes
+    let rates = |metering: &Option<Metering>| metering.map(|m| (m.unit_price, m.included_units));
+    require!(
+        subscription_plan.current_subscribers == 0 || rates(&metering) == rates(&subscription_plan.metering),
+        LooprError::PlanHasSubscribers
+    );
+    subscription_plan.metering = metering;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    match &subscription_plan.metering {
+        Some(metering) => msg!(
+            "Plan {} metered at {} per unit beyond {} included units",
+            subscription_plan.get_plan_id(),
+            subscription_plan.format_amount(metering.unit_price),
+            metering.included_units
+        ),
+        None => msg!("Plan {} no longer metered", subscription_plan.get_plan_id()),
+    }
+
+    Ok(())
+}
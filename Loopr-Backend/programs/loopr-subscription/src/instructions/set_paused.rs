@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        constraint = global_state.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub authority: Signer<'info>,
+}
+
+pub fn handler(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    global_state.is_paused = paused;
+
+    emit!(ProgramPauseChanged {
+        global_state: global_state.key(),
+        is_paused: paused,
+    });
+
+    msg!("Program {}", if paused { "paused" } else { "unpaused" });
+    
+    Ok(())
+}
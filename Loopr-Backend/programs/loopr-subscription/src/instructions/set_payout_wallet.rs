@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetPayoutWallet<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", authority.key().as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<SetPayoutWallet>, mint: Pubkey, wallet: Option<Pubkey>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let merchant = &mut ctx.accounts.merchant;
+
+    // Clearing a mint's wallet sends its payments back to the authority
+    merchant.payout_wallets.retain(|payout| payout.mint != mint);
+    if let Some(wallet) = wallet {
+        require!(wallet != Pubkey::default(), LooprError::InvalidPayoutWallet);
+        require!(
+            merchant.payout_wallets.len() < Merchant::MAX_PAYOUT_WALLETS,
+            LooprError::TooManyPayoutWallets
+        );
+        merchant.payout_wallets.push(PayoutWallet { mint, wallet });
+    }
+    merchant.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PayoutWalletSet {
+        merchant: merchant.key(),
+        mint,
+        wallet,
+    });
+
+    msg!(
+        "Merchant {} paid in mint {} at {}",
+        merchant.get_name(),
+        mint,
+        merchant.payout_wallet(&mint)
+    );
+    
+    Ok(())
+}
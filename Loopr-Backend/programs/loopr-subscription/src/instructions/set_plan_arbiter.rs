@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetPlanArbiter<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    /// Arbiters are appointed by the protocol, not the merchant whose payments they judge
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        constraint = global_state.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub authority: Signer<'info>,
+}
+
+pub fn handler(ctx: Context<SetPlanArbiter>, arbiter: Option<Pubkey>) -> Result<()> {
+    if let Some(arbiter) = arbiter {
+        require!(arbiter != Pubkey::default(), LooprError::InvalidArbiter);
+        require!(
+            arbiter != ctx.accounts.subscription_plan.authority,
+            LooprError::InvalidArbiter
+        );
+    }
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    subscription_plan.arbiter = arbiter;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    msg!(
+        "Plan {} disputes resolved by {}",
+        subscription_plan.get_plan_id(),
+        subscription_plan.arbiter(&ctx.accounts.global_state)
+    );
+    
+    Ok(())
+}
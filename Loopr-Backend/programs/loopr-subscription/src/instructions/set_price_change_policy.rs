@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetPriceChangePolicy<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(
+    ctx: Context<SetPriceChangePolicy>,
+    price_notice_period: Option<i64>,
+    price_consent_threshold_bps: u16,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    if let Some(notice) = price_notice_period {
+        require!(notice >= 0, LooprError::InvalidNoticePeriod);
+    }
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    subscription_plan.price_notice_period = price_notice_period;
+    subscription_plan.price_consent_threshold_bps = price_consent_threshold_bps;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    match price_notice_period {
+        Some(notice) => msg!(
+            "Plan {} price increases reach subscribers after {}s notice, with consent above {} bps",
+            subscription_plan.get_plan_id(),
+            notice,
+            price_consent_threshold_bps
+        ),
+        None => msg!(
+            "Plan {} subscribers keep their price until they accept a new one",
+            subscription_plan.get_plan_id()
+        ),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetPricingModel<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(
+    ctx: Context<SetPricingModel>,
+    pricing_model: PricingModel,
+    price_tiers: Vec<PriceTier>,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    // Subscribers' pinned prices don't cover tiers, so the model is fixed once anyone subscribes
+    require!(
+        ctx.accounts.subscription_plan.current_subscribers == 0,
+        LooprError::PlanHasSubscribers
+    );
+
+    match pricing_model {
+        PricingModel::Flat | PricingModel::PerUnit => {
+            require!(price_tiers.is_empty(), LooprError::InvalidPriceTiers);
+        }
+        PricingModel::Graduated | PricingModel::Volume => {
+            require!(
+                !price_tiers.is_empty() && price_tiers.len() <= SubscriptionPlan::MAX_PRICE_TIERS,
+                LooprError::InvalidPriceTiers
+            );
+            // Bounds must strictly increase, ending in an open-ended tier so every quantity is priced
+            let (top, bounded) = price_tiers.split_last().unwrap();
+            require!(top.up_to.is_none(), LooprError::InvalidPriceTiers);
+            let mut previous = 0u64;
+            for tier in bounded {
+                let Some(up_to) = tier.up_to else {
+                    return err!(LooprError::InvalidPriceTiers);
+                };
+                require!(up_to > previous, LooprError::InvalidPriceTiers);
+                previous = up_to;
+            }
+        }
+    }
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    subscription_plan.pricing_model = pricing_model;
+    subscription_plan.price_tiers = price_tiers;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    msg!(
+        "Plan {} now priced {:?} with {} tiers",
+        subscription_plan.get_plan_id(),
+        subscription_plan.pricing_model,
+        subscription_plan.price_tiers.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct SetRevenueSplit<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<SetRevenueSplit>, recipients: Vec<SplitRecipient>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    require!(
+        recipients.len() <= SubscriptionPlan::MAX_SPLIT_RECIPIENTS,
+        LooprError::TooManySplitRecipients
+    );
+
+    // Shares are of the merchant's part of each payment; the authority keeps whatever is left
+    let mut total_bps: u32 = 0;
+    for (i, recipient) in recipients.iter().enumerate() {
+        require!(recipient.share_bps > 0, LooprError::InvalidSplitShares);
+        require!(
+            recipients[..i].iter().all(|other| other.wallet != recipient.wallet),
+            LooprError::InvalidSplitShares
+        );
+        total_bps += u32::from(recipient.share_bps);
+    }
+    require!(total_bps <= 10_000, LooprError::InvalidSplitShares);
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    subscription_plan.revenue_split = recipients;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    msg!(
+        "Revenue split for plan {} set: {} recipients sharing {} bps",
+        subscription_plan.get_plan_id(),
+        subscription_plan.revenue_split.len(),
+        total_bps
+    );
+
+    Ok(())
+}
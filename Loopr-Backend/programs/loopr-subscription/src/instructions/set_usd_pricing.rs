@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*, oracle};
+
+#[derive(Accounts)]
+pub struct SetUsdPricing<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+
+    /// CHECK: Checked against the new pricing's feed, product and the price
+    /// oracle; only needed when setting USD pricing
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(ctx: Context<SetUsdPricing>, usd_pricing: Option<UsdPricing>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+    if let Some(usd_pricing) = &usd_pricing {
+        require!(
+            usd_pricing.price_feed != Pubkey::default()
+                && usd_pricing.product != Pubkey::default()
+                && usd_pricing.max_price_age > 0
+                && usd_pricing.max_confidence_bps > 0
+                && usd_pricing.max_confidence_bps <= 10_000,
+            LooprError::InvalidUsdPricing
+        );
+        let Some(price_feed) = &ctx.accounts.price_feed else {
+            return err!(LooprError::MissingPriceFeed);
+        };
+        oracle::load_price_feed(price_feed, usd_pricing, &ctx.accounts.global_state.price_oracle)?;
+    }
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+
+    // Existing subscribers' pinned prices and credit are in the plan's
+    // current currency, and they agreed to pay against its current feed, so
+    // the pricing is fixed once anybody is subscribed
+    require!(
+        subscription_plan.usd_pricing == usd_pricing || subscription_plan.current_subscribers == 0,
+        LooprError::PlanHasSubscribers
+    );
+
+    subscription_plan.usd_pricing = usd_pricing;
+    subscription_plan.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    match &subscription_plan.usd_pricing {
+        Some(usd_pricing) => msg!(
+            "Plan {} priced in USD via feed {}",
+            subscription_plan.get_plan_id(),
+            usd_pricing.price_feed
+        ),
+        None => msg!("Plan {} priced in its mint", subscription_plan.get_plan_id()),
+    }
+
+    Ok(())
+}
@@ -1,12 +1,12 @@
 use anchor_lang::prelude::*;
-use crate::{state::*, errors::*};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, oracle, transfer, events::*, pda::*};
 
 #[derive(Accounts)]
-#[instruction(subscription_id: String)]
 pub struct SubscribeAndPay<'info> {
     #[account(
         mut,
-        seeds = [b"payment_intent", payment_intent.intent_id.as_bytes()],
+        seeds = [b"payment_intent", payment_intent.get_intent_id().as_bytes()],
         bump = payment_intent.bump,
         constraint = payment_intent.status == PaymentIntentStatus::Created @ LooprError::InvalidPaymentIntentStatus
     )]
@@ -14,17 +14,24 @@ pub struct SubscribeAndPay<'info> {
     
     #[account(
         mut,
-        seeds = [b"subscription_plan", payment_intent.plan_id.as_bytes()],
+        seeds = [b"subscription_plan", payment_intent.get_plan_id().as_bytes()],
         bump = subscription_plan.bump,
         constraint = subscription_plan.is_active @ LooprError::PlanNotActive
     )]
     pub subscription_plan: Account<'info, SubscriptionPlan>,
     
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant,
+        constraint = merchant.is_active() @ LooprError::MerchantNotActive
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
     #[account(
         init,
         payer = user,
         space = UserSubscription::LEN,
-        seeds = [b"user_subscription", user.key().as_ref(), subscription_id.as_bytes()],
+        seeds = [USER_SUBSCRIPTION_SEED, user.key().as_ref(), subscription_plan.key().as_ref()],
         bump
     )]
     pub user_subscription: Account<'info, UserSubscription>,
@@ -32,10 +39,95 @@ pub struct SubscribeAndPay<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
     
-    /// CHECK: Authority that receives payment
-    #[account(mut)]
+    /// History of the subscription's payments, paid for by the subscriber
+    #[account(
+        init,
+        payer = user,
+        space = PaymentLedger::LEN,
+        seeds = [PAYMENT_LEDGER_SEED, user_subscription.key().as_ref()],
+        bump
+    )]
+    pub payment_ledger: Account<'info, PaymentLedger>,
+    
+    /// Remembers this wallet's trial on the plan so it is only granted once
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = TrialRecord::LEN,
+        seeds = [b"trial_record", subscription_plan.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub trial_record: Account<'info, TrialRecord>,
+    
+    /// CHECK: Merchant's payout wallet, which receives native SOL payments
+    #[account(
+        mut,
+        constraint = authority.key() == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet
+    )]
     pub authority: AccountInfo<'info>,
     
+    /// CHECK: Protocol treasury that receives the fee on native SOL payments
+    #[account(
+        mut,
+        constraint = treasury.key() == global_state.treasury @ LooprError::InvalidTreasury
+    )]
+    pub treasury: AccountInfo<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", subscription_plan.settlement_mint().as_ref()],
+        bump
+    )]
+    pub fee_stats: Account<'info, FeeStats>,
+    
+    /// Coupon the intent was discounted with; required when the intent names one
+    #[account(
+        mut,
+        constraint = Some(coupon.key()) == payment_intent.coupon @ LooprError::InvalidCoupon
+    )]
+    pub coupon: Option<Account<'info, Coupon>>,
+    
+    /// Token accounts below are only needed when the plan is priced in an SPL mint,
+    /// or settles native SOL in wrapped SOL while it holds payments in escrow
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: Option<InterfaceAccount<'info, Mint>>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key(),
+        constraint = user_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub user_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub plan_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub treasury_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// Plan's escrow vault; required when the plan holds payments for a dispute window
+    #[account(
+        mut,
+        seeds = [b"escrow_vault", subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+    
+    /// CHECK: Pyth-format price account, checked against the plan's USD pricing; only needed for USD-priced plans
+    pub price_feed: Option<UncheckedAccount<'info>>,
+    
     #[account(
         mut,
         seeds = [b"global_state"],
@@ -43,15 +135,18 @@ pub struct SubscribeAndPay<'info> {
     )]
     pub global_state: Account<'info, GlobalState>,
     
+    pub token_program: Option<Interface<'info, TokenInterface>>,
     pub system_program: Program<'info, System>,
 }
 
-pub fn handler(
-    ctx: Context<SubscribeAndPay>,
+pub fn handler<'info>(
+    ctx: Context<'_, '_, '_, 'info, SubscribeAndPay<'info>>,
     subscription_id: String,
+    max_slippage_bps: u16,
 ) -> Result<()> {
     require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
     require!(subscription_id.len() <= 64, LooprError::SubscriptionIdTooLong);
+    require!(max_slippage_bps <= 10_000, LooprError::InvalidSlippage);
     
     let payment_intent = &mut ctx.accounts.payment_intent;
     let subscription_plan = &mut ctx.accounts.subscription_plan;
@@ -71,35 +166,191 @@ pub fn handler(
         );
     }
     
-    let amount = payment_intent.amount;
+    let trial_record = &mut ctx.accounts.trial_record;
+    trial_record.user = ctx.accounts.user.key();
+    trial_record.subscription_plan = subscription_plan.key();
+    trial_record.bump = ctx.bumps.trial_record;
+    trial_record.version = ACCOUNT_VERSION;
+    let trial_end = trial_record.claim(subscription_plan, clock.unix_timestamp);
     
-    // Transfer SOL from user to authority
-    let transfer_instruction = anchor_lang::system_program::Transfer {
-        from: ctx.accounts.user.to_account_info(),
-        to: ctx.accounts.authority.to_account_info(),
+    // A trial starts without charging; the first payment is due when it ends
+    let amount = if trial_end.is_some() {
+        0
+    } else {
+        oracle::to_settlement_amount(
+            subscription_plan,
+            ctx.accounts.price_feed.as_ref().map(|feed| feed.as_ref()),
+            &ctx.accounts.global_state.price_oracle,
+            max_slippage_bps,
+            payment_intent.amount,
+            clock.unix_timestamp,
+        )?
     };
     
-    let cpi_ctx = CpiContext::new(
-        ctx.accounts.system_program.to_account_info(),
-        transfer_instruction,
-    );
+    // The protocol fee comes out of the merchant's share
+    let protocol_fee = ctx.accounts.global_state.protocol_fee(amount);
+    let merchant_amount = amount.checked_sub(protocol_fee).unwrap();
+    
+    // Remaining accounts carry the split recipients' accounts (wallets for
+    // native SOL, token accounts otherwise), then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(ctx.remaining_accounts, subscription_plan.revenue_split.len())?;
     
-    anchor_lang::system_program::transfer(cpi_ctx, amount)?;
+    let net_amount = if amount == 0 {
+        // Nothing to collect
+        0
+    } else if subscription_plan.is_native_sol() && !subscription_plan.holds_in_escrow() {
+        // Transfer SOL from user to authority and any revenue split partners
+        transfer::pay_out_lamports(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &subscription_plan.revenue_split,
+            &subscription_plan.split_shares(merchant_amount),
+            recipient_accounts,
+            merchant_amount,
+        )?;
+        
+        if protocol_fee > 0 {
+            let fee_instruction = anchor_lang::system_program::Transfer {
+                from: ctx.accounts.user.to_account_info(),
+                to: ctx.accounts.treasury.to_account_info(),
+            };
+            
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                fee_instruction,
+            );
+            
+            anchor_lang::system_program::transfer(cpi_ctx, protocol_fee)?;
+        }
+        
+        merchant_amount
+    } else {
+        // Pay the plan authority and any revenue split partners, or escrow their share
+        let (Some(mint), Some(user_token_account), Some(plan_token_account), Some(token_program)) = (
+            &ctx.accounts.mint,
+            &ctx.accounts.user_token_account,
+            &ctx.accounts.plan_token_account,
+            &ctx.accounts.token_program,
+        ) else {
+            return err!(LooprError::MissingTokenAccounts);
+        };
+        
+        let net_amount = transfer::pay_merchant(
+            &token_program.to_account_info(),
+            &user_token_account.to_account_info(),
+            mint,
+            subscription_plan,
+            &plan_token_account.to_account_info(),
+            ctx.accounts.escrow_vault.as_ref().map(|vault| vault.to_account_info()),
+            &ctx.accounts.user.to_account_info(),
+            recipient_accounts,
+            extra_accounts,
+            merchant_amount,
+            &[],
+        )?;
+        
+        if protocol_fee > 0 {
+            let Some(treasury_token_account) = &ctx.accounts.treasury_token_account else {
+                return err!(LooprError::MissingTokenAccounts);
+            };
+            
+            transfer::transfer_checked(
+                &token_program.to_account_info(),
+                &user_token_account.to_account_info(),
+                mint,
+                &treasury_token_account.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                extra_accounts,
+                protocol_fee,
+                &[],
+            )?;
+        }
+        
+        net_amount
+    };
     
     // Create user subscription
     let user_subscription = &mut ctx.accounts.user_subscription;
     user_subscription.user = ctx.accounts.user.key();
     user_subscription.subscription_plan = subscription_plan.key();
-    user_subscription.subscription_id = subscription_id;
-    user_subscription.is_active = true;
-    user_subscription.next_payment_due = clock.unix_timestamp + subscription_plan.period_duration;
-    user_subscription.last_payment_date = Some(clock.unix_timestamp);
-    user_subscription.auto_pay_enabled = true; // Enable autopay for QR payments
-    user_subscription.payment_thread = None;
-    user_subscription.total_payments_made = 1;
+    user_subscription.set_subscription_id(&subscription_id);
+    match trial_end {
+        Some(trial_end) => {
+            user_subscription.status = SubscriptionStatus::Trialing;
+            user_subscription.next_payment_due = trial_end;
+            user_subscription.last_payment_date = None;
+        }
+        None => {
+            user_subscription.status = SubscriptionStatus::Active;
+            user_subscription.next_payment_due = clock.unix_timestamp + subscription_plan.period_duration;
+            user_subscription.last_payment_date = Some(clock.unix_timestamp);
+        }
+    }
+    user_subscription.failed_payment_attempts = 0;
+    user_subscription.last_failed_attempt = None;
+    user_subscription.paused_at = None;
+    user_subscription.pause_window_start = 0;
+    user_subscription.pauses_in_window = 0;
+    user_subscription.auto_pay_enabled = false; // Autopay needs an explicit mandate via enable_autopay
+    user_subscription.autopay_token_account = None;
+    user_subscription.autopay_allowance = 0;
+    user_subscription.credit_balance = 0;
+    user_subscription.discount = None;
+    user_subscription.period_usage = 0;
+    user_subscription.quantity = 1;
+    user_subscription.pin_price(subscription_plan);
+    user_subscription.stream = None;
+    user_subscription.max_slippage_bps = max_slippage_bps;
+    user_subscription.escrowed_payments = 0;
+    user_subscription.total_payments_made = if amount > 0 { 1 } else { 0 };
     user_subscription.created_at = clock.unix_timestamp;
     user_subscription.updated_at = clock.unix_timestamp;
     user_subscription.bump = ctx.bumps.user_subscription;
+    user_subscription.version = ACCOUNT_VERSION;
+    
+    let payment_ledger = &mut ctx.accounts.payment_ledger;
+    payment_ledger.subscription = user_subscription.key();
+    payment_ledger.next_sequence = 0;
+    payment_ledger.history_hash = [0u8; 32];
+    payment_ledger.entries = Vec::new();
+    payment_ledger.bump = ctx.bumps.payment_ledger;
+    payment_ledger.version = ACCOUNT_VERSION;
+    let escrowed = amount > 0 && subscription_plan.holds_in_escrow();
+    let sequence = if amount > 0 {
+        let mut entry = PaymentEntry::from_fields(
+            amount,
+            subscription_plan.settlement_mint(),
+            subscription_plan.mint_decimals,
+            merchant_amount.checked_sub(net_amount).unwrap(),
+            protocol_fee,
+            net_amount,
+            clock.unix_timestamp,
+            PaymentMethod::QRCode,
+            PaymentStatus::Completed,
+        );
+        if escrowed {
+            entry.hold_in_escrow(net_amount, clock.unix_timestamp + subscription_plan.dispute_window);
+            user_subscription.escrowed_payments = 1;
+        }
+        Some(payment_ledger.append(entry)?)
+    } else {
+        None
+    };
+    
+    // Redeem the intent's coupon; the amount just paid already covered its first period
+    if payment_intent.coupon.is_some() {
+        let Some(coupon) = ctx.accounts.coupon.as_mut() else {
+            return err!(LooprError::InvalidCoupon);
+        };
+        coupon.check_redeemable(subscription_plan, clock.unix_timestamp)?;
+        coupon.times_redeemed = coupon.times_redeemed.checked_add(1).unwrap();
+        user_subscription.discount = Some(coupon.to_applied(coupon.key()));
+        if amount > 0 {
+            user_subscription.consume_discount_period();
+        }
+    }
     
     // Update payment intent
     payment_intent.payer = Some(ctx.accounts.user.key());
@@ -114,25 +365,75 @@ pub fn handler(
         .unwrap();
     subscription_plan.updated_at = clock.unix_timestamp;
     
+    let merchant = &mut ctx.accounts.merchant;
+    merchant.current_subscribers = merchant
+        .current_subscribers
+        .checked_add(1)
+        .unwrap();
+    merchant.record_revenue(amount);
+    
     // Update global state
     let global_state = &mut ctx.accounts.global_state;
     global_state.total_subscriptions = global_state
         .total_subscriptions
         .checked_add(1)
         .unwrap();
-    global_state.total_payments_processed = global_state
-        .total_payments_processed
-        .checked_add(1)
-        .unwrap();
+    if amount > 0 {
+        global_state.total_payments_processed = global_state
+            .total_payments_processed
+            .checked_add(1)
+            .unwrap();
+    }
     global_state.total_volume = global_state
         .total_volume
         .checked_add(amount)
         .unwrap();
     
-    msg!(
-        "QR payment completed: {} lamports for subscription {}",
+    let fee_stats = &mut ctx.accounts.fee_stats;
+    fee_stats.mint = subscription_plan.settlement_mint();
+    fee_stats.bump = ctx.bumps.fee_stats;
+    fee_stats.version = ACCOUNT_VERSION;
+    if amount > 0 {
+        fee_stats.record(amount, protocol_fee);
+    }
+    
+    emit!(SubscriptionCreated {
+        subscription: user_subscription.key(),
+        subscription_id: user_subscription.get_subscription_id(),
+        user: user_subscription.user,
+        plan: subscription_plan.key(),
+        status: user_subscription.status,
+        next_payment_due: user_subscription.next_payment_due,
+    });
+    if let Some(sequence) = sequence {
+        emit!(PaymentCollected {
+            subscription: user_subscription.key(),
+            user: user_subscription.user,
+            plan: subscription_plan.key(),
+            sequence,
+            mint: subscription_plan.settlement_mint(),
+            amount,
+            protocol_fee,
+            keeper_bounty: 0,
+            net_amount,
+            payment_method: PaymentMethod::QRCode,
+            escrowed,
+            next_payment_due: user_subscription.next_payment_due,
+        });
+    }
+    emit!(IntentFulfilled {
+        intent: payment_intent.key(),
+        intent_id: payment_intent.get_intent_id(),
+        payer: user_subscription.user,
+        subscription: user_subscription.key(),
         amount,
-        user_subscription.subscription_id
+    });
+    
+    msg!(
+        "QR payment completed: {} of mint {} for subscription {}",
+        subscription_plan.format_amount(amount),
+        subscription_plan.accepted_mint,
+        user_subscription.get_subscription_id()
     );
     
     Ok(())
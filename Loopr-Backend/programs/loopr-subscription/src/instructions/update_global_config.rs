@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct UpdateGlobalConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump,
+        constraint = global_state.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    
+    pub authority: Signer<'info>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateGlobalConfig>,
+    keeper_bounty_bps: Option<u16>,
+    fee_bps: Option<u16>,
+    treasury: Option<Pubkey>,
+    arbiter: Option<Pubkey>,
+    price_oracle: Option<Pubkey>,
+) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+
+    if let Some(keeper_bounty_bps) = keeper_bounty_bps {
+        require!(
+            keeper_bounty_bps <= GlobalState::MAX_KEEPER_BOUNTY_BPS,
+            LooprError::KeeperBountyTooHigh
+        );
+        global_state.keeper_bounty_bps = keeper_bounty_bps;
+    }
+    if let Some(fee_bps) = fee_bps {
+        require!(fee_bps <= GlobalState::MAX_FEE_BPS, LooprError::ProtocolFeeTooHigh);
+        global_state.fee_bps = fee_bps;
+    }
+    if let Some(treasury) = treasury {
+        require!(treasury != Pubkey::default(), LooprError::InvalidTreasury);
+        global_state.treasury = treasury;
+    }
+    if let Some(arbiter) = arbiter {
+        require!(arbiter != Pubkey::default(), LooprError::InvalidArbiter);
+        global_state.arbiter = arbiter;
+    }
+    if let Some(price_oracle) = price_oracle {
+        require!(price_oracle != Pubkey::default(), LooprError::InvalidPriceOracle);
+        global_state.price_oracle = price_oracle;
+    }
+
+    emit!(GlobalConfigUpdated {
+        global_state: global_state.key(),
+        treasury: global_state.treasury,
+        arbiter: global_state.arbiter,
+        price_oracle: global_state.price_oracle,
+        keeper_bounty_bps: global_state.keeper_bounty_bps,
+        fee_bps: global_state.fee_bps,
+    });
+
+    msg!(
+        "Global config updated: keeper bounty {} bps, protocol fee {} bps, treasury {}, arbiter {}, price oracle {}",
+        global_state.keeper_bounty_bps,
+        global_state.fee_bps,
+        global_state.treasury,
+        global_state.arbiter,
+        global_state.price_oracle
+    );
+    
+    Ok(())
+}
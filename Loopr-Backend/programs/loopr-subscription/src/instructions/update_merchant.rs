@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct UpdateMerchant<'info> {
+    #[account(
+        mut,
+        seeds = [b"merchant", authority.key().as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateMerchant>,
+    name: Option<String>,
+    logo_uri: Option<String>,
+    support_contact: Option<String>,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let merchant = &mut ctx.accounts.merchant;
+
+    if let Some(name) = name {
+        require!(name.len() <= 64, LooprError::MerchantNameTooLong);
+        merchant.set_name(&name);
+    }
+    if let Some(logo_uri) = logo_uri {
+        require!(logo_uri.len() <= 128, LooprError::LogoUriTooLong);
+        merchant.set_logo_uri(&logo_uri);
+    }
+    if let Some(support_contact) = support_contact {
+        require!(support_contact.len() <= 64, LooprError::SupportContactTooLong);
+        merchant.set_support_contact(&support_contact);
+    }
+    merchant.updated_at = Clock::get()?.unix_timestamp;
+
+    emit!(MerchantUpdated {
+        merchant: merchant.key(),
+        name: merchant.get_name(),
+        logo_uri: merchant.get_logo_uri(),
+        support_contact: merchant.get_support_contact(),
+    });
+
+    msg!("Merchant updated: {}", merchant.get_name());
+    
+    Ok(())
+}
@@ -1 +1,89 @@
-use anchor_lang::prelude::*;use crate::{state::*, errors::*};#[derive(Accounts)]pub struct UpdateSubscriptionPlan<'info> {    #[account(        mut,        seeds = [b"subscription_plan", subscription_plan.plan_id.as_bytes()],        bump = subscription_plan.bump,        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized    )]    pub subscription_plan: Account<'info, SubscriptionPlan>,        pub authority: Signer<'info>,        #[account(        seeds = [b"global_state"],        bump = global_state.bump    )]    pub global_state: Account<'info, GlobalState>,}pub fn handler(    ctx: Context<UpdateSubscriptionPlan>,    name: Option<String>,    description: Option<String>,    price_per_period: Option<u64>,    period_duration: Option<i64>,    max_subscribers: Option<u32>,    is_active: Option<bool>,) -> Result<()> {    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);    let subscription_plan = &mut ctx.accounts.subscription_plan;    let clock = Clock::get()?;    if let Some(name) = name {        require!(name.len() <= 128, LooprError::PlanNameTooLong);        subscription_plan.name = name;    }    if let Some(description) = description {        require!(description.len() <= 256, LooprError::PlanDescriptionTooLong);        subscription_plan.description = description;    }    if let Some(price) = price_per_period {        subscription_plan.price_per_period = price;    }    if let Some(duration) = period_duration {        require!(duration > 0, LooprError::InvalidPeriodDuration);        subscription_plan.period_duration = duration;    }    if let Some(max_subs) = max_subscribers {        subscription_plan.max_subscribers = Some(max_subs);    }    if let Some(active) = is_active {        subscription_plan.is_active = active;    }    subscription_plan.updated_at = clock.unix_timestamp;    msg!("Subscription plan updated: {}", subscription_plan.plan_id);    Ok(())}
+use anchor_lang::prelude::*;
+use crate::{state::*, errors::*, events::*};
+
+#[derive(Accounts)]
+pub struct UpdateSubscriptionPlan<'info> {
+    #[account(
+        mut,
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateSubscriptionPlan>,
+    name: Option<String>,
+    description: Option<String>,
+    price_per_period: Option<u64>,
+    period_duration: Option<i64>,
+    trial_duration: Option<i64>,
+    grace_period: Option<i64>,
+    max_retry_attempts: Option<u8>,
+    max_pause_duration: Option<i64>,
+    max_pauses_per_year: Option<u8>,
+    max_subscribers: Option<u32>,
+    is_active: Option<bool>,
+) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let subscription_plan = &mut ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    if let Some(name) = name {
+        require!(name.len() <= 128, LooprError::PlanNameTooLong);
+        subscription_plan.set_name(&name);
+    }
+    if let Some(description) = description {
+        require!(description.len() <= 256, LooprError::PlanDescriptionTooLong);
+        subscription_plan.set_description(&description);
+    }
+    if let Some(duration) = period_duration {
+        require!(duration > 0, LooprError::InvalidPeriodDuration);
+    }
+    // Price changes publish a new version; existing subscribers keep theirs
+    // until the plan's notice period and consent rules let it reach them
+    let price = price_per_period.unwrap_or(subscription_plan.price_per_period);
+    let duration = period_duration.unwrap_or(subscription_plan.period_duration);
+    subscription_plan.set_price(price, duration, clock.unix_timestamp);
+    if let Some(trial) = trial_duration {
+        require!(trial >= 0, LooprError::InvalidTrialDuration);
+        subscription_plan.trial_duration = trial;
+    }
+    if let Some(grace) = grace_period {
+        require!(grace >= 0, LooprError::InvalidGracePeriod);
+        subscription_plan.grace_period = grace;
+    }
+    if let Some(retries) = max_retry_attempts {
+        subscription_plan.max_retry_attempts = retries;
+    }
+    if let Some(pause) = max_pause_duration {
+        require!(pause >= 0, LooprError::InvalidPauseDuration);
+        subscription_plan.max_pause_duration = pause;
+    }
+    if let Some(pauses) = max_pauses_per_year {
+        subscription_plan.max_pauses_per_year = pauses;
+    }
+    if let Some(max_subs) = max_subscribers {
+        subscription_plan.max_subscribers = Some(max_subs);
+    }
+    if let Some(active) = is_active {
+        subscription_plan.is_active = active;
+    }
+    subscription_plan.updated_at = clock.unix_timestamp;
+
+    emit!(PlanUpdated::new(subscription_plan.key(), subscription_plan));
+
+    msg!("Subscription plan updated: {}", subscription_plan.get_plan_id());
+
+    Ok(())
+}
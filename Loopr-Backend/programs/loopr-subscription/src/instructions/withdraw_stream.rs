@@ -0,0 +1,171 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use crate::{state::*, errors::*, transfer, events::*};
+
+#[derive(Accounts)]
+pub struct WithdrawStream<'info> {
+    #[account(
+        mut,
+        constraint = user_subscription.stream.is_some() @ LooprError::SubscriptionNotStreaming
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+
+    #[account(
+        seeds = [b"subscription_plan", subscription_plan.get_plan_id().as_bytes()],
+        bump = subscription_plan.bump,
+        constraint = subscription_plan.key() == user_subscription.subscription_plan,
+        constraint = subscription_plan.authority == authority.key() @ LooprError::Unauthorized
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+
+    #[account(
+        mut,
+        address = subscription_plan.merchant @ LooprError::InvalidMerchant
+    )]
+    pub merchant: Account<'info, Merchant>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Program-owned PDA that owns every stream vault
+    #[account(
+        seeds = [b"stream_authority"],
+        bump
+    )]
+    pub stream_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"stream_vault", user_subscription.key().as_ref()],
+        bump
+    )]
+    pub stream_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = plan_token_account.owner == merchant.payout_wallet(&subscription_plan.accepted_mint) @ LooprError::InvalidPayoutWallet,
+        constraint = plan_token_account.mint == mint.key()
+    )]
+    pub plan_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        constraint = mint.key() == subscription_plan.settlement_mint() @ LooprError::InvalidMint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Treasury's account in the payment mint, receiving the protocol fee
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == global_state.treasury @ LooprError::InvalidTreasury,
+        constraint = treasury_token_account.mint == mint.key() @ LooprError::InvalidMint
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = FeeStats::LEN,
+        seeds = [b"fee_stats", mint.key().as_ref()],
+        bump
+    )]
+    pub fee_stats: Account<'info, FeeStats>,
+
+    #[account(
+        mut,
+        seeds = [b"global_state"],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+pub fn handler<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawStream<'info>>) -> Result<()> {
+    require!(!ctx.accounts.global_state.is_paused, LooprError::ProgramPaused);
+
+    let user_subscription = &mut ctx.accounts.user_subscription;
+    let subscription_plan = &ctx.accounts.subscription_plan;
+    let clock = Clock::get()?;
+
+    let price = user_subscription.stream_price(subscription_plan)?;
+    let mut stream = user_subscription.stream.unwrap();
+    stream.accrue(
+        clock.unix_timestamp,
+        price,
+        user_subscription.period_duration,
+        ctx.accounts.stream_vault.amount,
+    );
+    let amount = stream.accrued;
+    require!(amount > 0, LooprError::NothingAccrued);
+
+    // The protocol fee comes out of the merchant's share
+    let protocol_fee = ctx.accounts.global_state.protocol_fee(amount);
+    let merchant_amount = amount.checked_sub(protocol_fee).unwrap();
+
+    // Remaining accounts carry the split recipients' token accounts, then any transfer-hook extras
+    let (recipient_accounts, extra_accounts) =
+        transfer::split_remaining_accounts(ctx.remaining_accounts, subscription_plan.revenue_split.len())?;
+
+    let stream_seeds: &[&[u8]] = &[b"stream_authority", &[ctx.bumps.stream_authority]];
+    let signer_seeds = &[stream_seeds];
+
+    transfer::pay_out(
+        &ctx.accounts.token_program.to_account_info(),
+        &ctx.accounts.stream_vault.to_account_info(),
+        &ctx.accounts.mint,
+        &ctx.accounts.plan_token_account.to_account_info(),
+        &ctx.accounts.stream_authority.to_account_info(),
+        &subscription_plan.revenue_split,
+        &subscription_plan.split_shares(merchant_amount),
+        recipient_accounts,
+        extra_accounts,
+        merchant_amount,
+        signer_seeds,
+    )?;
+
+    if protocol_fee > 0 {
+        transfer::transfer_checked(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.stream_vault.to_account_info(),
+            &ctx.accounts.mint,
+            &ctx.accounts.treasury_token_account.to_account_info(),
+            &ctx.accounts.stream_authority.to_account_info(),
+            extra_accounts,
+            protocol_fee,
+            signer_seeds,
+        )?;
+    }
+
+    // Only accrued funds leave the vault, so the paid-through time is unchanged
+    stream.accrued = 0;
+    user_subscription.stream = Some(stream);
+    user_subscription.last_payment_date = Some(clock.unix_timestamp);
+    user_subscription.updated_at = clock.unix_timestamp;
+
+    let global_state = &mut ctx.accounts.global_state;
+    global_state.total_payments_processed = global_state.total_payments_processed.checked_add(1).unwrap();
+    global_state.total_volume = global_state.total_volume.checked_add(amount).unwrap();
+    ctx.accounts.merchant.record_revenue(amount);
+
+    let fee_stats = &mut ctx.accounts.fee_stats;
+    fee_stats.mint = ctx.accounts.mint.key();
+    fee_stats.bump = ctx.bumps.fee_stats;
+    fee_stats.version = ACCOUNT_VERSION;
+    fee_stats.record(amount, protocol_fee);
+
+    emit!(StreamWithdrawn {
+        subscription: user_subscription.key(),
+        mint: ctx.accounts.mint.key(),
+        amount,
+        protocol_fee,
+    });
+
+    msg!(
+        "Withdrew {} accrued by subscription {}",
+        subscription_plan.format_amount(amount),
+        user_subscription.get_subscription_id()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,218 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::{state::*, errors::*, oracle, pda::*};
+
+/// Program state as it was first deployed, before protocol fees, refunds
+/// and the keeper bounty
+#[derive(AnchorDeserialize)]
+pub struct GlobalStateV1 {
+    pub authority: Pubkey,
+    pub total_plans: u64,
+    pub total_subscriptions: u64,
+    pub total_payments_processed: u64,
+    pub total_volume: u64,
+    pub is_paused: bool,
+    pub bump: u8,
+}
+
+impl GlobalStateV1 {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 8 + 1 + 1;
+
+    pub fn read(account: &AccountInfo) -> Result<Self> {
+        read_v1(account, &GlobalState::DISCRIMINATOR, Self::LEN, GlobalState::LEN)
+    }
+
+    /// The state in the current layout, with the defaults
+    /// `initialize_global_state` gives: no protocol fee, the authority as
+    /// treasury and arbiter, and Pyth as the price oracle
+    pub fn upgrade(&self) -> GlobalState {
+        GlobalState {
+            authority: self.authority,
+            total_plans: self.total_plans,
+            total_subscriptions: self.total_subscriptions,
+            total_payments_processed: self.total_payments_processed,
+            total_volume: self.total_volume,
+            total_refunded: 0,
+            is_paused: self.is_paused,
+            keeper_bounty_bps: GlobalState::DEFAULT_KEEPER_BOUNTY_BPS,
+            fee_bps: 0,
+            treasury: self.authority,
+            arbiter: self.authority,
+            price_oracle: oracle::pyth_oracle::ID,
+            pending_authority: None,
+            bump: self.bump,
+            version: ACCOUNT_VERSION,
+            reserved: [0; RESERVED_SPACE],
+        }
+    }
+}
+
+/// A plan as the program first wrote it, billed in native SOL and owned
+/// directly by its authority's wallet
+#[derive(AnchorDeserialize)]
+pub struct SubscriptionPlanV1 {
+    pub authority: Pubkey,
+    pub plan_id: [u8; 64],
+    pub name: [u8; 128],
+    pub description: [u8; 256],
+    pub price_per_period: u64,
+    pub period_duration: i64,
+    pub max_subscribers: Option<u32>,
+    pub current_subscribers: u32,
+    pub is_active: bool,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl SubscriptionPlanV1 {
+    pub const LEN: usize = 8 + 32 + 64 + 128 + 256 + 8 + 8 + (1 + 4) + 4 + 1 + 8 + 8 + 1;
+
+    pub fn read(account: &AccountInfo) -> Result<Self> {
+        read_v1(account, &SubscriptionPlan::DISCRIMINATOR, Self::LEN, SubscriptionPlan::LEN)
+    }
+
+    /// The plan in the current layout, still priced in native SOL and
+    /// attached to the authority's merchant profile, which the authority
+    /// registers before the plan can take payments again
+    pub fn upgrade(&self) -> SubscriptionPlan {
+        let mut plan = SubscriptionPlan::from_fields(
+            self.authority,
+            merchant_address(&self.authority).0,
+            &bytes_to_string(&self.plan_id),
+            &bytes_to_string(&self.name),
+            &bytes_to_string(&self.description),
+            self.price_per_period,
+            self.period_duration,
+            NATIVE_SOL_MINT,
+            NATIVE_SOL_DECIMALS,
+            self.max_subscribers,
+            self.is_active,
+            self.bump,
+        );
+        plan.current_subscribers = self.current_subscribers;
+        plan.created_at = self.created_at;
+        plan.updated_at = self.updated_at;
+        plan
+    }
+}
+
+/// A subscription as the program first wrote it, before subscriptions had a
+/// status machine or autopay mandates. `subscribe_and_pay` keyed these by
+/// their subscription id
+#[derive(AnchorDeserialize)]
+pub struct UserSubscriptionV1 {
+    pub user: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub subscription_id: [u8; 64],
+    pub is_active: bool,
+    pub next_payment_due: i64,
+    pub last_payment_date: Option<i64>,
+    pub auto_pay_enabled: bool,
+    pub payment_thread: Option<Pubkey>,
+    pub total_payments_made: u64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+impl UserSubscriptionV1 {
+    pub const LEN: usize = 8 + 32 + 32 + 64 + 1 + 8 + (1 + 8) + 1 + (1 + 32) + 8 + 8 + 8 + 1;
+
+    pub fn read(account: &AccountInfo) -> Result<Self> {
+        read_v1(account, &UserSubscription::DISCRIMINATOR, Self::LEN, UserSubscription::LEN)
+    }
+
+    pub fn get_subscription_id(&self) -> String {
+        bytes_to_string(&self.subscription_id)
+    }
+
+    /// The subscription in the current layout, pinned to `plan`'s current
+    /// price. Its Clockwork payment thread is no autopay mandate, so autopay
+    /// stays off until the subscriber enables it
+    pub fn upgrade(&self, plan: &SubscriptionPlan, bump: u8) -> UserSubscription {
+        let status = if self.is_active {
+            SubscriptionStatus::Active
+        } else {
+            SubscriptionStatus::Cancelled
+        };
+        let mut subscription = UserSubscription::from_fields(
+            self.user,
+            self.subscription_plan,
+            plan,
+            &self.get_subscription_id(),
+            status,
+            self.next_payment_due,
+            false,
+            bump,
+        );
+        subscription.last_payment_date = self.last_payment_date;
+        subscription.total_payments_made = self.total_payments_made;
+        subscription.created_at = self.created_at;
+        subscription
+    }
+}
+
+/// A payment intent as the program first wrote it, before intents recorded
+/// their creator or a coupon
+#[derive(AnchorDeserialize)]
+pub struct PaymentIntentV1 {
+    pub intent_id: [u8; 64],
+    pub plan_id: [u8; 64],
+    pub payer: Option<Pubkey>,
+    pub amount: u64,
+    pub status: PaymentIntentStatus,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub fulfilled_at: Option<i64>,
+    pub subscription: Option<Pubkey>,
+    pub bump: u8,
+}
+
+impl PaymentIntentV1 {
+    pub const LEN: usize = 8 + 64 + 64 + (1 + 32) + 8 + 1 + 8 + 8 + (1 + 8) + (1 + 32) + 1;
+
+    pub fn read(account: &AccountInfo) -> Result<Self> {
+        read_v1(account, &PaymentIntent::DISCRIMINATOR, Self::LEN, PaymentIntent::LEN)
+    }
+
+    /// The intent in the current layout. V1 never recorded who created an
+    /// intent, so `plan`'s authority stands in and is refunded its rent on close
+    pub fn upgrade(&self, plan: &SubscriptionPlan) -> PaymentIntent {
+        PaymentIntent {
+            intent_id: self.intent_id,
+            plan_id: self.plan_id,
+            authority: plan.authority,
+            payer: self.payer,
+            amount: self.amount,
+            status: self.status,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            fulfilled_at: self.fulfilled_at,
+            subscription: self.subscription,
+            coupon: None,
+            bump: self.bump,
+            version: ACCOUNT_VERSION,
+            reserved: [0; RESERVED_SPACE],
+        }
+    }
+}
+
+/// Deserialize a v1 account: it carries the current discriminator, at least
+/// `v1_len` bytes and less than the current `len`. V1 accounts were created
+/// with spare bytes after their fields, which are ignored
+fn read_v1<T: AnchorDeserialize>(
+    account: &AccountInfo,
+    discriminator: &[u8; 8],
+    v1_len: usize,
+    len: usize,
+) -> Result<T> {
+    require_keys_eq!(*account.owner, crate::ID, LooprError::UnknownAccountLayout);
+    let data = account.try_borrow_data()?;
+    require!(data.len() < len, LooprError::AccountAlreadyMigrated);
+    require!(
+        data.len() >= v1_len && data[..8] == discriminator[..],
+        LooprError::UnknownAccountLayout
+    );
+    T::deserialize(&mut &data[8..]).map_err(|_| error!(LooprError::UnknownAccountLayout))
+}
@@ -1,20 +1,60 @@
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
 pub mod state;
 pub mod errors;
 pub mod instructions;
+pub mod transfer;
+pub mod collection;
+pub mod oracle;
+pub mod events;
+pub mod pda;
+pub mod legacy;
 
 use instructions::*;
-use state::*;
-use errors::*;
+use state::{CouponDuration, DiscountType, MerchantStatus, Metering, PriceTier, PricingModel, SplitRecipient, UsdPricing};
 
-declare_id!("LooprSub11111111111111111111111111111111");
+declare_id!("LooprSub11111111111111111111111111111111111");
 
 #[program]
 pub mod loopr_subscription {
     use super::*;
 
+    /// Register the signer as a merchant, ready to publish plans
+    pub fn register_merchant(
+        ctx: Context<RegisterMerchant>,
+        name: String,
+        logo_uri: String,
+        support_contact: String,
+    ) -> Result<()> {
+        instructions::register_merchant::handler(ctx, name, logo_uri, support_contact)
+    }
+
+    /// Update a merchant's profile; fields left `None` are unchanged
+    pub fn update_merchant(
+        ctx: Context<UpdateMerchant>,
+        name: Option<String>,
+        logo_uri: Option<String>,
+        support_contact: Option<String>,
+    ) -> Result<()> {
+        instructions::update_merchant::handler(ctx, name, logo_uri, support_contact)
+    }
+
+    /// Route a mint's payments to `wallet`, or back to the merchant authority when `None`
+    pub fn set_payout_wallet(
+        ctx: Context<SetPayoutWallet>,
+        mint: Pubkey,
+        wallet: Option<Pubkey>,
+    ) -> Result<()> {
+        instructions::set_payout_wallet::handler(ctx, mint, wallet)
+    }
+
+    /// Suspend or reinstate a merchant
+    pub fn set_merchant_status(ctx: Context<SetMerchantStatus>, status: MerchantStatus) -> Result<()> {
+        instructions::set_merchant_status::handler(ctx, status)
+    }
+
     /// Initialize a new subscription plan
     pub fn initialize_subscription_plan(
         ctx: Context<InitializeSubscriptionPlan>,
@@ -45,15 +85,92 @@ pub mod loopr_subscription {
     }
 
     /// Process a payment for a subscription
-    pub fn process_payment(ctx: Context<ProcessPayment>, amount: u64) -> Result<()> {
+    pub fn process_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, ProcessPayment<'info>>,
+        amount: u64,
+    ) -> Result<()> {
         instructions::process_payment::handler(ctx, amount)
     }
 
-    /// Cancel a subscription
-    pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+    /// Return all or part of a completed payment to its payer
+    pub fn refund_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, RefundPayment<'info>>,
+        sequence: u64,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::refund_payment::handler(ctx, sequence, amount)
+    }
+
+    /// Hold a plan's token payments in escrow for a dispute window before the merchant is paid
+    pub fn set_dispute_window(ctx: Context<SetDisputeWindow>, dispute_window: i64) -> Result<()> {
+        instructions::set_dispute_window::handler(ctx, dispute_window)
+    }
+
+    /// Dispute an escrowed payment before its dispute window closes
+    pub fn open_dispute(ctx: Context<OpenDispute>, sequence: u64) -> Result<()> {
+        instructions::open_dispute::handler(ctx, sequence)
+    }
+
+    /// Release an undisputed escrowed payment to the merchant once its window has closed
+    pub fn release_escrow<'info>(
+        ctx: Context<'_, '_, '_, 'info, ReleaseEscrow<'info>>,
+        sequence: u64,
+    ) -> Result<()> {
+        instructions::release_escrow::handler(ctx, sequence)
+    }
+
+    /// Settle a dispute, refunding part or all of the escrowed payment and releasing the rest
+    pub fn resolve_dispute<'info>(
+        ctx: Context<'_, '_, '_, 'info, ResolveDispute<'info>>,
+        sequence: u64,
+        refund_amount: u64,
+    ) -> Result<()> {
+        instructions::resolve_dispute::handler(ctx, sequence, refund_amount)
+    }
+
+    /// Fund a subscription's prepaid stream, which the merchant is paid out of by the second
+    pub fn deposit_stream<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositStream<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::deposit_stream::handler(ctx, amount)
+    }
+
+    /// Withdraw what a subscription's stream has accrued to the merchant
+    pub fn withdraw_stream<'info>(ctx: Context<'_, '_, '_, 'info, WithdrawStream<'info>>) -> Result<()> {
+        instructions::withdraw_stream::handler(ctx)
+    }
+
+    /// Cancel a subscription, refunding whatever its stream has not yet accrued
+    pub fn cancel_subscription<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelSubscription<'info>>,
+    ) -> Result<()> {
         instructions::cancel_subscription::handler(ctx)
     }
 
+    /// Pause billing on a subscription
+    pub fn pause_subscription(ctx: Context<PauseSubscription>) -> Result<()> {
+        instructions::pause_subscription::handler(ctx)
+    }
+
+    /// Resume a paused subscription
+    pub fn resume_subscription(ctx: Context<ResumeSubscription>) -> Result<()> {
+        instructions::resume_subscription::handler(ctx)
+    }
+
+    /// Move a subscription to another plan, prorating the current period
+    pub fn change_plan<'info>(ctx: Context<'_, '_, '_, 'info, ChangePlan<'info>>) -> Result<()> {
+        instructions::change_plan::handler(ctx)
+    }
+
+    /// Change a subscription's seat count, prorating the current period
+    pub fn change_quantity<'info>(
+        ctx: Context<'_, '_, '_, 'info, ChangeQuantity<'info>>,
+        quantity: u32,
+    ) -> Result<()> {
+        instructions::change_quantity::handler(ctx, quantity)
+    }
+
     /// Update subscription plan details
     pub fn update_subscription_plan(
         ctx: Context<UpdateSubscriptionPlan>,
@@ -61,6 +178,11 @@ pub mod loopr_subscription {
         description: Option<String>,
         price_per_period: Option<u64>,
         period_duration: Option<i64>,
+        trial_duration: Option<i64>,
+        grace_period: Option<i64>,
+        max_retry_attempts: Option<u8>,
+        max_pause_duration: Option<i64>,
+        max_pauses_per_year: Option<u8>,
         max_subscribers: Option<u32>,
         is_active: Option<bool>,
     ) -> Result<()> {
@@ -70,22 +192,93 @@ pub mod loopr_subscription {
             description,
             price_per_period,
             period_duration,
+            trial_duration,
+            grace_period,
+            max_retry_attempts,
+            max_pause_duration,
+            max_pauses_per_year,
             max_subscribers,
             is_active,
         )
     }
 
-    /// Automated payment processing (called by Clockwork thread)
-    pub fn automated_payment(ctx: Context<AutomatedPayment>) -> Result<()> {
+    /// Set how much notice subscribers get before a price increase reaches
+    /// them, and the increase above which they must accept it
+    pub fn set_price_change_policy(
+        ctx: Context<SetPriceChangePolicy>,
+        price_notice_period: Option<i64>,
+        price_consent_threshold_bps: u16,
+    ) -> Result<()> {
+        instructions::set_price_change_policy::handler(ctx, price_notice_period, price_consent_threshold_bps)
+    }
+
+    /// Move a subscription onto its plan's current price version
+    pub fn accept_price_change(ctx: Context<AcceptPriceChange>, price_version: u32) -> Result<()> {
+        instructions::accept_price_change::handler(ctx, price_version)
+    }
+
+    /// Share a plan's revenue with up to five partners by basis points
+    pub fn set_revenue_split(ctx: Context<SetRevenueSplit>, recipients: Vec<SplitRecipient>) -> Result<()> {
+        instructions::set_revenue_split::handler(ctx, recipients)
+    }
+
+    /// Choose how a plan prices seats: flat, per unit, graduated or volume tiers
+    pub fn set_pricing_model(
+        ctx: Context<SetPricingModel>,
+        pricing_model: PricingModel,
+        price_tiers: Vec<PriceTier>,
+    ) -> Result<()> {
+        instructions::set_pricing_model::handler(ctx, pricing_model, price_tiers)
+    }
+
+    /// Bill a plan per unit of reported usage, or pass `None` to stop metering it
+    pub fn set_metering(ctx: Context<SetMetering>, metering: Option<Metering>) -> Result<()> {
+        instructions::set_metering::handler(ctx, metering)
+    }
+
+    /// Price a plan in USD, converted to its mint through a price feed at
+    /// charge time, or pass `None` to price it in the mint again
+    pub fn set_usd_pricing(ctx: Context<SetUsdPricing>, usd_pricing: Option<UsdPricing>) -> Result<()> {
+        instructions::set_usd_pricing::handler(ctx, usd_pricing)
+    }
+
+    /// Cap how far below its moving average a USD plan's rate may be when this subscription is charged
+    pub fn set_max_slippage(ctx: Context<SetMaxSlippage>, max_slippage_bps: u16) -> Result<()> {
+        instructions::set_max_slippage::handler(ctx, max_slippage_bps)
+    }
+
+    /// Report metered usage against a subscription, billed with its next payment
+    pub fn report_usage(ctx: Context<ReportUsage>, report_id: u64, units: u64) -> Result<()> {
+        instructions::report_usage::handler(ctx, report_id, units)
+    }
+
+    /// Automated payment processing, pulled through the autopay delegate
+    pub fn automated_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, AutomatedPayment<'info>>,
+    ) -> Result<()> {
         instructions::automated_payment::handler(ctx)
     }
 
-    /// Initialize payment thread for autopay
-    pub fn initialize_payment_thread(
-        ctx: Context<InitializePaymentThread>,
-        thread_id: String,
+    /// Permissionless keeper crank that collects a due payment for a bounty
+    pub fn collect_due_payment<'info>(
+        ctx: Context<'_, '_, '_, 'info, CollectDuePayment<'info>>,
     ) -> Result<()> {
-        instructions::initialize_payment_thread::handler(ctx, thread_id)
+        instructions::collect_due_payment::handler(ctx)
+    }
+
+    /// Permissionless crank applying time-based dunning and expiry transitions
+    pub fn refresh_subscription_status(ctx: Context<RefreshSubscriptionStatus>) -> Result<()> {
+        instructions::refresh_subscription_status::handler(ctx)
+    }
+
+    /// Approve the autopay delegate for a bounded amount
+    pub fn enable_autopay(ctx: Context<EnableAutopay>, allowance: u64) -> Result<()> {
+        instructions::enable_autopay::handler(ctx, allowance)
+    }
+
+    /// Revoke this subscription's autopay mandate
+    pub fn disable_autopay(ctx: Context<DisableAutopay>) -> Result<()> {
+        instructions::disable_autopay::handler(ctx)
     }
 
     /// Initialize global state
@@ -93,6 +286,39 @@ pub mod loopr_subscription {
         instructions::initialize_global_state::handler(ctx)
     }
 
+    /// Pause or unpause every user-facing instruction
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        instructions::set_paused::handler(ctx, paused)
+    }
+
+    /// Propose a new protocol authority, which takes over once it accepts
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+        instructions::propose_authority::handler(ctx, new_authority)
+    }
+
+    /// Take over as protocol authority after being proposed
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        instructions::accept_authority::handler(ctx)
+    }
+
+    /// Update the keeper bounty, protocol fee, treasury, default dispute arbiter
+    /// and the program USD price feeds must belong to
+    pub fn update_global_config(
+        ctx: Context<UpdateGlobalConfig>,
+        keeper_bounty_bps: Option<u16>,
+        fee_bps: Option<u16>,
+        treasury: Option<Pubkey>,
+        arbiter: Option<Pubkey>,
+        price_oracle: Option<Pubkey>,
+    ) -> Result<()> {
+        instructions::update_global_config::handler(ctx, keeper_bounty_bps, fee_bps, treasury, arbiter, price_oracle)
+    }
+
+    /// Appoint a plan's own dispute arbiter, or pass `None` to use the default
+    pub fn set_plan_arbiter(ctx: Context<SetPlanArbiter>, arbiter: Option<Pubkey>) -> Result<()> {
+        instructions::set_plan_arbiter::handler(ctx, arbiter)
+    }
+
     /// Create payment intent for QR code flow
     pub fn create_payment_intent(
         ctx: Context<CreatePaymentIntent>,
@@ -104,16 +330,78 @@ pub mod loopr_subscription {
         instructions::create_payment_intent::handler(ctx, intent_id, plan_id, amount, expires_at)
     }
 
-    /// Subscribe and pay (complete QR flow)
-    pub fn subscribe_and_pay(
-        ctx: Context<SubscribeAndPay>,
+    /// Issue a discount coupon for one of the authority's plans, or all of them in a mint
+    pub fn create_coupon(
+        ctx: Context<CreateCoupon>,
+        code: String,
+        discount_type: DiscountType,
+        discount_value: u64,
+        duration: CouponDuration,
+        max_redemptions: Option<u32>,
+        expires_at: Option<i64>,
+        plan: Option<Pubkey>,
+        mint: Pubkey,
+    ) -> Result<()> {
+        instructions::create_coupon::handler(
+            ctx,
+            code,
+            discount_type,
+            discount_value,
+            duration,
+            max_redemptions,
+            expires_at,
+            plan,
+            mint,
+        )
+    }
+
+    /// Apply a coupon to an existing subscription
+    pub fn apply_coupon(ctx: Context<ApplyCoupon>) -> Result<()> {
+        instructions::apply_coupon::handler(ctx)
+    }
+
+    /// Subscribe and pay (complete QR flow); `max_slippage_bps` is the
+    /// subscriber's cap on a USD price's slippage, kept for later payments
+    pub fn subscribe_and_pay<'info>(
+        ctx: Context<'_, '_, '_, 'info, SubscribeAndPay<'info>>,
         subscription_id: String,
+        max_slippage_bps: u16,
     ) -> Result<()> {
-        instructions::subscribe_and_pay::handler(ctx, subscription_id)
+        instructions::subscribe_and_pay::handler(ctx, subscription_id, max_slippage_bps)
     }
 
     /// Confirm payment and complete subscription setup
     pub fn confirm_payment(ctx: Context<ConfirmPayment>) -> Result<()> {
         instructions::confirm_payment::handler(ctx)
     }
+
+    /// Close a deactivated plan with no subscribers, returning its rent to the authority
+    pub fn close_subscription_plan(ctx: Context<CloseSubscriptionPlan>) -> Result<()> {
+        instructions::close_subscription_plan::handler(ctx)
+    }
+
+    /// Close a settled, cancelled or expired subscription and its payment ledger,
+    /// returning their rent to the subscriber
+    pub fn close_user_subscription(ctx: Context<CloseUserSubscription>) -> Result<()> {
+        instructions::close_user_subscription::handler(ctx)
+    }
+
+    /// Close a paid, cancelled or expired payment intent, returning its rent to its creator
+    pub fn close_payment_intent(ctx: Context<ClosePaymentIntent>) -> Result<()> {
+        instructions::close_payment_intent::handler(ctx)
+    }
+
+    /// Move a v1 subscription created under its subscription id to its
+    /// canonical `[user, plan]` address in the current layout, with a new
+    /// payment ledger
+    pub fn migrate_user_subscription(ctx: Context<MigrateUserSubscription>) -> Result<()> {
+        instructions::migrate_user_subscription::handler(ctx)
+    }
+
+    /// Upgrade an account in the layout the program was first deployed with to
+    /// the current layout, reallocating it in place; the payer covers the
+    /// extra rent
+    pub fn migrate_account(ctx: Context<MigrateAccount>) -> Result<()> {
+        instructions::migrate_account::handler(ctx)
+    }
 }
@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use pyth_sdk_solana::{state::load_price_account, PriceFeed};
+use crate::{errors::*, state::{SubscriptionPlan, UsdPricing}};
+
+/// Pyth's oracle program on mainnet, the default owner of price feeds
+pub mod pyth_oracle {
+    use anchor_lang::prelude::*;
+
+    declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+}
+
+/// Reads `usd_pricing`'s feed out of `price_feed`, which must be that
+/// account, owned by `price_oracle` and published under the pricing's product.
+pub fn load_price_feed(
+    price_feed: &AccountInfo,
+    usd_pricing: &UsdPricing,
+    price_oracle: &Pubkey,
+) -> Result<PriceFeed> {
+    require_keys_eq!(price_feed.key(), usd_pricing.price_feed, LooprError::InvalidPriceFeed);
+    require_keys_eq!(*price_feed.owner, *price_oracle, LooprError::InvalidPriceFeed);
+
+    let data = price_feed.try_borrow_data()?;
+    let price_account = load_price_account(&data).map_err(|_| error!(LooprError::InvalidPriceFeed))?;
+    require_keys_eq!(price_account.prod, usd_pricing.product, LooprError::InvalidPriceFeed);
+
+    Ok(price_account.to_price_feed(price_feed.key))
+}
+
+/// Converts `amount` in a plan's pricing units into its mint: unchanged for
+/// plans priced in the mint, read off `price_feed` for USD-priced plans.
+///
+/// The feed must be fresh and tight enough for the plan's `UsdPricing`, and
+/// its spot price no more than `max_slippage_bps` below its moving average,
+/// so a dip in the mint's price is not passed on to the subscriber beyond
+/// what they accepted. Rounds up, in the merchant's favour.
+pub fn to_settlement_amount(
+    plan: &SubscriptionPlan,
+    price_feed: Option<&AccountInfo>,
+    price_oracle: &Pubkey,
+    max_slippage_bps: u16,
+    amount: u64,
+    now: i64,
+) -> Result<u64> {
+    let Some(usd_pricing) = plan.usd_pricing else {
+        return Ok(amount);
+    };
+    let Some(price_feed) = price_feed else {
+        return err!(LooprError::MissingPriceFeed);
+    };
+    let feed = load_price_feed(price_feed, &usd_pricing, price_oracle)?;
+    let price = feed
+        .get_price_no_older_than(now, usd_pricing.max_price_age)
+        .ok_or_else(|| error!(LooprError::StalePrice))?;
+    let ema_price = feed.get_ema_price_unchecked();
+    require!(price.price > 0 && ema_price.price > 0, LooprError::InvalidPriceFeed);
+
+    let spot = price.price as u128;
+    require!(
+        price.conf as u128 * 10_000 <= spot * usd_pricing.max_confidence_bps as u128,
+        LooprError::PriceTooUncertain
+    );
+    require!(
+        spot * 10_000 >= ema_price.price as u128 * (10_000 - max_slippage_bps.min(10_000)) as u128,
+        LooprError::SlippageExceeded
+    );
+
+    // amount / 10^USD_DECIMALS dollars at spot * 10^expo dollars per token
+    let mut numerator = (amount as u128).checked_mul(10u128.pow(plan.mint_decimals as u32));
+    let mut denominator = spot.checked_mul(10u128.pow(UsdPricing::USD_DECIMALS));
+    if price.expo < 0 {
+        numerator = numerator.and_then(|n| n.checked_mul(10u128.checked_pow(price.expo.unsigned_abs())?));
+    } else {
+        denominator = denominator.and_then(|d| d.checked_mul(10u128.checked_pow(price.expo as u32)?));
+    }
+    let (Some(numerator), Some(denominator)) = (numerator, denominator) else {
+        return err!(LooprError::InvalidPaymentAmount);
+    };
+
+    let amount = numerator / denominator + u128::from(numerator % denominator != 0);
+    u64::try_from(amount).map_err(|_| error!(LooprError::InvalidPaymentAmount))
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+/// Prefix of a merchant profile's address, followed by its authority
+pub const MERCHANT_SEED: &[u8] = b"merchant";
+
+/// Prefix of a subscription's address, followed by the subscriber and the plan
+pub const USER_SUBSCRIPTION_SEED: &[u8] = b"user_subscription";
+
+/// Prefix of a payment ledger's address, followed by its subscription
+pub const PAYMENT_LEDGER_SEED: &[u8] = b"payment_ledger";
+
+/// Address of the merchant profile registered by `authority`
+pub fn merchant_address(authority: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MERCHANT_SEED, authority.as_ref()], &crate::ID)
+}
+
+/// Address of `user`'s subscription to `subscription_plan`; a wallet holds at
+/// most one subscription per plan
+pub fn user_subscription_address(user: &Pubkey, subscription_plan: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USER_SUBSCRIPTION_SEED, user.as_ref(), subscription_plan.as_ref()],
+        &crate::ID,
+    )
+}
+
+/// Address `subscribe_and_pay` used to key subscriptions by their id; only
+/// `migrate_user_subscription` still accepts it
+pub fn legacy_user_subscription_address(user: &Pubkey, subscription_id: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[USER_SUBSCRIPTION_SEED, user.as_ref(), subscription_id.as_bytes()],
+        &crate::ID,
+    )
+}
+
+/// Address of the payment ledger belonging to `user_subscription`
+pub fn payment_ledger_address(user_subscription: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PAYMENT_LEDGER_SEED, user_subscription.as_ref()], &crate::ID)
+}
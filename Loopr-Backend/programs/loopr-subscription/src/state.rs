@@ -1,24 +1,346 @@
 use anchor_lang::prelude::*;
 
+/// Sentinel `accepted_mint` for plans billed in native SOL lamports
+pub const NATIVE_SOL_MINT: Pubkey = Pubkey::new_from_array([0u8; 32]);
+pub const NATIVE_SOL_DECIMALS: u8 = 9;
+
+/// Layout version of every account the program writes. Version 1 is the
+/// layout the program was first deployed with, before accounts carried a
+/// version; `migrate_account` and `migrate_user_subscription` convert those
+pub const ACCOUNT_VERSION: u8 = 2;
+
+/// Bytes every account keeps free after its version, so later fields can be
+/// carved out of `reserved` without reallocating existing accounts
+pub const RESERVED_SPACE: usize = 64;
+
+/// Space the version byte and reserved bytes add to every layout
+pub const VERSIONED_SPACE: usize = 1 + RESERVED_SPACE;
+
+/// Merchant profile and payout settings shared by all of a wallet's plans
+#[account]
+pub struct Merchant {
+    pub authority: Pubkey,
+    pub name: [u8; 64],
+    pub logo_uri: [u8; 128],
+    pub support_contact: [u8; 64],
+    /// Where each mint's payments are sent; mints without an entry pay the authority
+    pub payout_wallets: Vec<PayoutWallet>,
+    pub status: MerchantStatus,
+    pub total_plans: u64,
+    /// Open subscriptions across all of the merchant's plans
+    pub current_subscribers: u64,
+    /// Collected across all plans and mints, kept net of refunds
+    pub total_revenue: u64,
+    pub created_at: i64,
+    pub updated_at: i64,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl Merchant {
+    pub const LEN: usize = 8 + 32 + 64 + 128 + 64
+        + (4 + Self::MAX_PAYOUT_WALLETS * PayoutWallet::LEN)
+        + 1 + 8 + 8 + 8 + 8 + 8 + 1
+        + VERSIONED_SPACE;
+
+    pub const MAX_PAYOUT_WALLETS: usize = 8;
+
+    pub fn is_active(&self) -> bool {
+        self.status == MerchantStatus::Active
+    }
+
+    /// Wallet that receives the merchant's share of payments in `mint`
+    pub fn payout_wallet(&self, mint: &Pubkey) -> Pubkey {
+        self.payout_wallets
+            .iter()
+            .find(|payout| payout.mint == *mint)
+            .map_or(self.authority, |payout| payout.wallet)
+    }
+
+    pub fn record_revenue(&mut self, amount: u64) {
+        self.total_revenue = self.total_revenue.checked_add(amount).unwrap();
+    }
+
+    pub fn record_refund(&mut self, amount: u64) {
+        self.total_revenue = self.total_revenue.saturating_sub(amount);
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = string_to_fixed_bytes::<64>(name);
+    }
+
+    pub fn get_name(&self) -> String {
+        bytes_to_string(&self.name)
+    }
+
+    pub fn set_logo_uri(&mut self, logo_uri: &str) {
+        self.logo_uri = string_to_fixed_bytes::<128>(logo_uri);
+    }
+
+    pub fn get_logo_uri(&self) -> String {
+        bytes_to_string(&self.logo_uri)
+    }
+
+    pub fn set_support_contact(&mut self, support_contact: &str) {
+        self.support_contact = string_to_fixed_bytes::<64>(support_contact);
+    }
+
+    pub fn get_support_contact(&self) -> String {
+        bytes_to_string(&self.support_contact)
+    }
+}
+
+/// Wallet a merchant is paid at in one mint
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PayoutWallet {
+    /// Plans' `accepted_mint`, so `NATIVE_SOL_MINT` covers both lamports and wrapped SOL
+    pub mint: Pubkey,
+    pub wallet: Pubkey,
+}
+
+impl PayoutWallet {
+    pub const LEN: usize = 32 + 32;
+}
+
 /// Subscription plan state
 #[account]
 pub struct SubscriptionPlan {
     pub authority: Pubkey,
+    /// Merchant the plan is sold under, whose payout wallets it pays
+    pub merchant: Pubkey,
     pub plan_id: [u8; 64],
     pub name: [u8; 128],
     pub description: [u8; 256],
     pub price_per_period: u64,
     pub period_duration: i64,
+    /// Mint the plan is priced in, or `NATIVE_SOL_MINT` for lamports
+    pub accepted_mint: Pubkey,
+    pub mint_decimals: u8,
+    /// Free trial granted to a wallet's first subscription; 0 disables trials
+    pub trial_duration: i64,
+    /// How long a missed payment may stay outstanding before suspension
+    pub grace_period: i64,
+    /// Failed collections allowed before the subscription is suspended
+    pub max_retry_attempts: u8,
+    /// Longest a single pause may last before billing resumes on its own
+    pub max_pause_duration: i64,
+    /// Pauses a subscriber may take in any rolling year; 0 disables pausing
+    pub max_pauses_per_year: u8,
+    /// Partners paid a share of each payment; the authority keeps the rest
+    pub revenue_split: Vec<SplitRecipient>,
+    /// Usage pricing billed on top of `price_per_period`, if the plan is metered
+    pub metering: Option<Metering>,
+    /// How `price_per_period` and `price_tiers` turn a seat quantity into a price
+    pub pricing_model: PricingModel,
+    /// Ascending tiers for graduated and volume pricing
+    pub price_tiers: Vec<PriceTier>,
+    /// Bumped whenever `price_per_period` or `period_duration` changes
+    pub price_version: u32,
+    /// When the current price version was published
+    pub price_changed_at: i64,
+    /// Notice subscribers get before a price increase reaches them; `None`
+    /// keeps them on their current version until they accept a new one
+    pub price_notice_period: Option<i64>,
+    /// Rate increase, in basis points, above which subscribers must consent
+    pub price_consent_threshold_bps: u16,
+    /// How long token payments are held in escrow, open to dispute, before
+    /// the merchant is paid; 0 pays the merchant straight away
+    pub dispute_window: i64,
+    /// Resolves this plan's disputes in place of `GlobalState::arbiter`
+    pub arbiter: Option<Pubkey>,
+    /// Oracle terms for plans priced in USD; when set, every price on the
+    /// plan is in micro-USD and converted to the mint when charged
+    pub usd_pricing: Option<UsdPricing>,
     pub max_subscribers: Option<u32>,
     pub current_subscribers: u32,
     pub is_active: bool,
     pub created_at: i64,
     pub updated_at: i64,
     pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
 }
 
 impl SubscriptionPlan {
-    pub const LEN: usize = 8 + 32 + 64 + 128 + 256 + 8 + 8 + (1 + 4) + 4 + 1 + 8 + 8 + 1 + 32;
+    pub const LEN: usize = 8 + 32 + 32 + 64 + 128 + 256 + 8 + 8 + 32 + 1 + 8 + 8 + 1 + 8 + 1
+        + (4 + Self::MAX_SPLIT_RECIPIENTS * SplitRecipient::LEN) + (1 + Metering::LEN)
+        + 1 + (4 + Self::MAX_PRICE_TIERS * PriceTier::LEN) + 4 + 8 + (1 + 8) + 2 + 8 + (1 + 32)
+        + (1 + UsdPricing::LEN)
+        + (1 + 4) + 4 + 1 + 8 + 8 + 1
+        + VERSIONED_SPACE;
+
+    pub const MAX_SPLIT_RECIPIENTS: usize = 5;
+    pub const MAX_PRICE_TIERS: usize = 5;
+    pub const MAX_QUANTITY: u32 = 1_000_000;
+
+    pub const DEFAULT_GRACE_PERIOD: i64 = 3 * 24 * 60 * 60;
+    pub const DEFAULT_MAX_RETRY_ATTEMPTS: u8 = 3;
+    pub const DEFAULT_MAX_PAUSE_DURATION: i64 = 30 * 24 * 60 * 60;
+    pub const DEFAULT_MAX_PAUSES_PER_YEAR: u8 = 2;
+    pub const DEFAULT_PRICE_NOTICE_PERIOD: i64 = 30 * 24 * 60 * 60;
+    pub const DEFAULT_PRICE_CONSENT_THRESHOLD_BPS: u16 = 1_000;
+    pub const MAX_DISPUTE_WINDOW: i64 = 90 * 24 * 60 * 60;
+
+    pub fn is_usd_priced(&self) -> bool {
+        self.usd_pricing.is_some()
+    }
+
+    /// Count a new paying or trialing subscriber, up to `max_subscribers`
+    pub fn add_subscriber(&mut self) -> Result<()> {
+        if let Some(max_subscribers) = self.max_subscribers {
+            require!(
+                self.current_subscribers < max_subscribers,
+                crate::errors::LooprError::MaxSubscribersReached
+            );
+        }
+        self.current_subscribers = self.current_subscribers.checked_add(1).unwrap();
+        Ok(())
+    }
+
+    /// Whether the plan is retired with no subscribers left, so its account may be closed
+    pub fn is_closable(&self) -> bool {
+        !self.is_active && self.current_subscribers == 0
+    }
+
+    pub fn is_native_sol(&self) -> bool {
+        self.accepted_mint == NATIVE_SOL_MINT
+    }
+
+    /// Mint token transfers settle in; native SOL plans settle in wrapped SOL
+    pub fn settlement_mint(&self) -> Pubkey {
+        if self.is_native_sol() {
+            anchor_spl::token::spl_token::native_mint::ID
+        } else {
+            self.accepted_mint
+        }
+    }
+
+    pub fn format_amount(&self, amount: u64) -> String {
+        format_token_amount(amount, self.mint_decimals)
+    }
+
+    /// Share of a per-period `price` covering `remaining` seconds, rounded down
+    pub fn prorate(&self, price: u64, remaining: i64) -> u64 {
+        let remaining = remaining.clamp(0, self.period_duration) as u128;
+        (price as u128 * remaining / self.period_duration as u128) as u64
+    }
+
+    /// Price of `quantity` seats for one period at the current price version
+    pub fn seat_price(&self, quantity: u32) -> Result<u64> {
+        self.seat_price_at(self.price_per_period, quantity)
+    }
+
+    /// Price of `quantity` seats for one period under the plan's pricing model,
+    /// with flat and per-unit seats charged at `price_per_period`
+    pub fn seat_price_at(&self, price_per_period: u64, quantity: u32) -> Result<u64> {
+        use crate::errors::LooprError;
+
+        let quantity = u64::from(quantity);
+        let price = match self.pricing_model {
+            PricingModel::Flat => Some(price_per_period),
+            PricingModel::PerUnit => price_per_period.checked_mul(quantity),
+            PricingModel::Graduated => {
+                // Each seat is priced by the tier it falls in
+                let mut price = Some(0u64);
+                let mut floor = 0u64;
+                for tier in &self.price_tiers {
+                    let ceiling = tier.up_to.map_or(quantity, |up_to| up_to.min(quantity));
+                    if ceiling > floor {
+                        price = (ceiling - floor)
+                            .checked_mul(tier.unit_price)
+                            .and_then(|tier_price| price?.checked_add(tier_price));
+                        floor = ceiling;
+                    }
+                }
+                price
+            }
+            PricingModel::Volume => {
+                // Every seat is priced by the tier the total quantity falls in
+                let tier = self
+                    .price_tiers
+                    .iter()
+                    .find(|tier| tier.up_to.map_or(true, |up_to| quantity <= up_to));
+                tier.map_or(Some(0), |tier| quantity.checked_mul(tier.unit_price))
+            }
+        };
+        price.ok_or_else(|| error!(LooprError::PriceOverflow))
+    }
+
+    /// Charge for the subscription's current period: its seats at its pinned
+    /// price version plus every metered unit beyond the included allowance
+    pub fn period_price(&self, subscription: &UserSubscription) -> Result<u64> {
+        use crate::errors::LooprError;
+
+        let seat_price = self.seat_price_at(subscription.price_per_period, subscription.quantity)?;
+        match &self.metering {
+            Some(metering) => {
+                let billable_units = subscription.period_usage.saturating_sub(metering.included_units);
+                billable_units
+                    .checked_mul(metering.unit_price)
+                    .and_then(|usage_price| seat_price.checked_add(usage_price))
+                    .ok_or_else(|| error!(LooprError::PriceOverflow))
+            }
+            None => Ok(seat_price),
+        }
+    }
+
+    /// Publish a new price version; subscribers stay on the one they are
+    /// pinned to until `UserSubscription::sync_price` moves them across
+    pub fn set_price(&mut self, price_per_period: u64, period_duration: i64, now: i64) {
+        if price_per_period == self.price_per_period && period_duration == self.period_duration {
+            return;
+        }
+        self.price_per_period = price_per_period;
+        self.period_duration = period_duration;
+        self.price_version = self.price_version.checked_add(1).unwrap();
+        self.price_changed_at = now;
+    }
+
+    /// Whether the current price version charges more per second than
+    /// `price_per_period` every `period_duration`, by over `tolerance_bps`
+    pub fn raises_rate_from(&self, price_per_period: u64, period_duration: i64, tolerance_bps: u16) -> bool {
+        // new_price / new_duration > old_price / old_duration * (1 + tolerance)
+        let new_rate = (self.price_per_period as u128)
+            .saturating_mul(period_duration as u128)
+            .saturating_mul(10_000);
+        let old_rate = (price_per_period as u128)
+            .saturating_mul(self.period_duration as u128)
+            .saturating_mul(10_000 + tolerance_bps as u128);
+        new_rate > old_rate
+    }
+
+    /// Whether payments are held in escrow for a dispute window
+    pub fn holds_in_escrow(&self) -> bool {
+        self.dispute_window > 0
+    }
+
+    /// Key that resolves disputes on this plan's payments
+    pub fn arbiter(&self, global_state: &GlobalState) -> Pubkey {
+        self.arbiter.unwrap_or(global_state.arbiter)
+    }
+
+    /// Whether `key` may report usage: the plan authority or its delegated reporter
+    pub fn is_usage_reporter(&self, key: &Pubkey) -> bool {
+        *key == self.authority
+            || self.metering.map_or(false, |metering| metering.reporter == Some(*key))
+    }
+
+    /// Each split recipient's cut of `amount`, rounded down.
+    ///
+    /// The authority is paid whatever is left, so rounding dust always stays
+    /// with the merchant and the payouts add up to exactly `amount`.
+    pub fn split_shares(&self, amount: u64) -> Vec<u64> {
+        self.revenue_split
+            .iter()
+            .map(|recipient| (amount as u128 * recipient.share_bps as u128 / 10_000) as u64)
+            .collect()
+    }
+
+    /// Minimum spacing between collection retries, spread across the grace period
+    pub fn retry_interval(&self) -> i64 {
+        self.grace_period / i64::from(self.max_retry_attempts.max(1))
+    }
 
     pub fn set_plan_id(&mut self, plan_id: &str) {
         self.plan_id = string_to_fixed_bytes::<64>(plan_id);
@@ -46,28 +368,52 @@ impl SubscriptionPlan {
 
     pub fn from_fields(
         authority: Pubkey,
+        merchant: Pubkey,
         plan_id: &str,
         name: &str,
         description: &str,
         price_per_period: u64,
         period_duration: i64,
+        accepted_mint: Pubkey,
+        mint_decimals: u8,
         max_subscribers: Option<u32>,
         is_active: bool,
         bump: u8,
     ) -> Self {
         Self {
             authority,
+            merchant,
             plan_id: string_to_fixed_bytes::<64>(plan_id),
             name: string_to_fixed_bytes::<128>(name),
             description: string_to_fixed_bytes::<256>(description),
             price_per_period,
             period_duration,
+            accepted_mint,
+            mint_decimals,
+            trial_duration: 0,
+            grace_period: Self::DEFAULT_GRACE_PERIOD,
+            max_retry_attempts: Self::DEFAULT_MAX_RETRY_ATTEMPTS,
+            max_pause_duration: Self::DEFAULT_MAX_PAUSE_DURATION,
+            max_pauses_per_year: Self::DEFAULT_MAX_PAUSES_PER_YEAR,
+            revenue_split: Vec::new(),
+            metering: None,
+            pricing_model: PricingModel::Flat,
+            price_tiers: Vec::new(),
+            price_version: 1,
+            price_changed_at: Clock::get().unwrap().unix_timestamp,
+            price_notice_period: Some(Self::DEFAULT_PRICE_NOTICE_PERIOD),
+            price_consent_threshold_bps: Self::DEFAULT_PRICE_CONSENT_THRESHOLD_BPS,
+            dispute_window: 0,
+            arbiter: None,
+            usd_pricing: None,
             max_subscribers,
             current_subscribers: 0,
             is_active,
             created_at: Clock::get().unwrap().unix_timestamp,
             updated_at: Clock::get().unwrap().unix_timestamp,
             bump,
+            version: ACCOUNT_VERSION,
+            reserved: [0; RESERVED_SPACE],
         }
     }
 
@@ -77,6 +423,11 @@ impl SubscriptionPlan {
         description: Option<&str>,
         price_per_period: Option<u64>,
         period_duration: Option<i64>,
+        trial_duration: Option<i64>,
+        grace_period: Option<i64>,
+        max_retry_attempts: Option<u8>,
+        max_pause_duration: Option<i64>,
+        max_pauses_per_year: Option<u8>,
         max_subscribers: Option<u32>,
         is_active: Option<bool>,
     ) {
@@ -86,11 +437,25 @@ impl SubscriptionPlan {
         if let Some(description) = description {
             self.description = string_to_fixed_bytes::<256>(description);
         }
-        if let Some(price) = price_per_period {
-            self.price_per_period = price;
+        self.set_price(
+            price_per_period.unwrap_or(self.price_per_period),
+            period_duration.unwrap_or(self.period_duration),
+            Clock::get().unwrap().unix_timestamp,
+        );
+        if let Some(trial) = trial_duration {
+            self.trial_duration = trial;
         }
-        if let Some(duration) = period_duration {
-            self.period_duration = duration;
+        if let Some(grace) = grace_period {
+            self.grace_period = grace;
+        }
+        if let Some(retries) = max_retry_attempts {
+            self.max_retry_attempts = retries;
+        }
+        if let Some(pause) = max_pause_duration {
+            self.max_pause_duration = pause;
+        }
+        if let Some(pauses) = max_pauses_per_year {
+            self.max_pauses_per_year = pauses;
         }
         if let Some(max) = max_subscribers {
             self.max_subscribers = Some(max);
@@ -112,25 +477,173 @@ impl SubscriptionPlan {
     }
 }
 
+/// A partner's share of a plan's revenue
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SplitRecipient {
+    pub wallet: Pubkey,
+    pub share_bps: u16,
+}
+
+impl SplitRecipient {
+    pub const LEN: usize = 32 + 2;
+}
+
+/// Per-unit usage pricing for metered plans
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Metering {
+    /// Price of each billable unit, in the plan's mint
+    pub unit_price: u64,
+    /// Units each period covers before usage is billed
+    pub included_units: u64,
+    /// Key besides the plan authority allowed to report usage
+    pub reporter: Option<Pubkey>,
+}
+
+impl Metering {
+    pub const LEN: usize = 8 + 8 + (1 + 32);
+}
+
+/// Oracle a USD-priced plan converts its prices to the mint with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UsdPricing {
+    /// Pyth-format price account quoting the plan's mint in USD
+    pub price_feed: Pubkey,
+    /// Product account `price_feed` must be published under, tying it to the mint
+    pub product: Pubkey,
+    /// Oldest a published price may be, in seconds, before it is refused
+    pub max_price_age: u64,
+    /// Widest confidence interval accepted, in basis points of the price
+    pub max_confidence_bps: u16,
+}
+
+impl UsdPricing {
+    pub const LEN: usize = 32 + 32 + 8 + 2;
+
+    /// Plan prices are quoted in millionths of a dollar
+    pub const USD_DECIMALS: u32 = 6;
+}
+
+/// One band of graduated or volume pricing
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PriceTier {
+    /// Last seat covered by this tier; `None` for the open-ended top tier
+    pub up_to: Option<u64>,
+    pub unit_price: u64,
+}
+
+impl PriceTier {
+    pub const LEN: usize = (1 + 8) + 8;
+}
+
+/// Prepaid stream accruing to the merchant out of a subscription's vault
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Stream {
+    /// Time accrual has been settled up to
+    pub accrued_at: i64,
+    /// Earned by the merchant but not yet withdrawn
+    pub accrued: u64,
+}
+
+impl Stream {
+    pub const LEN: usize = 8 + 8;
+
+    /// Longest a single deposit is counted as paying for, so `paid_through` stays in range
+    pub const MAX_PREPAID: i64 = 100 * 365 * 24 * 60 * 60;
+
+    /// Accrue `price` per `period_duration` up to `now`, never beyond the
+    /// vault's `balance`
+    pub fn accrue(&mut self, now: i64, price: u64, period_duration: i64, balance: u64) {
+        if now <= self.accrued_at {
+            return;
+        }
+        if price == 0 {
+            // A fully discounted stream owes nothing for the time
+            self.accrued_at = now;
+            return;
+        }
+        let elapsed = (now - self.accrued_at) as u128;
+        let earned = price as u128 * elapsed / period_duration as u128;
+        let available = balance.saturating_sub(self.accrued) as u128;
+        if earned >= available {
+            // The vault ran dry; time after that is not owed
+            self.accrued = balance;
+            self.accrued_at = now;
+        } else {
+            self.accrued = self.accrued.checked_add(earned as u64).unwrap();
+            // Only advance by the time actually paid for, so fractions of a
+            // token carry over to the next accrual instead of being lost
+            self.accrued_at += (earned * period_duration as u128 / price as u128) as i64;
+        }
+    }
+
+    /// When the unaccrued part of the vault's `balance` runs out
+    pub fn paid_through(&self, price: u64, period_duration: i64, balance: u64) -> i64 {
+        if price == 0 {
+            return self.accrued_at + Self::MAX_PREPAID;
+        }
+        let unaccrued = balance.saturating_sub(self.accrued) as u128;
+        let covered = unaccrued * period_duration as u128 / price as u128;
+        self.accrued_at + covered.min(Self::MAX_PREPAID as u128) as i64
+    }
+}
+
 /// User subscription state
 #[account]
 pub struct UserSubscription {
     pub user: Pubkey,
     pub subscription_plan: Pubkey,
     pub subscription_id: [u8; 64],
-    pub is_active: bool,
+    pub status: SubscriptionStatus,
     pub next_payment_due: i64,
     pub last_payment_date: Option<i64>,
+    /// Failed collections since the last successful payment
+    pub failed_payment_attempts: u8,
+    pub last_failed_attempt: Option<i64>,
+    /// When the current pause started, if the subscription is paused
+    pub paused_at: Option<i64>,
+    /// Start of the rolling year `pauses_in_window` counts against
+    pub pause_window_start: i64,
+    pub pauses_in_window: u8,
     pub auto_pay_enabled: bool,
-    pub payment_thread: Option<Pubkey>,
+    /// Token account the autopay delegate is approved on
+    pub autopay_token_account: Option<Pubkey>,
+    /// Remaining amount the autopay delegate may still pull for this subscription
+    pub autopay_allowance: u64,
+    /// Prorated credit from plan changes, applied to upcoming payments
+    pub credit_balance: u64,
+    /// Coupon discount applied to upcoming payments
+    pub discount: Option<AppliedDiscount>,
+    /// Metered units reported since the last payment, billed with the next one
+    pub period_usage: u64,
+    /// Seats billed under the plan's pricing model
+    pub quantity: u32,
+    /// Plan price version the subscription is billed at, and its terms
+    pub price_version: u32,
+    pub price_per_period: u64,
+    pub period_duration: i64,
+    /// Prepaid stream paying for the subscription from its vault, if any
+    pub stream: Option<Stream>,
+    /// Furthest, in basis points, a USD plan's spot rate may sit below the
+    /// oracle's moving average when this subscription is charged
+    pub max_slippage_bps: u16,
+    /// Payments whose merchant share is still held in escrow; the
+    /// subscription can't be closed or re-keyed until they settle
+    pub escrowed_payments: u32,
     pub total_payments_made: u64,
     pub created_at: i64,
     pub updated_at: i64,
     pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
 }
 
 impl UserSubscription {
-    pub const LEN: usize = 8 + 32 + 32 + 64 + 1 + 8 + (1 + 8) + 1 + (1 + 32) + 8 + 8 + 8 + 1 + 16;
+    /// Rolling window `SubscriptionPlan::max_pauses_per_year` is counted over
+    pub const PAUSE_WINDOW: i64 = 365 * 24 * 60 * 60;
+
+    pub const DEFAULT_MAX_SLIPPAGE_BPS: u16 = 100;
+
+    pub const LEN: usize = 8 + 32 + 32 + 64 + 1 + 8 + (1 + 8) + 1 + (1 + 8) + (1 + 8) + 8 + 1 + 1 + (1 + 32) + 8 + 8 + (1 + AppliedDiscount::LEN) + 8 + 4 + 4 + 8 + 8 + (1 + Stream::LEN) + 2 + 4 + 8 + 8 + 8 + 1 + VERSIONED_SPACE;
 
     pub fn set_subscription_id(&mut self, id: &str) {
         self.subscription_id = string_to_fixed_bytes::<64>(id);
@@ -143,8 +656,9 @@ impl UserSubscription {
     pub fn from_fields(
         user: Pubkey,
         subscription_plan: Pubkey,
+        plan: &SubscriptionPlan,
         subscription_id: &str,
-        is_active: bool,
+        status: SubscriptionStatus,
         next_payment_due: i64,
         auto_pay_enabled: bool,
         bump: u8,
@@ -153,27 +667,45 @@ impl UserSubscription {
             user,
             subscription_plan,
             subscription_id: string_to_fixed_bytes::<64>(subscription_id),
-            is_active,
+            status,
             next_payment_due,
             last_payment_date: None,
+            failed_payment_attempts: 0,
+            last_failed_attempt: None,
+            paused_at: None,
+            pause_window_start: 0,
+            pauses_in_window: 0,
             auto_pay_enabled,
-            payment_thread: None,
+            autopay_token_account: None,
+            autopay_allowance: 0,
+            credit_balance: 0,
+            discount: None,
+            period_usage: 0,
+            quantity: 1,
+            price_version: plan.price_version,
+            price_per_period: plan.price_per_period,
+            period_duration: plan.period_duration,
+            stream: None,
+            max_slippage_bps: Self::DEFAULT_MAX_SLIPPAGE_BPS,
+            escrowed_payments: 0,
             total_payments_made: 0,
             created_at: Clock::get().unwrap().unix_timestamp,
             updated_at: Clock::get().unwrap().unix_timestamp,
             bump,
+            version: ACCOUNT_VERSION,
+            reserved: [0; RESERVED_SPACE],
         }
     }
 
     pub fn update(
         &mut self,
-        is_active: Option<bool>,
+        status: Option<SubscriptionStatus>,
         next_payment_due: Option<i64>,
         last_payment_date: Option<i64>,
         auto_pay_enabled: Option<bool>,
     ) {
-        if let Some(active) = is_active {
-            self.is_active = active;
+        if let Some(status) = status {
+            self.status = status;
         }
         if let Some(next) = next_payment_due {
             self.next_payment_due = next;
@@ -188,59 +720,370 @@ impl UserSubscription {
     }
 
     pub fn activate(&mut self) {
-        self.is_active = true;
+        self.status = SubscriptionStatus::Active;
         self.updated_at = Clock::get().unwrap().unix_timestamp;
     }
 
     pub fn deactivate(&mut self) {
-        self.is_active = false;
+        self.status = SubscriptionStatus::Cancelled;
         self.updated_at = Clock::get().unwrap().unix_timestamp;
     }
+
+    /// Settle the current period and clear any dunning state
+    pub fn record_payment(&mut self, now: i64) {
+        self.status = SubscriptionStatus::Active;
+        self.last_payment_date = Some(now);
+        self.next_payment_due = now + self.period_duration;
+        self.total_payments_made = self.total_payments_made.checked_add(1).unwrap();
+        self.period_usage = 0;
+        self.failed_payment_attempts = 0;
+        self.last_failed_attempt = None;
+        self.updated_at = now;
+    }
+
+    /// Register a failed collection, moving to PastDue or, once retries or
+    /// the grace period run out, to Suspended. A subscription that never
+    /// paid stays Incomplete instead, and expires once they run out
+    pub fn record_failed_payment(&mut self, now: i64, plan: &SubscriptionPlan) {
+        self.failed_payment_attempts = self.failed_payment_attempts.saturating_add(1);
+        self.last_failed_attempt = Some(now);
+        let exhausted = self.failed_payment_attempts >= plan.max_retry_attempts
+            || now > self.next_payment_due + plan.grace_period;
+        self.status = match self.status {
+            SubscriptionStatus::Incomplete if exhausted => SubscriptionStatus::Expired,
+            SubscriptionStatus::Incomplete => SubscriptionStatus::Incomplete,
+            _ if exhausted => SubscriptionStatus::Suspended,
+            _ => SubscriptionStatus::PastDue,
+        };
+        self.updated_at = now;
+    }
+
+    /// Start a pause, charging it against the plan's yearly allowance
+    pub fn pause(&mut self, now: i64, plan: &SubscriptionPlan) -> Result<()> {
+        if now >= self.pause_window_start + Self::PAUSE_WINDOW {
+            self.pause_window_start = now;
+            self.pauses_in_window = 0;
+        }
+        require!(
+            self.pauses_in_window < plan.max_pauses_per_year,
+            crate::errors::LooprError::PauseLimitReached
+        );
+
+        self.pauses_in_window = self.pauses_in_window.checked_add(1).unwrap();
+        self.paused_at = Some(now);
+        self.status = SubscriptionStatus::Paused;
+        self.updated_at = now;
+        Ok(())
+    }
+
+    /// End the current pause, pushing `next_payment_due` out by the time
+    /// spent paused (capped at the plan's maximum pause length)
+    pub fn resume(&mut self, now: i64, plan: &SubscriptionPlan) {
+        if let Some(paused_at) = self.paused_at.take() {
+            let paused_for = (now - paused_at).clamp(0, plan.max_pause_duration);
+            self.next_payment_due += paused_for;
+            if self.status == SubscriptionStatus::Paused {
+                self.status = SubscriptionStatus::Active;
+            }
+            self.updated_at = now;
+        }
+    }
+
+    /// Apply the time-based transitions for a missed payment or an ended term
+    pub fn refresh_status(&mut self, now: i64, plan: &SubscriptionPlan) {
+        // A pause left running past its maximum length resumes on its own
+        if let Some(paused_at) = self.paused_at {
+            if now >= paused_at + plan.max_pause_duration {
+                self.resume(paused_at + plan.max_pause_duration, plan);
+            }
+        }
+
+        let status = match self.status {
+            SubscriptionStatus::Trialing | SubscriptionStatus::Active | SubscriptionStatus::PastDue
+                if now > self.next_payment_due + plan.grace_period =>
+            {
+                SubscriptionStatus::Suspended
+            }
+            SubscriptionStatus::Trialing | SubscriptionStatus::Active
+                if now > self.next_payment_due =>
+            {
+                SubscriptionStatus::PastDue
+            }
+            SubscriptionStatus::Cancelled if now >= self.next_payment_due => {
+                SubscriptionStatus::Expired
+            }
+            SubscriptionStatus::Incomplete if now > self.next_payment_due + plan.grace_period => {
+                SubscriptionStatus::Expired
+            }
+            status => status,
+        };
+        if status != self.status {
+            self.status = status;
+            self.updated_at = now;
+        }
+    }
+
+    /// Earliest time the next collection attempt may run
+    pub fn next_collection_at(&self, plan: &SubscriptionPlan) -> i64 {
+        match self.last_failed_attempt {
+            Some(last) => (last + plan.retry_interval()).max(self.next_payment_due),
+            None => self.next_payment_due,
+        }
+    }
+
+    /// Bill the subscription at the plan's current price version
+    pub fn pin_price(&mut self, plan: &SubscriptionPlan) {
+        self.price_version = plan.price_version;
+        self.price_per_period = plan.price_per_period;
+        self.period_duration = plan.period_duration;
+    }
+
+    /// Move onto the plan's current price version once it may reach this
+    /// subscriber. Cuts apply straight away; increases wait out the plan's
+    /// notice period, and those above its consent threshold wait for
+    /// `accept_price_change`
+    pub fn sync_price(&mut self, now: i64, plan: &SubscriptionPlan) {
+        if self.price_version == plan.price_version {
+            return;
+        }
+        let adopt = if plan.raises_rate_from(self.price_per_period, self.period_duration, 0) {
+            plan.price_notice_period.map_or(false, |notice| now >= plan.price_changed_at + notice)
+                && !plan.raises_rate_from(
+                    self.price_per_period,
+                    self.period_duration,
+                    plan.price_consent_threshold_bps,
+                )
+        } else {
+            true
+        };
+        if adopt {
+            self.pin_price(plan);
+            self.updated_at = now;
+        }
+    }
+
+    /// Share of a per-period `price` covering `remaining` seconds of the
+    /// subscription's pinned period, rounded down
+    pub fn prorate(&self, price: u64, remaining: i64) -> u64 {
+        let remaining = remaining.clamp(0, self.period_duration) as u128;
+        (price as u128 * remaining / self.period_duration as u128) as u64
+    }
+
+    /// Per-period price a stream accrues at: the subscription's seats at its
+    /// pinned price version, after any forever coupon discount
+    pub fn stream_price(&self, plan: &SubscriptionPlan) -> Result<u64> {
+        Ok(self.effective_price(plan.seat_price_at(self.price_per_period, self.quantity)?))
+    }
+
+    /// Period price after any coupon discount
+    pub fn effective_price(&self, price: u64) -> u64 {
+        self.discount.as_ref().map_or(price, |discount| discount.apply(price))
+    }
+
+    /// Whether the applied coupon only covers a number of periods. Streams
+    /// never settle whole periods, so they cannot run such a coupon down
+    pub fn has_limited_discount(&self) -> bool {
+        self.discount.as_ref().map_or(false, |discount| discount.periods_remaining.is_some())
+    }
+
+    /// Count a settled period against the coupon, dropping it once used up
+    pub fn consume_discount_period(&mut self) {
+        if let Some(discount) = self.discount.as_mut() {
+            if let Some(remaining) = discount.periods_remaining.as_mut() {
+                *remaining = remaining.saturating_sub(1);
+                if *remaining == 0 {
+                    self.discount = None;
+                }
+            }
+        }
+    }
+
+    /// What is still owed on a `price` charge once credit is applied
+    pub fn amount_due(&self, price: u64) -> u64 {
+        price.saturating_sub(self.credit_balance)
+    }
+
+    /// Draw down credit against a `price` charge that has been settled
+    pub fn apply_credit(&mut self, price: u64) {
+        self.credit_balance = self.credit_balance.saturating_sub(price);
+    }
+
+    /// Consume part of the autopay mandate, failing if it would be overdrawn
+    pub fn consume_autopay_allowance(&mut self, amount: u64) -> Result<()> {
+        self.autopay_allowance = self
+            .autopay_allowance
+            .checked_sub(amount)
+            .ok_or(crate::errors::LooprError::AutopayAllowanceExceeded)?;
+        Ok(())
+    }
+
+    pub fn clear_autopay(&mut self) {
+        self.auto_pay_enabled = false;
+        self.autopay_token_account = None;
+        self.autopay_allowance = 0;
+    }
 }
 
-/// Payment record state
+/// Bounded history of a subscription's payments, replacing an account per payment
 #[account]
-pub struct PaymentRecord {
-    pub user: Pubkey,
+pub struct PaymentLedger {
     pub subscription: Pubkey,
+    /// Sequence number the next payment will be given; also the number of
+    /// payments ever recorded
+    pub next_sequence: u64,
+    /// Hash chained over every entry as it was appended, so history that has
+    /// rolled out of `entries` can still be checked against the payment logs
+    pub history_hash: [u8; 32],
+    /// The last `CAPACITY` payments; entry `n` lives in slot `n % CAPACITY`
+    pub entries: Vec<PaymentEntry>,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl PaymentLedger {
+    pub const LEN: usize = 8 + 32 + 8 + 32 + (4 + Self::CAPACITY * PaymentEntry::LEN) + 1 + VERSIONED_SPACE;
+
+    pub const CAPACITY: usize = 16;
+
+    /// Record a payment under the next sequence number, overwriting the
+    /// oldest entry once the ledger is full. An entry whose merchant share is
+    /// still in escrow is never overwritten, since it is needed to settle it
+    pub fn append(&mut self, mut entry: PaymentEntry) -> Result<u64> {
+        let sequence = self.next_sequence;
+        entry.sequence = sequence;
+
+        let slot = (sequence % Self::CAPACITY as u64) as usize;
+        match self.entries.get_mut(slot) {
+            Some(oldest) => {
+                require!(!oldest.is_escrowed(), crate::errors::LooprError::LedgerEntryEscrowed);
+                *oldest = entry;
+            }
+            None => self.entries.push(entry),
+        }
+
+        self.history_hash = anchor_lang::solana_program::hash::hashv(&[&self.history_hash, &entry.try_to_vec()?])
+            .to_bytes();
+        self.next_sequence = sequence.checked_add(1).unwrap();
+        Ok(sequence)
+    }
+
+    /// The entry for payment `sequence`, if it has not rolled out of the ledger
+    pub fn entry_mut(&mut self, sequence: u64) -> Result<&mut PaymentEntry> {
+        let slot = (sequence % Self::CAPACITY as u64) as usize;
+        self.entries
+            .get_mut(slot)
+            .filter(|entry| entry.sequence == sequence)
+            .ok_or_else(|| error!(crate::errors::LooprError::PaymentNotFound))
+    }
+
+    /// The ledger carried over to a subscription moved to a new address; the
+    /// history hash carries over too, so the chain stays unbroken
+    pub fn rekeyed(&self, subscription: Pubkey, bump: u8) -> Self {
+        Self {
+            subscription,
+            bump,
+            ..self.clone()
+        }
+    }
+}
+
+/// One payment in a subscription's ledger
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PaymentEntry {
+    /// Position in the subscription's payment history, starting at 0
+    pub sequence: u64,
     pub amount: u64,
+    pub mint: Pubkey,
+    pub decimals: u8,
+    /// Token-2022 transfer fee withheld from the merchant's share
+    pub fee_amount: u64,
+    /// Protocol fee paid to the treasury out of `amount`
+    pub protocol_fee: u64,
+    /// Amount the merchant actually received, less what it has refunded since
+    pub net_amount: u64,
+    /// Part of `amount` returned to the payer so far
+    pub refunded_amount: u64,
+    /// Merchant's share still held in the plan's escrow vault
+    pub escrowed_amount: u64,
+    /// When the escrowed share may be released to the merchant; `None` once
+    /// it has been paid out or if the payment was never escrowed
+    pub release_at: Option<i64>,
     pub payment_date: i64,
-    pub transaction_signature: [u8; 128],
     pub payment_method: PaymentMethod,
     pub status: PaymentStatus,
-    pub bump: u8,
 }
 
-impl PaymentRecord {
-    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 128 + 1 + 1 + 1 + 8;
+impl PaymentEntry {
+    pub const LEN: usize = 8 + 8 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + (1 + 8) + 8 + 1 + 1;
 
-    pub fn set_transaction_signature(&mut self, signature: &str) {
-        self.transaction_signature = string_to_fixed_bytes::<128>(signature);
+    /// Whether the merchant's share is still held in escrow
+    pub fn is_escrowed(&self) -> bool {
+        self.release_at.is_some()
     }
 
-    pub fn get_transaction_signature(&self) -> String {
-        bytes_to_string(&self.transaction_signature)
+    /// Hold the merchant's share in escrow until `release_at`; nothing has
+    /// reached the merchant until it is released
+    pub fn hold_in_escrow(&mut self, amount: u64, release_at: i64) {
+        self.escrowed_amount = amount;
+        self.release_at = Some(release_at);
+        self.net_amount = 0;
     }
 
+    /// Mark the escrowed share as paid out, `net_amount` of it reaching the merchant
+    pub fn release_escrow(&mut self, net_amount: u64) {
+        self.escrowed_amount = 0;
+        self.release_at = None;
+        self.net_amount = net_amount;
+    }
+
+    /// Part of the payment the merchant may still refund: what it received
+    /// and hasn't returned. Escrowed payments are settled through disputes instead
+    pub fn refundable(&self) -> u64 {
+        match self.status {
+            PaymentStatus::Completed | PaymentStatus::PartiallyRefunded if !self.is_escrowed() => self.net_amount,
+            _ => 0,
+        }
+    }
+
+    /// Return `amount` of the merchant's share to the payer, moving to
+    /// `PartiallyRefunded` or, once the merchant holds nothing of it, `Refunded`
+    pub fn record_refund(&mut self, amount: u64) {
+        self.net_amount = self.net_amount.checked_sub(amount).unwrap();
+        self.refunded_amount = self.refunded_amount.checked_add(amount).unwrap();
+        self.status = if self.net_amount == 0 {
+            PaymentStatus::Refunded
+        } else {
+            PaymentStatus::PartiallyRefunded
+        };
+    }
+
+    /// A new entry; its sequence number is assigned when it is appended to a ledger
     pub fn from_fields(
-        user: Pubkey,
-        subscription: Pubkey,
         amount: u64,
+        mint: Pubkey,
+        decimals: u8,
+        fee_amount: u64,
+        protocol_fee: u64,
+        net_amount: u64,
         payment_date: i64,
-        transaction_signature: &str,
         payment_method: PaymentMethod,
         status: PaymentStatus,
-        bump: u8,
     ) -> Self {
         Self {
-            user,
-            subscription,
+            sequence: 0,
             amount,
+            mint,
+            decimals,
+            fee_amount,
+            protocol_fee,
+            net_amount,
+            refunded_amount: 0,
+            escrowed_amount: 0,
+            release_at: None,
             payment_date,
-            transaction_signature: string_to_fixed_bytes::<128>(transaction_signature),
             payment_method,
             status,
-            bump,
         }
     }
 }
@@ -250,6 +1093,8 @@ impl PaymentRecord {
 pub struct PaymentIntent {
     pub intent_id: [u8; 64],
     pub plan_id: [u8; 64],
+    /// Created the intent and paid its rent
+    pub authority: Pubkey,
     pub payer: Option<Pubkey>,
     pub amount: u64,
     pub status: PaymentIntentStatus,
@@ -257,11 +1102,15 @@ pub struct PaymentIntent {
     pub expires_at: i64,
     pub fulfilled_at: Option<i64>,
     pub subscription: Option<Pubkey>,
+    /// Coupon the intent's amount was discounted with, redeemed on payment
+    pub coupon: Option<Pubkey>,
     pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
 }
 
 impl PaymentIntent {
-    pub const LEN: usize = 8 + 64 + 64 + (1 + 32) + 8 + 1 + 8 + 8 + (1 + 8) + (1 + 32) + 1 + 16;
+    pub const LEN: usize = 8 + 64 + 64 + 32 + (1 + 32) + 8 + 1 + 8 + 8 + (1 + 8) + (1 + 32) + (1 + 32) + 1 + VERSIONED_SPACE;
 
     pub fn set_intent_id(&mut self, id: &str) {
         self.intent_id = string_to_fixed_bytes::<64>(id);
@@ -279,4 +1128,350 @@ impl PaymentIntent {
         bytes_to_string(&self.plan_id)
     }
 
-    pub
+    pub fn is_expired(&self, now: i64) -> bool {
+        now > self.expires_at
+    }
+
+    /// Whether the intent can no longer be paid, so its account may be closed
+    pub fn is_closable(&self, now: i64) -> bool {
+        self.status != PaymentIntentStatus::Created || self.is_expired(now)
+    }
+}
+
+/// A usage report against a metered subscription; its PDA makes each report id count once
+#[account]
+pub struct UsageRecord {
+    pub subscription: Pubkey,
+    pub reporter: Pubkey,
+    pub report_id: u64,
+    pub units: u64,
+    pub reported_at: i64,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl UsageRecord {
+    pub const LEN: usize = 8 + 32 + 32 + 8 + 8 + 8 + 1 + VERSIONED_SPACE;
+}
+
+/// Marks that a wallet has used its trial on a plan
+#[account]
+pub struct TrialRecord {
+    pub user: Pubkey,
+    pub subscription_plan: Pubkey,
+    /// When the trial started, or `None` if the wallet has not trialled yet
+    pub started_at: Option<i64>,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl TrialRecord {
+    pub const LEN: usize = 8 + 32 + 32 + (1 + 8) + 1 + VERSIONED_SPACE;
+
+    /// Claim the plan's trial for this wallet, returning when it ends, or
+    /// `None` if the plan has no trial or the wallet already used it
+    pub fn claim(&mut self, plan: &SubscriptionPlan, now: i64) -> Option<i64> {
+        if plan.trial_duration == 0 || self.started_at.is_some() {
+            return None;
+        }
+        self.started_at = Some(now);
+        Some(now + plan.trial_duration)
+    }
+}
+
+/// Discount coupon issued by a plan authority for its plans
+#[account]
+pub struct Coupon {
+    pub authority: Pubkey,
+    pub code: [u8; 32],
+    pub discount_type: DiscountType,
+    /// Basis points off for `Percent`, raw token units off for `Fixed`
+    pub discount_value: u64,
+    pub duration: CouponDuration,
+    pub max_redemptions: Option<u32>,
+    pub times_redeemed: u32,
+    pub expires_at: Option<i64>,
+    /// Plan the coupon is limited to, or any of the authority's plans
+    pub plan: Option<Pubkey>,
+    /// Mint the discount is denominated in, as in `SubscriptionPlan::accepted_mint`
+    pub mint: Pubkey,
+    pub is_active: bool,
+    pub created_at: i64,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl Coupon {
+    pub const LEN: usize = 8 + 32 + 32 + 1 + 8 + (1 + 2) + (1 + 4) + 4 + (1 + 8) + (1 + 32) + 32 + 1 + 8 + 1 + VERSIONED_SPACE;
+
+    pub fn set_code(&mut self, code: &str) {
+        self.code = string_to_fixed_bytes::<32>(code);
+    }
+
+    pub fn get_code(&self) -> String {
+        bytes_to_string(&self.code)
+    }
+
+    /// Fail unless the coupon can still be redeemed against `plan` at `now`
+    pub fn check_redeemable(&self, plan: &Account<SubscriptionPlan>, now: i64) -> Result<()> {
+        use crate::errors::LooprError;
+
+        require!(self.is_active, LooprError::InvalidCoupon);
+        require!(self.plan.map_or(true, |bound| bound == plan.key()), LooprError::InvalidCoupon);
+        require!(self.mint == plan.accepted_mint, LooprError::InvalidCoupon);
+        // A fixed amount is in the mint's units, which a USD-priced plan's prices aren't
+        require!(
+            self.discount_type != DiscountType::Fixed || !plan.is_usd_priced(),
+            LooprError::InvalidCoupon
+        );
+        if let Some(expires_at) = self.expires_at {
+            require!(now <= expires_at, LooprError::CouponExpired);
+        }
+        if let Some(max_redemptions) = self.max_redemptions {
+            require!(
+                self.times_redeemed < max_redemptions,
+                LooprError::CouponRedemptionLimitReached
+            );
+        }
+        Ok(())
+    }
+
+    /// Snapshot of the discount as applied to a subscription
+    pub fn to_applied(&self, coupon: Pubkey) -> AppliedDiscount {
+        AppliedDiscount {
+            coupon,
+            discount_type: self.discount_type,
+            discount_value: self.discount_value,
+            periods_remaining: match self.duration {
+                CouponDuration::Once => Some(1),
+                CouponDuration::Repeating(periods) => Some(periods),
+                CouponDuration::Forever => None,
+            },
+            plan: self.plan,
+            mint: self.mint,
+        }
+    }
+}
+
+/// Discount carried on a subscription, copied from its coupon when applied
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AppliedDiscount {
+    pub coupon: Pubkey,
+    pub discount_type: DiscountType,
+    pub discount_value: u64,
+    /// Periods the discount still covers; `None` for a forever coupon
+    pub periods_remaining: Option<u16>,
+    /// Plan the coupon was limited to, as in `Coupon::plan`
+    pub plan: Option<Pubkey>,
+    /// Mint the discount is denominated in, as in `Coupon::mint`
+    pub mint: Pubkey,
+}
+
+impl AppliedDiscount {
+    pub const LEN: usize = 32 + 1 + 8 + (1 + 2) + (1 + 32) + 32;
+
+    /// Whether the coupon could have been redeemed against `plan`
+    pub fn covers(&self, plan: &Account<SubscriptionPlan>) -> bool {
+        self.plan.map_or(true, |bound| bound == plan.key())
+            && self.mint == plan.accepted_mint
+            && (self.discount_type != DiscountType::Fixed || !plan.is_usd_priced())
+    }
+
+    /// `price` with the discount taken off, never below zero
+    pub fn apply(&self, price: u64) -> u64 {
+        match self.discount_type {
+            DiscountType::Percent => {
+                let off = price as u128 * self.discount_value.min(10_000) as u128 / 10_000;
+                price - off as u64
+            }
+            DiscountType::Fixed => price.saturating_sub(self.discount_value),
+        }
+    }
+}
+
+/// Protocol fees and volume collected in one mint
+#[account]
+pub struct FeeStats {
+    pub mint: Pubkey,
+    pub total_fees: u64,
+    pub total_volume: u64,
+    pub total_payments: u64,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl FeeStats {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 1 + VERSIONED_SPACE;
+
+    pub fn record(&mut self, amount: u64, fee: u64) {
+        self.total_fees = self.total_fees.checked_add(fee).unwrap();
+        self.total_volume = self.total_volume.checked_add(amount).unwrap();
+        self.total_payments = self.total_payments.checked_add(1).unwrap();
+    }
+}
+
+/// Global program state
+#[account]
+pub struct GlobalState {
+    pub authority: Pubkey,
+    pub total_plans: u64,
+    pub total_subscriptions: u64,
+    pub total_payments_processed: u64,
+    pub total_volume: u64,
+    /// Refunded out of `total_volume`, which is kept net of refunds
+    pub total_refunded: u64,
+    pub is_paused: bool,
+    /// Share of each keeper-collected payment paid to the keeper, in basis points
+    pub keeper_bounty_bps: u16,
+    /// Protocol fee taken from every payment, in basis points
+    pub fee_bps: u16,
+    /// Wallet protocol fees are paid to
+    pub treasury: Pubkey,
+    /// Resolves disputes on escrowed payments unless a plan names its own
+    pub arbiter: Pubkey,
+    /// Program that must own the price feeds USD-priced plans convert with
+    pub price_oracle: Pubkey,
+    /// Proposed new authority, which takes over once it accepts
+    pub pending_authority: Option<Pubkey>,
+    pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; RESERVED_SPACE],
+}
+
+impl GlobalState {
+    pub const LEN: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 2 + 2 + 32 + 32 + 32 + (1 + 32) + 1 + VERSIONED_SPACE;
+
+    pub const DEFAULT_KEEPER_BOUNTY_BPS: u16 = 10;
+    pub const MAX_KEEPER_BOUNTY_BPS: u16 = 500;
+    pub const MAX_FEE_BPS: u16 = 1_000;
+
+    /// Protocol fee owed to the treasury on `amount`
+    pub fn protocol_fee(&self, amount: u64) -> u64 {
+        (amount as u128 * self.fee_bps as u128 / 10_000) as u64
+    }
+
+    /// Bounty owed to a keeper for collecting `amount`
+    pub fn keeper_bounty(&self, amount: u64) -> u64 {
+        (amount as u128 * self.keeper_bounty_bps as u128 / 10_000) as u64
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MerchantStatus {
+    Active,
+    /// Barred by the protocol from new plans, subscribers and charges
+    Suspended,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubscriptionStatus {
+    Trialing,
+    Active,
+    Paused,
+    PastDue,
+    Suspended,
+    Cancelled,
+    Expired,
+    /// Created without a trial and waiting for its first payment; it only
+    /// counts against the plan's capacity once that payment goes through
+    Incomplete,
+}
+
+impl SubscriptionStatus {
+    /// In good standing or still inside the grace period
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Trialing | Self::Active | Self::PastDue)
+    }
+
+    /// Whether autopay may collect from it: active, or awaiting its first payment
+    pub fn is_collectable(&self) -> bool {
+        self.is_active() || *self == Self::Incomplete
+    }
+
+    /// Not yet ended by cancellation or expiry
+    pub fn is_open(&self) -> bool {
+        !matches!(self, Self::Cancelled | Self::Expired)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PricingModel {
+    /// `price_per_period` regardless of quantity
+    Flat,
+    /// `price_per_period` for every seat
+    PerUnit,
+    /// Each seat priced by the tier it falls in
+    Graduated,
+    /// Every seat priced by the tier the total quantity falls in
+    Volume,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiscountType {
+    Percent,
+    Fixed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CouponDuration {
+    Once,
+    Repeating(u16),
+    Forever,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentMethod {
+    Manual,
+    AutoPay,
+    QRCode,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentStatus {
+    Pending,
+    Completed,
+    Failed,
+    Refunded,
+    PartiallyRefunded,
+    /// Escrowed payment under dispute, awaiting the arbiter
+    Disputed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaymentIntentStatus {
+    Created,
+    Completed,
+    Expired,
+    Cancelled,
+}
+
+/// Copy a string into a zero-padded fixed-size byte array, truncating if needed
+pub fn string_to_fixed_bytes<const N: usize>(value: &str) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    let src = value.as_bytes();
+    let len = src.len().min(N);
+    bytes[..len].copy_from_slice(&src[..len]);
+    bytes
+}
+
+/// Render a raw token amount with its decimal point, e.g. 1500000 @ 6 -> "1.5"
+pub fn format_token_amount(amount: u64, decimals: u8) -> String {
+    let scale = 10u128.pow(decimals as u32);
+    let whole = amount as u128 / scale;
+    let frac = amount as u128 % scale;
+    if frac == 0 {
+        return whole.to_string();
+    }
+    let frac = format!("{:0width$}", frac, width = decimals as usize);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+/// Read a zero-padded byte array back into a string
+pub fn bytes_to_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
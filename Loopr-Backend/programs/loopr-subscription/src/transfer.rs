@@ -0,0 +1,255 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+};
+use anchor_spl::token_interface::{self, Approve, CloseAccount, Mint, Revoke, TokenAccount};
+use crate::{errors::*, state::{SplitRecipient, SubscriptionPlan}};
+
+/// Transfer fee Token-2022 withholds when moving `amount` of `mint`
+pub fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+
+    let mint_data = mint_info.try_borrow_data()?;
+    let mint_state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint_data)?;
+    match mint_state.get_extension::<TransferFeeConfig>() {
+        Ok(fee_config) => fee_config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or_else(|| error!(LooprError::InvalidPaymentAmount)),
+        Err(_) => Ok(0),
+    }
+}
+
+/// `transfer_checked` through whichever token program owns `mint`.
+///
+/// Transfer-hook mints resolve their extra accounts from `extra_accounts`
+/// (the instruction's remaining accounts). Returns the amount `to` actually
+/// receives once any transfer fee is withheld.
+pub fn transfer_checked<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    extra_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    let fee = transfer_fee(mint, amount)?;
+
+    spl_token_2022::onchain::invoke_transfer_checked(
+        token_program.key,
+        from.clone(),
+        mint.to_account_info(),
+        to.clone(),
+        authority.clone(),
+        extra_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )?;
+
+    Ok(amount.checked_sub(fee).unwrap())
+}
+
+/// Splits an instruction's remaining accounts into the revenue split
+/// recipients' accounts (the first `recipients` entries, in split order) and
+/// the transfer-hook extras that follow them
+pub fn split_remaining_accounts<'a, 'info>(
+    remaining_accounts: &'a [AccountInfo<'info>],
+    recipients: usize,
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    require!(remaining_accounts.len() >= recipients, LooprError::MissingSplitRecipients);
+    Ok(remaining_accounts.split_at(recipients))
+}
+
+/// Pays `amount` out to a plan's payees: each split recipient gets its
+/// `shares` entry and `authority_account` the rest.
+///
+/// Returns the total the payees actually receive once any transfer fee is withheld.
+pub fn pay_out<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    authority_account: &AccountInfo<'info>,
+    signer: &AccountInfo<'info>,
+    recipients: &[SplitRecipient],
+    shares: &[u64],
+    recipient_accounts: &[AccountInfo<'info>],
+    extra_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    let mut net_amount = 0u64;
+    let mut remainder = amount;
+
+    for ((recipient, share), account) in recipients.iter().zip(shares).zip(recipient_accounts) {
+        require_keys_eq!(*account.owner, token_program.key(), LooprError::InvalidSplitRecipient);
+        let token_account = TokenAccount::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+        require!(
+            token_account.owner == recipient.wallet && token_account.mint == mint.key(),
+            LooprError::InvalidSplitRecipient
+        );
+
+        remainder = remainder.checked_sub(*share).unwrap();
+        if *share > 0 {
+            let received = transfer_checked(
+                token_program, from, mint, account, signer, extra_accounts, *share, signer_seeds,
+            )?;
+            net_amount = net_amount.checked_add(received).unwrap();
+        }
+    }
+
+    let received = transfer_checked(
+        token_program, from, mint, authority_account, signer, extra_accounts, remainder, signer_seeds,
+    )?;
+    Ok(net_amount.checked_add(received).unwrap())
+}
+
+/// Pays the merchant's share of a payment: to the plan's payees via
+/// [`pay_out`], or into the plan's escrow vault when it holds payments for a
+/// dispute window, in which case split partners are paid on release.
+///
+/// Returns the amount that actually arrives either way.
+pub fn pay_merchant<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    plan: &SubscriptionPlan,
+    plan_token_account: &AccountInfo<'info>,
+    escrow_vault: Option<AccountInfo<'info>>,
+    signer: &AccountInfo<'info>,
+    recipient_accounts: &[AccountInfo<'info>],
+    extra_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<u64> {
+    if !plan.holds_in_escrow() {
+        return pay_out(
+            token_program,
+            from,
+            mint,
+            plan_token_account,
+            signer,
+            &plan.revenue_split,
+            &plan.split_shares(amount),
+            recipient_accounts,
+            extra_accounts,
+            amount,
+            signer_seeds,
+        );
+    }
+
+    let escrow_vault = escrow_vault.ok_or(LooprError::MissingEscrowVault)?;
+    transfer_checked(token_program, from, mint, &escrow_vault, signer, extra_accounts, amount, signer_seeds)
+}
+
+/// Native SOL counterpart of [`pay_out`]; recipients are paid to their wallets directly
+pub fn pay_out_lamports<'info>(
+    system_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    recipients: &[SplitRecipient],
+    shares: &[u64],
+    recipient_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let mut remainder = amount;
+
+    for ((recipient, share), account) in recipients.iter().zip(shares).zip(recipient_accounts) {
+        require_keys_eq!(account.key(), recipient.wallet, LooprError::InvalidSplitRecipient);
+
+        remainder = remainder.checked_sub(*share).unwrap();
+        if *share > 0 {
+            transfer_lamports(system_program, from, account, *share)?;
+        }
+    }
+
+    transfer_lamports(system_program, from, authority, remainder)
+}
+
+fn transfer_lamports<'info>(
+    system_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    to: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<()> {
+    let cpi_ctx = CpiContext::new(
+        system_program.clone(),
+        anchor_lang::system_program::Transfer {
+            from: from.clone(),
+            to: to.clone(),
+        },
+    );
+    anchor_lang::system_program::transfer(cpi_ctx, amount)
+}
+
+/// Close an empty program-owned vault, returning its rent to `destination`
+pub fn close_vault<'info>(
+    token_program: &AccountInfo<'info>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    destination: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    require!(vault.amount == 0, LooprError::AccountNotEmpty);
+
+    let cpi_ctx = CpiContext::new_with_signer(
+        token_program.clone(),
+        CloseAccount {
+            account: vault.to_account_info(),
+            destination: destination.clone(),
+            authority: authority.clone(),
+        },
+        signer_seeds,
+    );
+    token_interface::close_account(cpi_ctx)
+}
+
+/// Whether `delegate` can currently pull `amount` out of `token_account`
+pub fn can_pull(token_account: &InterfaceAccount<TokenAccount>, delegate: &Pubkey, amount: u64) -> bool {
+    !token_account.is_frozen()
+        && token_account.amount >= amount
+        && token_account.delegate == Some(*delegate).into()
+        && token_account.delegated_amount >= amount
+}
+
+/// Give back one mandate's `allowance` of the autopay delegate's approval on
+/// `token_account`, revoking the approval once nothing is left. Other
+/// subscriptions paying from the same account keep their share.
+pub fn release_autopay_approval<'info>(
+    token_program: &AccountInfo<'info>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    delegate: &AccountInfo<'info>,
+    owner: &AccountInfo<'info>,
+    allowance: u64,
+) -> Result<()> {
+    if token_account.delegate != Some(delegate.key()).into() {
+        return Ok(());
+    }
+
+    let remaining = token_account.delegated_amount.saturating_sub(allowance);
+    if remaining == 0 {
+        let revoke_ctx = CpiContext::new(
+            token_program.clone(),
+            Revoke {
+                source: token_account.to_account_info(),
+                authority: owner.clone(),
+            },
+        );
+        token_interface::revoke(revoke_ctx)
+    } else {
+        let approve_ctx = CpiContext::new(
+            token_program.clone(),
+            Approve {
+                to: token_account.to_account_info(),
+                delegate: delegate.clone(),
+                authority: owner.clone(),
+            },
+        );
+        token_interface::approve(approve_ctx, remaining)
+    }
+}
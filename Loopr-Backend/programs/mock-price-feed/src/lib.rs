@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+declare_id!("MockPrice1111111111111111111111111111111111");
+
+/// Test-only stand-in for an oracle: tests create an account owned by this
+/// program and write raw Pyth price account bytes into it
+#[program]
+pub mod mock_price_feed {
+    use super::*;
+
+    /// Overwrite `data.len()` bytes of the price account starting at `offset`
+    pub fn write(ctx: Context<Write>, offset: u32, data: Vec<u8>) -> Result<()> {
+        let mut account_data = ctx.accounts.price_account.try_borrow_mut_data()?;
+        let start = offset as usize;
+        let end = start.checked_add(data.len()).unwrap();
+        require!(end <= account_data.len(), ErrorCode::AccountDidNotDeserialize);
+        account_data[start..end].copy_from_slice(&data);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Write<'info> {
+    /// CHECK: Raw price account bytes; only its owner (this program) can write them
+    #[account(mut, owner = crate::ID)]
+    pub price_account: UncheckedAccount<'info>,
+}
@@ -0,0 +1,184 @@
+import { PublicKey, SystemProgram } from "@solana/web3.js";
+import { expect } from "chai";
+import v1SubscriptionPlan from "./fixtures/v1-subscription-plan.json";
+import v1UserSubscription from "./fixtures/v1-user-subscription.json";
+import v1PaymentIntent from "./fixtures/v1-payment-intent.json";
+import { provider, program, setupMerchant, findMerchantPda, findPaymentLedgerPda } from "./setup";
+
+// The fixtures are loaded into the test validator from Anchor.toml and hold
+// accounts in the layouts the program was first deployed with
+describe("Account versions", () => {
+  const ACCOUNT_VERSION = 2;
+
+  const planAuthority = new PublicKey("8gDdEtXRAdgAzMZWqUX2VctyhDbDuLA9AK6Mpqkx6TeB");
+  const subscriber = new PublicKey("LQVcTQajEfHFgC7dJeWJ6R3uBsqZrSdp9rTzv344p4A");
+
+  const fixtureAddress = (fixture: { pubkey: string }) => new PublicKey(fixture.pubkey);
+  const planPda = fixtureAddress(v1SubscriptionPlan);
+  const subscriptionPda = fixtureAddress(v1UserSubscription);
+
+  const toString = (bytes: number[]) => Buffer.from(bytes).toString().replace(/\0+$/, "");
+
+  // Migrate a fixture, checking it grew and the payer topped its rent up to
+  // the new size
+  const migrate = async (fixture: { pubkey: string; account: { space: number } }, accounts: object = {}) => {
+    const account = fixtureAddress(fixture);
+    const before = await provider.connection.getAccountInfo(account);
+    expect(before.data.length).to.equal(fixture.account.space);
+
+    await program.methods
+      .migrateAccount()
+      .accounts({
+        account,
+        ...accounts,
+        payer: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const after = await provider.connection.getAccountInfo(account);
+    expect(after.owner.toString()).to.equal(program.programId.toString());
+    expect(after.data.length).to.be.greaterThan(fixture.account.space);
+    expect(after.lamports).to.equal(
+      await provider.connection.getMinimumBalanceForRentExemption(after.data.length)
+    );
+    return account;
+  };
+
+  const expectCurrentVersion = (account: { version: number; reserved: number[] }) => {
+    expect(account.version).to.equal(ACCOUNT_VERSION);
+    expect(account.reserved).to.deep.equal(new Array(64).fill(0));
+  };
+
+  it("Creates new accounts at the current version", async () => {
+    const { merchantPda } = await setupMerchant("Versioned Merchant");
+
+    expectCurrentVersion(await program.account.merchant.fetch(merchantPda));
+
+    try {
+      await program.methods
+        .migrateAccount()
+        .accounts({
+          account: merchantPda,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      expect.fail("Should have failed with the account already migrated");
+    } catch (error) {
+      expect(error.message).to.include("AccountAlreadyMigrated");
+    }
+  });
+
+  it("Migrates a v1 plan in place, billed in native SOL", async () => {
+    const plan = await program.account.subscriptionPlan.fetch(await migrate(v1SubscriptionPlan));
+
+    expectCurrentVersion(plan);
+    expect(plan.authority.toString()).to.equal(planAuthority.toString());
+    expect(plan.merchant.toString()).to.equal(findMerchantPda(planAuthority).toString());
+    expect(toString(plan.planId)).to.equal("v1-plan");
+    expect(toString(plan.description)).to.equal("Plan created before accounts were versioned");
+    expect(plan.acceptedMint.toString()).to.equal(PublicKey.default.toString());
+    expect(plan.mintDecimals).to.equal(9);
+    expect(plan.pricePerPeriod.toNumber()).to.equal(100_000_000);
+    expect(plan.priceVersion).to.equal(1);
+    expect(plan.revenueSplit).to.have.length(0);
+    expect(plan.maxSubscribers).to.equal(1000);
+    expect(plan.currentSubscribers).to.equal(1);
+    expect(plan.createdAt.toNumber()).to.equal(1_697_408_000);
+    expect(plan.isActive).to.be.true;
+    expect(plan.bump).to.equal(255);
+  });
+
+  it("Needs the plan and a new ledger to migrate a v1 subscription", async () => {
+    try {
+      await program.methods
+        .migrateAccount()
+        .accounts({
+          account: subscriptionPda,
+          paymentLedger: findPaymentLedgerPda(subscriptionPda),
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      expect.fail("Should have failed without the plan");
+    } catch (error) {
+      expect(error.message).to.include("InvalidMigrationAccounts");
+    }
+  });
+
+  it("Migrates a v1 subscription in place, starting its payment ledger", async () => {
+    const paymentLedger = findPaymentLedgerPda(subscriptionPda);
+    await migrate(v1UserSubscription, { subscriptionPlan: planPda, paymentLedger });
+
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda);
+    expectCurrentVersion(subscription);
+    expect(subscription.user.toString()).to.equal(subscriber.toString());
+    expect(subscription.subscriptionPlan.toString()).to.equal(planPda.toString());
+    expect(toString(subscription.subscriptionId)).to.equal("v1-sub");
+    expect(subscription.status).to.deep.equal({ active: {} });
+    expect(subscription.nextPaymentDue.toNumber()).to.equal(1_702_592_000);
+    expect(subscription.lastPaymentDate.toNumber()).to.equal(1_700_000_000);
+    // Its Clockwork thread is no autopay mandate
+    expect(subscription.autoPayEnabled).to.be.false;
+    expect(subscription.pricePerPeriod.toNumber()).to.equal(100_000_000);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(3);
+    expect(subscription.createdAt.toNumber()).to.equal(1_697_408_000);
+    expect(subscription.bump).to.equal(255);
+
+    const ledger = await program.account.paymentLedger.fetch(paymentLedger);
+    expectCurrentVersion(ledger);
+    expect(ledger.subscription.toString()).to.equal(subscriptionPda.toString());
+    expect(ledger.nextSequence.toNumber()).to.equal(0);
+    expect(ledger.entries).to.have.length(0);
+  });
+
+  it("Migrates a v1 payment intent in place, credited to the plan authority", async () => {
+    const intent = await program.account.paymentIntent.fetch(
+      await migrate(v1PaymentIntent, { subscriptionPlan: planPda })
+    );
+
+    expectCurrentVersion(intent);
+    expect(toString(intent.intentId)).to.equal("v1-intent");
+    expect(toString(intent.planId)).to.equal("v1-plan");
+    expect(intent.authority.toString()).to.equal(planAuthority.toString());
+    expect(intent.amount.toNumber()).to.equal(100_000_000);
+    expect(intent.status).to.deep.equal({ created: {} });
+    expect(intent.expiresAt.toNumber()).to.equal(1_700_086_400);
+    expect(intent.payer).to.be.null;
+    expect(intent.coupon).to.be.null;
+    expect(intent.bump).to.equal(255);
+  });
+
+  it("Refuses to migrate an account twice", async () => {
+    try {
+      await program.methods
+        .migrateAccount()
+        .accounts({
+          account: planPda,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      expect.fail("Should have failed with the account already migrated");
+    } catch (error) {
+      expect(error.message).to.include("AccountAlreadyMigrated");
+    }
+  });
+
+  it("Refuses accounts the program doesn't own", async () => {
+    try {
+      await program.methods
+        .migrateAccount()
+        .accounts({
+          account: provider.wallet.publicKey,
+          payer: provider.wallet.publicKey,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      expect.fail("Should have failed with an unknown layout");
+    } catch (error) {
+      expect(error.message).to.include("UnknownAccountLayout");
+    }
+  });
+});
@@ -0,0 +1,363 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  periodDuration,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  findSubscriptionPlanPda,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+} from "./setup";
+
+describe("Administration", () => {
+  const planId = "admin-plan";
+  const planPrice = 100;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let lateUser: Keypair;
+  let newAdmin: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let feeStatsPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const setPaused = async (paused: boolean) => {
+    await program.methods
+      .setPaused(paused)
+      .accounts({
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+  };
+
+  const subscribe = async (subscriber: Keypair) => {
+    const userSubscription = findUserSubscriptionPda(subscriber.publicKey, subscriptionPlanPda);
+    await program.methods
+      .createSubscription("admin-sub")
+      .accounts({
+        userSubscription,
+        paymentLedger: findPaymentLedgerPda(userSubscription),
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: subscriber.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, subscriber.publicKey),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([subscriber])
+      .rpc();
+    return userSubscription;
+  };
+
+  const expectPaused = async (call: Promise<unknown>) => {
+    try {
+      await call;
+      expect.fail("Should have failed with program paused");
+    } catch (error) {
+      expect(error.message).to.include("ProgramPaused");
+    }
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount, feeStats: feeStatsPda } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Admin plan",
+      description: "Plan used to exercise the global pause",
+    });
+
+    user = Keypair.generate();
+    lateUser = Keypair.generate();
+    newAdmin = Keypair.generate();
+    await airdrop(user.publicKey);
+    await airdrop(lateUser.publicKey);
+    await airdrop(newAdmin.publicKey);
+
+    userSubscriptionPda = await subscribe(user);
+
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+  });
+
+  after(async () => {
+    // Other suites assume the program is live
+    await setPaused(false);
+  });
+
+  it("Starts with no pending authority", async () => {
+    const globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.pendingAuthority).to.be.null;
+    expect(globalState.isPaused).to.be.false;
+  });
+
+  it("Rejects an authority proposal from anyone but the authority", async () => {
+    try {
+      await program.methods
+        .proposeAuthority(newAdmin.publicKey)
+        .accounts({
+          globalState: globalStatePda,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Transfers authority only once the proposed key accepts", async () => {
+    await program.methods
+      .proposeAuthority(newAdmin.publicKey)
+      .accounts({
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    let globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.pendingAuthority.toString()).to.equal(newAdmin.publicKey.toString());
+    expect(globalState.authority.toString()).to.equal(provider.wallet.publicKey.toString());
+
+    try {
+      await program.methods
+        .acceptAuthority()
+        .accounts({
+          globalState: globalStatePda,
+          newAuthority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with no pending authority");
+    } catch (error) {
+      expect(error.message).to.include("NoPendingAuthority");
+    }
+
+    await program.methods
+      .acceptAuthority()
+      .accounts({
+        globalState: globalStatePda,
+        newAuthority: newAdmin.publicKey,
+      })
+      .signers([newAdmin])
+      .rpc();
+
+    globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.authority.toString()).to.equal(newAdmin.publicKey.toString());
+    expect(globalState.pendingAuthority).to.be.null;
+
+    // The previous authority has no say once the handover completes
+    try {
+      await setPaused(true);
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+
+    // Hand the program back so the rest of the suites keep working
+    await program.methods
+      .proposeAuthority(provider.wallet.publicKey)
+      .accounts({
+        globalState: globalStatePda,
+        authority: newAdmin.publicKey,
+      })
+      .signers([newAdmin])
+      .rpc();
+    await program.methods
+      .acceptAuthority()
+      .accounts({
+        globalState: globalStatePda,
+        newAuthority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.authority.toString()).to.equal(provider.wallet.publicKey.toString());
+  });
+
+  it("Leaves unset fields alone when updating the config", async () => {
+    const before = await program.account.globalState.fetch(globalStatePda);
+
+    await program.methods
+      .updateGlobalConfig(before.keeperBountyBps, null, null, null, null)
+      .accounts({
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const after = await program.account.globalState.fetch(globalStatePda);
+    expect(after.feeBps).to.equal(before.feeBps);
+    expect(after.treasury.toString()).to.equal(before.treasury.toString());
+    expect(after.arbiter.toString()).to.equal(before.arbiter.toString());
+    expect(after.priceOracle.toString()).to.equal(before.priceOracle.toString());
+  });
+
+  it("Rejects invalid config values", async () => {
+    try {
+      await program.methods
+        .updateGlobalConfig(10_001, null, null, null, null)
+        .accounts({
+          globalState: globalStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+      expect.fail("Should have failed with keeper bounty too high");
+    } catch (error) {
+      expect(error.message).to.include("KeeperBountyTooHigh");
+    }
+
+    try {
+      await program.methods
+        .updateGlobalConfig(null, null, PublicKey.default, null, null)
+        .accounts({
+          globalState: globalStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+      expect.fail("Should have failed with invalid treasury");
+    } catch (error) {
+      expect(error.message).to.include("InvalidTreasury");
+    }
+
+    try {
+      await program.methods
+        .updateGlobalConfig(null, null, null, null, PublicKey.default)
+        .accounts({
+          globalState: globalStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+      expect.fail("Should have failed with invalid price oracle");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPriceOracle");
+    }
+  });
+
+  it("Rejects a pause from anyone but the authority", async () => {
+    try {
+      await program.methods
+        .setPaused(true)
+        .accounts({
+          globalState: globalStatePda,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  describe("While paused", () => {
+    before(async () => {
+      await setPaused(true);
+    });
+
+    it("Blocks new plans", async () => {
+      const pausedPlanPda = findSubscriptionPlanPda("admin-paused-plan");
+      await expectPaused(
+        program.methods
+          .initializeSubscriptionPlan(
+            "admin-paused-plan",
+            "Paused plan",
+            "Should never be created",
+            new anchor.BN(planPrice),
+            new anchor.BN(periodDuration),
+            null
+          )
+          .accounts({
+            subscriptionPlan: pausedPlanPda,
+            merchant: merchantPda,
+            authority: authority.publicKey,
+            acceptedMint: mint,
+            globalState: globalStatePda,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([authority])
+          .rpc()
+      );
+    });
+
+    it("Blocks new subscriptions", async () => {
+      await expectPaused(subscribe(lateUser));
+    });
+
+    it("Blocks payments", async () => {
+      await expectPaused(
+        program.methods
+          .processPayment(new anchor.BN(planPrice))
+          .accounts({
+            userSubscription: userSubscriptionPda,
+            subscriptionPlan: subscriptionPlanPda,
+            merchant: merchantPda,
+            paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+            user: user.publicKey,
+            userTokenAccount,
+            planTokenAccount,
+            mint,
+            treasuryTokenAccount,
+            feeStats: feeStatsPda,
+            globalState: globalStatePda,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([user])
+          .rpc()
+      );
+    });
+
+    it("Holds off dunning", async () => {
+      await expectPaused(
+        program.methods
+          .refreshSubscriptionStatus()
+          .accounts({
+            userSubscription: userSubscriptionPda,
+            subscriptionPlan: subscriptionPlanPda,
+            globalState: globalStatePda,
+          })
+          .rpc()
+      );
+    });
+
+    it("Blocks cancellations", async () => {
+      await expectPaused(
+        program.methods
+          .cancelSubscription()
+          .accounts({
+            userSubscription: userSubscriptionPda,
+            user: user.publicKey,
+            subscriptionPlan: subscriptionPlanPda,
+            merchant: merchantPda,
+            globalState: globalStatePda,
+          })
+          .signers([user])
+          .rpc()
+      );
+    });
+
+    it("Resumes once unpaused", async () => {
+      await setPaused(false);
+
+      const lateSubscription = await subscribe(lateUser);
+      const subscription = await program.account.userSubscription.fetch(lateSubscription);
+      expect(subscription.user.toString()).to.equal(lateUser.publicKey.toString());
+    });
+  });
+});
@@ -0,0 +1,177 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findAutopayDelegatePda,
+} from "./setup";
+
+describe("Autopay", () => {
+  const planPrice = 1_000;
+  const allowance = planPrice * 3;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+  let feeStatsPda: PublicKey;
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  // Creates a plan, a funded token account and a subscription with an autopay mandate on it
+  const setupAutopay = async (planId: string) => {
+    const subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Autopay plan",
+      description: "Plan paid through the autopay delegate",
+    });
+    const userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, `${planId}-sub`);
+
+    const userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey, Keypair.generate());
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+
+    await program.methods
+      .enableAutopay(new anchor.BN(allowance))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        user: user.publicKey,
+        userTokenAccount,
+        autopayDelegate: findAutopayDelegatePda(),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    return { subscriptionPlanPda, userSubscriptionPda, userTokenAccount };
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant("Autopay Merchant"));
+    ({ mint, planTokenAccount, treasuryTokenAccount, feeStats: feeStatsPda } = await setupMint(authority));
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+  });
+
+  it("Pulls a due payment through the autopay delegate", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } = await setupAutopay("autopay-pull");
+    const merchantBefore = await balance(planTokenAccount);
+    const treasuryBefore = await balance(treasuryTokenAccount);
+
+    await program.methods
+      .automatedPayment()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        payer: provider.wallet.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: feeStatsPda,
+        autopayDelegate: findAutopayDelegatePda(),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    expect(await balance(userTokenAccount)).to.equal(planPrice * 9);
+    const protocolFee = (await balance(treasuryTokenAccount)) - treasuryBefore;
+    expect((await balance(planTokenAccount)) - merchantBefore).to.equal(planPrice - protocolFee);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+    expect(subscription.autopayAllowance.toNumber()).to.equal(allowance - planPrice);
+
+    const tokenAccount = await getAccount(provider.connection, userTokenAccount);
+    expect(tokenAccount.delegate.toString()).to.equal(findAutopayDelegatePda().toString());
+    expect(Number(tokenAccount.delegatedAmount)).to.equal(allowance - planPrice);
+
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda));
+    expect(ledger.entries[0].status).to.deep.equal({ completed: {} });
+  });
+
+  it("Revokes the approval when autopay is disabled", async () => {
+    const { userSubscriptionPda, userTokenAccount } = await setupAutopay("autopay-disable");
+
+    await program.methods
+      .disableAutopay()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        userTokenAccount,
+        autopayDelegate: findAutopayDelegatePda(),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    const tokenAccount = await getAccount(provider.connection, userTokenAccount);
+    expect(tokenAccount.delegate).to.be.null;
+    expect(Number(tokenAccount.delegatedAmount)).to.equal(0);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.autoPayEnabled).to.be.false;
+    expect(subscription.autopayAllowance.toNumber()).to.equal(0);
+  });
+
+  it("Revokes the approval when the subscription is cancelled", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } = await setupAutopay("autopay-cancel");
+
+    try {
+      await program.methods
+        .cancelSubscription()
+        .accounts({
+          userSubscription: userSubscriptionPda,
+          user: user.publicKey,
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          globalState: globalStatePda,
+        })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed without the autopay token account");
+    } catch (error) {
+      expect(error.message).to.include("MissingTokenAccounts");
+    }
+
+    await program.methods
+      .cancelSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        globalState: globalStatePda,
+        userTokenAccount,
+        autopayDelegate: findAutopayDelegatePda(),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    const tokenAccount = await getAccount(provider.connection, userTokenAccount);
+    expect(tokenAccount.delegate).to.be.null;
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ cancelled: {} });
+    expect(subscription.autoPayEnabled).to.be.false;
+  });
+});
@@ -0,0 +1,190 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createAccount,
+  createWrappedNativeAccount,
+  getOrCreateAssociatedTokenAccount,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  createPlan,
+  createSubscription,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Plan changes", () => {
+  const basicPrice = 0.1 * LAMPORTS_PER_SOL;
+  const proPrice = 0.3 * LAMPORTS_PER_SOL;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let basicPlanPda: PublicKey;
+  let proPlanPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const subscriptionPda = (plan: PublicKey) =>
+    findUserSubscriptionPda(user.publicKey, plan);
+
+  const changePlan = async (from: PublicKey, to: PublicKey) => {
+    await program.methods
+      .changePlan()
+      .accounts({
+        userSubscription: subscriptionPda(from),
+        newUserSubscription: subscriptionPda(to),
+        paymentLedger: findPaymentLedgerPda(subscriptionPda(from)),
+        newPaymentLedger: findPaymentLedgerPda(subscriptionPda(to)),
+        currentPlan: from,
+        newPlan: to,
+        merchant: merchantPda,
+        user: user.publicKey,
+        mint: NATIVE_MINT,
+        userTokenAccount,
+        planTokenAccount,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(NATIVE_MINT),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    const description = "Plan used to exercise proration";
+    basicPlanPda = await createPlan(authority, "proration-basic", basicPrice, { name: "proration-basic", description });
+    proPlanPda = await createPlan(authority, "proration-pro", proPrice, { name: "proration-pro", description });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+
+    // Native plans settle in wrapped SOL
+    userTokenAccount = await createWrappedNativeAccount(provider.connection, user, user.publicKey, LAMPORTS_PER_SOL);
+    planTokenAccount = await createAccount(provider.connection, authority, NATIVE_MINT, authority.publicKey);
+    treasuryTokenAccount = (
+      await getOrCreateAssociatedTokenAccount(provider.connection, authority, NATIVE_MINT, provider.wallet.publicKey)
+    ).address;
+
+    await createSubscription(user, basicPlanPda, "proration-sub");
+
+    await program.methods
+      .processPayment(new anchor.BN(basicPrice))
+      .accounts({
+        userSubscription: subscriptionPda(basicPlanPda),
+        subscriptionPlan: basicPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(subscriptionPda(basicPlanPda)),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint: NATIVE_MINT,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(NATIVE_MINT),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  });
+
+  it("Upgrades mid-period, charging only the prorated difference", async () => {
+    const before = await provider.connection.getTokenAccountBalance(planTokenAccount);
+
+    await changePlan(basicPlanPda, proPlanPda);
+
+    const after = await provider.connection.getTokenAccountBalance(planTokenAccount);
+    const charged = Number(after.value.amount) - Number(before.value.amount);
+    expect(charged).to.be.greaterThan(0);
+    expect(charged).to.be.at.most(proPrice - basicPrice);
+
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda(proPlanPda));
+    expect(subscription.subscriptionPlan.toString()).to.equal(proPlanPda.toString());
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+    expect(subscription.creditBalance.toNumber()).to.equal(0);
+    expect(subscription.status).to.deep.equal({ active: {} });
+
+    expect(await provider.connection.getAccountInfo(subscriptionPda(basicPlanPda))).to.be.null;
+    expect(await provider.connection.getAccountInfo(findPaymentLedgerPda(subscriptionPda(basicPlanPda)))).to.be.null;
+
+    // The payment history moves with the subscription, and the charge joins it
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(subscriptionPda(proPlanPda)));
+    expect(ledger.subscription.toString()).to.equal(subscriptionPda(proPlanPda).toString());
+    expect(ledger.nextSequence.toNumber()).to.equal(2);
+    expect(ledger.entries[0].amount.toNumber()).to.equal(basicPrice);
+    expect(ledger.entries[1].netAmount.toNumber()).to.equal(charged);
+    expect(ledger.entries[1].amount.toNumber()).to.equal(charged + ledger.entries[1].protocolFee.toNumber());
+
+    const basic = await program.account.subscriptionPlan.fetch(basicPlanPda);
+    const pro = await program.account.subscriptionPlan.fetch(proPlanPda);
+    expect(basic.currentSubscribers).to.equal(0);
+    expect(pro.currentSubscribers).to.equal(1);
+  });
+
+  it("Downgrades mid-period, crediting the unused difference", async () => {
+    const before = await provider.connection.getTokenAccountBalance(planTokenAccount);
+
+    await changePlan(proPlanPda, basicPlanPda);
+
+    const after = await provider.connection.getTokenAccountBalance(planTokenAccount);
+    expect(after.value.amount).to.equal(before.value.amount);
+
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda(basicPlanPda));
+    expect(subscription.creditBalance.toNumber()).to.be.greaterThan(0);
+    expect(subscription.creditBalance.toNumber()).to.be.at.most(proPrice - basicPrice);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+
+    const basic = await program.account.subscriptionPlan.fetch(basicPlanPda);
+    const pro = await program.account.subscriptionPlan.fetch(proPlanPda);
+    expect(basic.currentSubscribers).to.equal(1);
+    expect(pro.currentSubscribers).to.equal(0);
+  });
+
+  it("Drops a coupon bound to the plan being left", async () => {
+    const code = "BASIC-ONLY";
+    const [couponPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("coupon"), authority.publicKey.toBuffer(), Buffer.from(code)],
+      program.programId
+    );
+    await program.methods
+      .createCoupon(code, { percent: {} }, new anchor.BN(5_000), { forever: {} }, null, null, basicPlanPda, PublicKey.default)
+      .accounts({
+        coupon: couponPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+    await program.methods
+      .applyCoupon()
+      .accounts({
+        userSubscription: subscriptionPda(basicPlanPda),
+        subscriptionPlan: basicPlanPda,
+        coupon: couponPda,
+        user: user.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+
+    await changePlan(basicPlanPda, proPlanPda);
+
+    // The same merchant's pro plan is not one the coupon could be redeemed on
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda(proPlanPda));
+    expect(subscription.discount).to.be.null;
+  });
+});
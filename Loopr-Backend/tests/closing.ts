@@ -0,0 +1,193 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  sleep,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findPaymentIntentPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Closing accounts", () => {
+  const planId = "closing-plan";
+  const intentId = "closing-intent";
+  const planPrice = 100;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let paymentIntentPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let authorityTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const closeSubscription = () =>
+    program.methods
+      .closeUserSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+
+  const closePlan = () =>
+    program.methods
+      .closeSubscriptionPlan()
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+  const closeIntent = () =>
+    program.methods
+      .closePaymentIntent()
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant("Closing Merchant"));
+    ({ mint, planTokenAccount: authorityTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Closing plan",
+      description: "Plan retired at the end of the suite",
+    });
+    paymentIntentPda = findPaymentIntentPda(intentId);
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "closing-sub");
+
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount: authorityTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  });
+
+  it("Refuses to close a subscription that is still open", async () => {
+    try {
+      await closeSubscription();
+      expect.fail("Should have failed with subscription still open");
+    } catch (error) {
+      expect(error.message).to.include("SubscriptionStillOpen");
+    }
+  });
+
+  it("Keeps a payment intent open until it expires", async () => {
+    const slot = await provider.connection.getSlot();
+    const now = await provider.connection.getBlockTime(slot);
+
+    await program.methods
+      .createPaymentIntent(intentId, planId, new anchor.BN(planPrice), new anchor.BN(now + 2))
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await closeIntent();
+      expect.fail("Should have failed with payment intent still open");
+    } catch (error) {
+      expect(error.message).to.include("PaymentIntentStillOpen");
+    }
+
+    await sleep(3000);
+    await closeIntent();
+    expect(await provider.connection.getAccountInfo(paymentIntentPda)).to.be.null;
+  });
+
+  it("Refuses to close a plan that is still active", async () => {
+    try {
+      await closePlan();
+      expect.fail("Should have failed with plan not retired");
+    } catch (error) {
+      expect(error.message).to.include("PlanNotRetired");
+    }
+  });
+
+  it("Closes a cancelled subscription along with its payment ledger", async () => {
+    await program.methods
+      .cancelSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+
+    const rent =
+      (await provider.connection.getBalance(userSubscriptionPda)) +
+      (await provider.connection.getBalance(findPaymentLedgerPda(userSubscriptionPda)));
+    const before = await provider.connection.getBalance(user.publicKey);
+
+    await closeSubscription();
+    expect(await provider.connection.getAccountInfo(userSubscriptionPda)).to.be.null;
+    expect(await provider.connection.getAccountInfo(findPaymentLedgerPda(userSubscriptionPda))).to.be.null;
+    // The user also paid the transaction fee
+    expect(await provider.connection.getBalance(user.publicKey)).to.be.greaterThan(before + rent - 10_000);
+  });
+
+  it("Closes a deactivated plan with no subscribers", async () => {
+    await program.methods
+      .updateSubscriptionPlan(null, null, null, null, null, null, null, null, null, null, false)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+    await closePlan();
+    expect(await provider.connection.getAccountInfo(subscriptionPlanPda)).to.be.null;
+  });
+});
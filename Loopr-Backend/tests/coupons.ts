@@ -0,0 +1,309 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createAccount,
+  createWrappedNativeAccount,
+  getOrCreateAssociatedTokenAccount,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  createPlan,
+  createSubscription,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+  findPaymentIntentPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Coupons", () => {
+  const planId = "coupon-plan";
+  const couponCode = "LAUNCH20";
+  const planPrice = 0.1 * LAMPORTS_PER_SOL;
+  const discountedPrice = planPrice * 0.8;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let otherUser: Keypair;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let couponPda: PublicKey;
+
+  const subscriptionPda = (wallet: PublicKey) =>
+    findUserSubscriptionPda(wallet, subscriptionPlanPda);
+
+  const applyCoupon = async (wallet: Keypair) => {
+    await program.methods
+      .applyCoupon()
+      .accounts({
+        userSubscription: subscriptionPda(wallet.publicKey),
+        subscriptionPlan: subscriptionPlanPda,
+        coupon: couponPda,
+        user: wallet.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([wallet])
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      name: "Coupon plan",
+      description: "Plan used to exercise coupons",
+    });
+    [couponPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("coupon"), authority.publicKey.toBuffer(), Buffer.from(couponCode)],
+      program.programId
+    );
+
+    user = Keypair.generate();
+    otherUser = Keypair.generate();
+    await airdrop(user.publicKey);
+    await airdrop(otherUser.publicKey);
+  });
+
+  it("Creates a percent-off coupon", async () => {
+    await program.methods
+      .createCoupon(
+        couponCode,
+        { percent: {} },
+        new anchor.BN(2_000), // 20% in basis points
+        { repeating: { 0: 2 } },
+        2,
+        null,
+        subscriptionPlanPda,
+        PublicKey.default // Native SOL, as on the plan
+      )
+      .accounts({
+        coupon: couponPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const coupon = await program.account.coupon.fetch(couponPda);
+    expect(Buffer.from(coupon.code).toString().replace(/\0+$/, "")).to.equal(couponCode);
+    expect(coupon.discountType).to.deep.equal({ percent: {} });
+    expect(coupon.plan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(coupon.timesRedeemed).to.equal(0);
+    expect(coupon.isActive).to.be.true;
+  });
+
+  it("Rejects a percent-off coupon above 100%", async () => {
+    const [badCouponPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("coupon"), authority.publicKey.toBuffer(), Buffer.from("TOO-MUCH")],
+      program.programId
+    );
+
+    try {
+      await program.methods
+        .createCoupon("TOO-MUCH", { percent: {} }, new anchor.BN(10_001), { once: {} }, null, null, null, PublicKey.default)
+        .accounts({
+          coupon: badCouponPda,
+          authority: authority.publicKey,
+          globalState: globalStatePda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with invalid discount");
+    } catch (error) {
+      expect(error.message).to.include("InvalidDiscount");
+    }
+  });
+
+  it("Charges the discounted price once a coupon is applied", async () => {
+    await createSubscription(user, subscriptionPlanPda, "coupon-sub");
+    await applyCoupon(user);
+
+    let subscription = await program.account.userSubscription.fetch(subscriptionPda(user.publicKey));
+    expect(subscription.discount.coupon.toString()).to.equal(couponPda.toString());
+    expect(subscription.discount.periodsRemaining).to.equal(2);
+    // The coupon's bounds travel with the discount
+    expect(subscription.discount.plan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(subscription.discount.mint.toString()).to.equal(PublicKey.default.toString());
+
+    // Native plans settle in wrapped SOL
+    const userTokenAccount = await createWrappedNativeAccount(provider.connection, user, user.publicKey, planPrice);
+    const planTokenAccount = await createAccount(provider.connection, authority, NATIVE_MINT, authority.publicKey);
+    const treasuryTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection, authority, NATIVE_MINT, provider.wallet.publicKey
+    );
+    const pay = async (amount: number) =>
+      program.methods
+        .processPayment(new anchor.BN(amount))
+        .accounts({
+          userSubscription: subscriptionPda(user.publicKey),
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          paymentLedger: findPaymentLedgerPda(subscriptionPda(user.publicKey)),
+          user: user.publicKey,
+          userTokenAccount,
+          planTokenAccount,
+          mint: NATIVE_MINT,
+          treasuryTokenAccount: treasuryTokenAccount.address,
+          feeStats: findFeeStatsPda(NATIVE_MINT),
+          globalState: globalStatePda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+
+    try {
+      await pay(planPrice);
+      expect.fail("Should have failed charging the full price");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPaymentAmount");
+    }
+
+    await pay(discountedPrice);
+
+    subscription = await program.account.userSubscription.fetch(subscriptionPda(user.publicKey));
+    expect(subscription.discount.periodsRemaining).to.equal(1);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+
+    const coupon = await program.account.coupon.fetch(couponPda);
+    expect(coupon.timesRedeemed).to.equal(1);
+  });
+
+  it("Rejects applying a second coupon", async () => {
+    try {
+      await applyCoupon(user);
+      expect.fail("Should have failed with coupon already applied");
+    } catch (error) {
+      expect(error.message).to.include("CouponAlreadyApplied");
+    }
+  });
+
+  it("Rejects coupons bound to another plan or mint", async () => {
+    const otherPlanPda = await createPlan(authority, "coupon-other-plan", planPrice, {
+      name: "Other coupon plan",
+      description: "Plan the launch coupon was not issued for",
+    });
+    const strayUser = Keypair.generate();
+    await airdrop(strayUser.publicKey);
+    await createSubscription(strayUser, otherPlanPda, "coupon-other-sub");
+
+    const otherMintCode = "OTHER-MINT";
+    const [otherMintCouponPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("coupon"), authority.publicKey.toBuffer(), Buffer.from(otherMintCode)],
+      program.programId
+    );
+    await program.methods
+      .createCoupon(otherMintCode, { fixed: {} }, new anchor.BN(1_000), { once: {} }, null, null, null, Keypair.generate().publicKey)
+      .accounts({
+        coupon: otherMintCouponPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    for (const coupon of [couponPda, otherMintCouponPda]) {
+      try {
+        await program.methods
+          .applyCoupon()
+          .accounts({
+            userSubscription: findUserSubscriptionPda(strayUser.publicKey, otherPlanPda),
+            subscriptionPlan: otherPlanPda,
+            coupon,
+            user: strayUser.publicKey,
+            globalState: globalStatePda,
+          })
+          .signers([strayUser])
+          .rpc();
+        expect.fail("Should have failed with invalid coupon");
+      } catch (error) {
+        expect(error.message).to.include("InvalidCoupon");
+      }
+    }
+  });
+
+  it("Prices a payment intent with the coupon and redeems it on payment", async () => {
+    const intentId = "coupon-intent";
+    const subscriptionId = "coupon-qr-sub";
+    // Subscriptions are keyed by wallet and plan, so the QR subscriber needs a wallet of its own
+    const qrUser = Keypair.generate();
+    await airdrop(qrUser.publicKey);
+    const paymentIntentPda = findPaymentIntentPda(intentId);
+    const userSubscriptionPda = subscriptionPda(qrUser.publicKey);
+    const expiresAt = new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+    const intentAccounts = {
+      paymentIntent: paymentIntentPda,
+      subscriptionPlan: subscriptionPlanPda,
+      coupon: couponPda,
+      authority: authority.publicKey,
+      globalState: globalStatePda,
+      systemProgram: SystemProgram.programId,
+    };
+
+    try {
+      await program.methods
+        .createPaymentIntent(intentId, planId, new anchor.BN(planPrice), expiresAt)
+        .accounts(intentAccounts)
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with the undiscounted amount");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPaymentAmount");
+    }
+
+    await program.methods
+      .createPaymentIntent(intentId, planId, new anchor.BN(discountedPrice), expiresAt)
+      .accounts(intentAccounts)
+      .signers([authority])
+      .rpc();
+
+    const intent = await program.account.paymentIntent.fetch(paymentIntentPda);
+    expect(intent.coupon.toString()).to.equal(couponPda.toString());
+
+    await program.methods
+      .subscribeAndPay(subscriptionId, 100)
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        userSubscription: userSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: qrUser.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, qrUser.publicKey),
+        coupon: couponPda,
+        authority: authority.publicKey,
+        treasury: provider.wallet.publicKey,
+        feeStats: findFeeStatsPda(NATIVE_MINT),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([qrUser])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.discount.periodsRemaining).to.equal(1);
+
+    const coupon = await program.account.coupon.fetch(couponPda);
+    expect(coupon.timesRedeemed).to.equal(2);
+  });
+
+  it("Enforces the redemption limit", async () => {
+    await createSubscription(otherUser, subscriptionPlanPda, "coupon-limit-sub");
+
+    try {
+      await applyCoupon(otherUser);
+      expect.fail("Should have failed with redemption limit reached");
+    } catch (error) {
+      expect(error.message).to.include("CouponRedemptionLimitReached");
+    }
+  });
+});
@@ -0,0 +1,384 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  sleep,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findPaymentIntentPda,
+  findTrialRecordPda,
+  findFeeStatsPda,
+  findEscrowAuthorityPda,
+  findEscrowVaultPda,
+} from "./setup";
+
+describe("Disputes and escrow", () => {
+  const planId = "escrowed-plan";
+  const planPrice = 100;
+  const disputeWindow = 5;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let arbiter: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let escrowAuthorityPda: PublicKey;
+  let escrowVaultPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+  let disputedPayment: number;
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  // Ledger entry for payment `sequence` of the subscription
+  const payment = async (sequence: number) =>
+    (await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda))).entries.find(
+      (entry) => entry.sequence.toNumber() === sequence
+    );
+
+  // Returns the sequence number the payment was recorded under
+  const pay = async () => {
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda));
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        escrowVault: escrowVaultPda,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+    return ledger.nextSequence.toNumber();
+  };
+
+  const openDispute = async (sequence: number, signer: Keypair) => {
+    await program.methods
+      .openDispute(new anchor.BN(sequence))
+      .accounts({
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        userSubscription: userSubscriptionPda,
+        user: signer.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([signer])
+      .rpc();
+  };
+
+  const releaseEscrow = async (sequence: number) => {
+    await program.methods
+      .releaseEscrow(new anchor.BN(sequence))
+      .accounts({
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        escrowAuthority: escrowAuthorityPda,
+        escrowVault: escrowVaultPda,
+        planTokenAccount,
+        mint,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+  };
+
+  const resolveDispute = async (sequence: number, signer: Keypair, refundAmount: number) => {
+    await program.methods
+      .resolveDispute(new anchor.BN(sequence), new anchor.BN(refundAmount))
+      .accounts({
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        arbiter: signer.publicKey,
+        escrowAuthority: escrowAuthorityPda,
+        escrowVault: escrowVaultPda,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc();
+  };
+
+  const setDisputeWindow = async (window: number) => {
+    await program.methods
+      .setDisputeWindow(new anchor.BN(window))
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        mint,
+        escrowAuthority: escrowAuthorityPda,
+        escrowVault: escrowVaultPda,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Escrowed plan",
+      description: "High-value plan whose payments can be disputed",
+    });
+    escrowAuthorityPda = findEscrowAuthorityPda();
+    escrowVaultPda = findEscrowVaultPda(subscriptionPlanPda);
+
+    user = Keypair.generate();
+    arbiter = Keypair.generate();
+    await airdrop(user.publicKey);
+    await airdrop(arbiter.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "escrowed-sub");
+  });
+
+  it("Rejects a negative dispute window", async () => {
+    try {
+      await setDisputeWindow(-1);
+      expect.fail("Should have failed with invalid dispute window");
+    } catch (error) {
+      expect(error.message).to.include("InvalidDisputeWindow");
+    }
+  });
+
+  it("Lets only the protocol authority appoint a plan arbiter", async () => {
+    try {
+      await program.methods
+        .setPlanArbiter(arbiter.publicKey)
+        .accounts({
+          subscriptionPlan: subscriptionPlanPda,
+          globalState: globalStatePda,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+
+    await program.methods
+      .setPlanArbiter(arbiter.publicKey)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.arbiter.toString()).to.equal(arbiter.publicKey.toString());
+  });
+
+  it("Holds payments in escrow for the dispute window", async () => {
+    await setDisputeWindow(disputeWindow);
+
+    disputedPayment = await pay();
+
+    expect(await balance(escrowVaultPda)).to.equal(planPrice);
+    expect(await balance(planTokenAccount)).to.equal(0);
+
+    const record = await payment(disputedPayment);
+    expect(record.escrowedAmount.toNumber()).to.equal(planPrice);
+    expect(record.releaseAt).to.not.be.null;
+    expect(record.netAmount.toNumber()).to.equal(0);
+  });
+
+  it("Keeps escrow locked until the window closes", async () => {
+    try {
+      await releaseEscrow(disputedPayment);
+      expect.fail("Should have failed with escrow locked");
+    } catch (error) {
+      expect(error.message).to.include("EscrowLocked");
+    }
+  });
+
+  it("Does not let the merchant refund an escrowed payment directly", async () => {
+    try {
+      await program.methods
+        .refundPayment(new anchor.BN(disputedPayment), new anchor.BN(10))
+        .accounts({
+          paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+          userSubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          authority: authority.publicKey,
+          payoutWallet: authority.publicKey,
+          payoutTokenAccount: planTokenAccount,
+          userTokenAccount,
+          mint,
+          globalState: globalStatePda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with payment in escrow");
+    } catch (error) {
+      expect(error.message).to.include("PaymentInEscrow");
+    }
+  });
+
+  it("Lets only the payer open a dispute", async () => {
+    try {
+      await openDispute(disputedPayment, authority);
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+
+    await openDispute(disputedPayment, user);
+
+    const record = await payment(disputedPayment);
+    expect(record.status).to.deep.equal({ disputed: {} });
+  });
+
+  it("Holds a disputed payment past its window", async () => {
+    await sleep((disputeWindow + 1) * 1_000);
+
+    try {
+      await releaseEscrow(disputedPayment);
+      expect.fail("Should have failed with payment disputed");
+    } catch (error) {
+      expect(error.message).to.include("PaymentDisputed");
+    }
+  });
+
+  it("Lets only the arbiter resolve a dispute", async () => {
+    try {
+      await resolveDispute(disputedPayment, authority, 0);
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Splits a resolution between subscriber and merchant", async () => {
+    const userBefore = await balance(userTokenAccount);
+
+    await resolveDispute(disputedPayment, arbiter, 30);
+
+    expect(await balance(userTokenAccount)).to.equal(userBefore + 30);
+    expect(await balance(planTokenAccount)).to.equal(planPrice - 30);
+    expect(await balance(escrowVaultPda)).to.equal(0);
+
+    const record = await payment(disputedPayment);
+    expect(record.status).to.deep.equal({ partiallyRefunded: {} });
+    expect(record.refundedAmount.toNumber()).to.equal(30);
+    expect(record.netAmount.toNumber()).to.equal(planPrice - 30);
+    expect(record.releaseAt).to.be.null;
+  });
+
+  it("Closes disputes once the window has passed", async () => {
+    const sequence = await pay();
+    await sleep((disputeWindow + 1) * 1_000);
+
+    try {
+      await openDispute(sequence, user);
+      expect.fail("Should have failed with dispute window closed");
+    } catch (error) {
+      expect(error.message).to.include("DisputeWindowClosed");
+    }
+
+    // Anyone may release an undisputed payment once its window has passed
+    const merchantBefore = await balance(planTokenAccount);
+    await releaseEscrow(sequence);
+
+    expect(await balance(planTokenAccount)).to.equal(merchantBefore + planPrice);
+    const record = await payment(sequence);
+    expect(record.status).to.deep.equal({ completed: {} });
+    expect(record.netAmount.toNumber()).to.equal(planPrice);
+    expect(record.escrowedAmount.toNumber()).to.equal(0);
+  });
+
+  it("Holds QR checkout payments in escrow", async () => {
+    const intentId = "escrowed-qr-intent";
+    const qrUser = Keypair.generate();
+    await airdrop(qrUser.publicKey);
+    const qrTokenAccount = await createAccount(provider.connection, qrUser, mint, qrUser.publicKey);
+    await mintTo(provider.connection, authority, mint, qrTokenAccount, authority, planPrice);
+    const qrSubscriptionPda = findUserSubscriptionPda(qrUser.publicKey, subscriptionPlanPda);
+
+    await program.methods
+      .createPaymentIntent(intentId, planId, new anchor.BN(planPrice), new anchor.BN(Math.floor(Date.now() / 1000) + 3600))
+      .accounts({
+        paymentIntent: findPaymentIntentPda(intentId),
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const escrowBefore = await balance(escrowVaultPda);
+    const merchantBefore = await balance(planTokenAccount);
+
+    await program.methods
+      .subscribeAndPay("escrowed-qr-sub", 100)
+      .accounts({
+        paymentIntent: findPaymentIntentPda(intentId),
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        userSubscription: qrSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(qrSubscriptionPda),
+        user: qrUser.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, qrUser.publicKey),
+        authority: authority.publicKey,
+        treasury: provider.wallet.publicKey,
+        feeStats: findFeeStatsPda(mint),
+        mint,
+        userTokenAccount: qrTokenAccount,
+        planTokenAccount,
+        treasuryTokenAccount,
+        escrowVault: escrowVaultPda,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([qrUser])
+      .rpc();
+
+    expect(await balance(escrowVaultPda)).to.equal(escrowBefore + planPrice);
+    expect(await balance(planTokenAccount)).to.equal(merchantBefore);
+
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(qrSubscriptionPda));
+    expect(ledger.entries[0].escrowedAmount.toNumber()).to.equal(planPrice);
+    expect(ledger.entries[0].releaseAt).to.not.be.null;
+
+    const subscription = await program.account.userSubscription.fetch(qrSubscriptionPda);
+    expect(subscription.escrowedPayments).to.equal(1);
+  });
+});
@@ -0,0 +1,288 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  sleep,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findSubscriptionPlanPda,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findAutopayDelegatePda,
+} from "./setup";
+
+describe("Dunning", () => {
+  const decimals = 6;
+  const planPrice = 5_000_000; // 5 tokens
+  const gracePeriod = 24 * 60 * 60;
+  // Short enough that a paid subscription falls due again within a test
+  const shortPeriod = 2;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let keeper: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let planTokenAccount: PublicKey;
+  let keeperTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+  let feeStatsPda: PublicKey;
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  // Creates a plan with the given retry budget and an autopay subscription to
+  // it, unfunded unless `funding` is given
+  const setupSubscription = async (planId: string, maxRetryAttempts: number, funding = 0, period?: number) => {
+    const subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Dunning plan",
+      description: "Plan used to exercise failed collections",
+      period,
+    });
+
+    await program.methods
+      .updateSubscriptionPlan(null, null, null, null, null, new anchor.BN(gracePeriod), maxRetryAttempts, null, null, null, null)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+    const userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, `${planId}-sub`);
+
+    // An empty token account means the mandate exists but there is nothing to pull
+    const userTokenAccount = await createAccount(
+      provider.connection, user, mint, user.publicKey, Keypair.generate(), undefined, TOKEN_PROGRAM_ID
+    );
+    if (funding > 0) {
+      await mintTo(provider.connection, authority, mint, userTokenAccount, authority, funding);
+    }
+
+    await program.methods
+      .enableAutopay(new anchor.BN(planPrice * 12))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        user: user.publicKey,
+        userTokenAccount,
+        autopayDelegate: findAutopayDelegatePda(),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    return { subscriptionPlanPda, userSubscriptionPda, userTokenAccount };
+  };
+
+  const collect = async (
+    subscriptionPlanPda: PublicKey,
+    userSubscriptionPda: PublicKey,
+    userTokenAccount: PublicKey
+  ) => {
+    await program.methods
+      .collectDuePayment()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        keeper: keeper.publicKey,
+        keeperTokenAccount,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: feeStatsPda,
+        autopayDelegate: findAutopayDelegatePda(),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([keeper])
+      .rpc();
+  };
+
+  // A subscription funded for exactly its first period, collected and then
+  // left to fall due again with nothing left to pull
+  const setupLapsedSubscription = async (planId: string, maxRetryAttempts: number) => {
+    const accounts = await setupSubscription(planId, maxRetryAttempts, planPrice, shortPeriod);
+    await collect(accounts.subscriptionPlanPda, accounts.userSubscriptionPda, accounts.userTokenAccount);
+    await sleep((shortPeriod + 1) * 1000);
+    return accounts;
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount, feeStats: feeStatsPda } = await setupMint(authority, decimals));
+
+    user = Keypair.generate();
+    keeper = Keypair.generate();
+    await airdrop(user.publicKey);
+    await airdrop(keeper.publicKey);
+    keeperTokenAccount = await createAccount(provider.connection, keeper, mint, keeper.publicKey);
+  });
+
+  it("Pays the keeper a bounty for a funded collection", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } =
+      await setupSubscription("dunning-funded", 3, planPrice * 2);
+    const { keeperBountyBps, feeBps } = await program.account.globalState.fetch(globalStatePda);
+    const bounty = Math.floor((planPrice * keeperBountyBps) / 10_000);
+    const protocolFee = Math.floor((planPrice * feeBps) / 10_000);
+    const keeperBefore = await balance(keeperTokenAccount);
+    const merchantBefore = await balance(planTokenAccount);
+
+    await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+
+    expect(await balance(userTokenAccount)).to.equal(planPrice);
+    expect((await balance(keeperTokenAccount)) - keeperBefore).to.equal(bounty);
+    expect((await balance(planTokenAccount)) - merchantBefore).to.equal(planPrice - bounty - protocolFee);
+
+    // The pull is drawn from both the mandate and the token approval
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ active: {} });
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+    expect(subscription.autopayAllowance.toNumber()).to.equal(planPrice * 11);
+    expect(Number((await getAccount(provider.connection, userTokenAccount)).delegatedAmount)).to.equal(planPrice * 11);
+
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda));
+    expect(ledger.entries[0].status).to.deep.equal({ completed: {} });
+    expect(ledger.entries[0].protocolFee.toNumber()).to.equal(protocolFee);
+  });
+
+  it("Counts a subscriber on the plan only once the first collection succeeds", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } =
+      await setupSubscription("dunning-first", 3, planPrice);
+
+    let subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ incomplete: {} });
+    let plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.currentSubscribers).to.equal(0);
+
+    await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+
+    subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ active: {} });
+    plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.currentSubscribers).to.equal(1);
+  });
+
+  it("Expires a subscription whose first payment never goes through", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } = await setupSubscription("dunning-unpaid", 1);
+
+    await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+
+    // It never started, so running out of retries ends it rather than suspending it
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ expired: {} });
+    expect(subscription.failedPaymentAttempts).to.equal(1);
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.currentSubscribers).to.equal(0);
+  });
+
+  it("Records a failed collection and moves the subscription to past due", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } = await setupLapsedSubscription("dunning-retry", 3);
+
+    await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+
+    // Nothing was paid, so the attempt stays out of the payment ledger
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda));
+    expect(ledger.nextSequence.toNumber()).to.equal(1);
+    expect(ledger.entries).to.have.length(1);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ pastDue: {} });
+    expect(subscription.failedPaymentAttempts).to.equal(1);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+
+    // Retries are spaced across the grace period
+    await sleep(1500);
+    try {
+      await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+      expect.fail("Should have failed before the retry interval elapsed");
+    } catch (error) {
+      expect(error.message).to.include("PaymentNotDue");
+    }
+  });
+
+  it("Recovers a past-due subscription with a manual payment", async () => {
+    const subscriptionPlanPda = findSubscriptionPlanPda("dunning-retry");
+    const userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+    const fundedAccount = await createAccount(provider.connection, user, mint, user.publicKey, Keypair.generate());
+    await mintTo(provider.connection, authority, mint, fundedAccount, authority, planPrice);
+
+    await sleep(1500);
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount: fundedAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: feeStatsPda,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ active: {} });
+    expect(subscription.failedPaymentAttempts).to.equal(0);
+    expect(subscription.lastFailedAttempt).to.be.null;
+  });
+
+  it("Suspends the subscription once retries are exhausted", async () => {
+    const { subscriptionPlanPda, userSubscriptionPda, userTokenAccount } = await setupLapsedSubscription("dunning-suspend", 1);
+
+    await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ suspended: {} });
+    expect(subscription.failedPaymentAttempts).to.equal(1);
+
+    await sleep(1500);
+    try {
+      await collect(subscriptionPlanPda, userSubscriptionPda, userTokenAccount);
+      expect.fail("Should not collect from a suspended subscription");
+    } catch (error) {
+      expect(error.message).to.include("SubscriptionNotActive");
+    }
+  });
+
+  it("Rejects a negative grace period", async () => {
+    const subscriptionPlanPda = findSubscriptionPlanPda("dunning-retry");
+
+    try {
+      await program.methods
+        .updateSubscriptionPlan(null, null, null, null, null, new anchor.BN(-1), null, null, null, null, null)
+        .accounts({
+          subscriptionPlan: subscriptionPlanPda,
+          authority: authority.publicKey,
+          globalState: globalStatePda,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with invalid grace period");
+    } catch (error) {
+      expect(error.message).to.include("InvalidGracePeriod");
+    }
+  });
+});
@@ -0,0 +1,250 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  periodDuration,
+  airdrop,
+  sleep,
+  initializeGlobalState,
+  setupMint,
+  findGlobalStatePda,
+  findMerchantPda,
+  findSubscriptionPlanPda,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+  findPaymentIntentPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Events", () => {
+  const eventParser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+
+  const planId = "events-plan";
+  const intentId = "events-intent";
+  const planPrice = 100;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let paymentLedgerPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  // Decode the events a confirmed transaction emitted, in order
+  const eventsOf = async (signature: string) => {
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    return [...eventParser.parseLogs(tx.meta.logMessages)];
+  };
+
+  const onlyEvent = async (signature: string, name: string) => {
+    const events = await eventsOf(signature);
+    expect(events.map((event) => event.name)).to.deep.equal([name]);
+    return events[0].data as any;
+  };
+
+  const pay = async () => {
+    // Identical transactions sharing a blockhash would be dropped as duplicates
+    await sleep(500);
+    return program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: paymentLedgerPda,
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+  };
+
+  before(async () => {
+    authority = Keypair.generate();
+    user = Keypair.generate();
+    await airdrop(authority.publicKey);
+    await airdrop(user.publicKey);
+    await initializeGlobalState();
+
+    globalStatePda = findGlobalStatePda();
+    merchantPda = findMerchantPda(authority.publicKey);
+    subscriptionPlanPda = findSubscriptionPlanPda(planId);
+    userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+    paymentLedgerPda = findPaymentLedgerPda(userSubscriptionPda);
+
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+  });
+
+  it("Emits MerchantRegistered", async () => {
+    const signature = await program.methods
+      .registerMerchant("Events Merchant", "", "")
+      .accounts({
+        merchant: merchantPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    const event = await onlyEvent(signature, "MerchantRegistered");
+    expect(event.merchant.toString()).to.equal(merchantPda.toString());
+    expect(event.authority.toString()).to.equal(authority.publicKey.toString());
+    expect(event.name).to.equal("Events Merchant");
+  });
+
+  it("Emits PlanCreated with the plan's mint and price", async () => {
+    const signature = await program.methods
+      .initializeSubscriptionPlan(
+        planId,
+        "Events plan",
+        "Plan whose every change is emitted",
+        new anchor.BN(planPrice),
+        new anchor.BN(periodDuration),
+        null
+      )
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        merchant: merchantPda,
+        acceptedMint: mint,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    const event = await onlyEvent(signature, "PlanCreated");
+    expect(event.plan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(event.planId).to.equal(planId);
+    expect(event.merchant.toString()).to.equal(merchantPda.toString());
+    expect(event.acceptedMint.toString()).to.equal(mint.toString());
+    expect(event.pricePerPeriod.toNumber()).to.equal(planPrice);
+    expect(event.periodDuration.toNumber()).to.equal(periodDuration);
+  });
+
+  it("Emits SubscriptionCreated", async () => {
+    const signature = await program.methods
+      .createSubscription("events-sub")
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        paymentLedger: paymentLedgerPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: user.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, user.publicKey),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    const event = await onlyEvent(signature, "SubscriptionCreated");
+    expect(event.subscription.toString()).to.equal(userSubscriptionPda.toString());
+    expect(event.subscriptionId).to.equal("events-sub");
+    expect(event.user.toString()).to.equal(user.publicKey.toString());
+    expect(event.plan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(event.status).to.deep.equal({ incomplete: {} });
+  });
+
+  it("Emits PaymentCollected with the payment's ledger sequence", async () => {
+    const first = await onlyEvent(await pay(), "PaymentCollected");
+    const second = await onlyEvent(await pay(), "PaymentCollected");
+
+    expect(first.sequence.toNumber()).to.equal(0);
+    expect(second.sequence.toNumber()).to.equal(1);
+    expect(second.subscription.toString()).to.equal(userSubscriptionPda.toString());
+    expect(second.mint.toString()).to.equal(mint.toString());
+    expect(second.amount.toNumber()).to.equal(planPrice);
+    expect(second.netAmount.toNumber()).to.equal(planPrice);
+    expect(second.paymentMethod).to.deep.equal({ manual: {} });
+    expect(second.escrowed).to.be.false;
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(second.nextPaymentDue.toNumber()).to.equal(subscription.nextPaymentDue.toNumber());
+  });
+
+  it("Emits PlanUpdated with the new price version", async () => {
+    const signature = await program.methods
+      .updateSubscriptionPlan(null, null, new anchor.BN(planPrice * 2), null, null, null, null, null, null, null, null)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    const event = await onlyEvent(signature, "PlanUpdated");
+    expect(event.plan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(event.pricePerPeriod.toNumber()).to.equal(planPrice * 2);
+    expect(event.priceVersion).to.equal(2);
+    expect(event.isActive).to.be.true;
+  });
+
+  it("Emits IntentCreated", async () => {
+    const slot = await provider.connection.getSlot();
+    const expiresAt = (await provider.connection.getBlockTime(slot)) + 3600;
+    const paymentIntentPda = findPaymentIntentPda(intentId);
+
+    const signature = await program.methods
+      .createPaymentIntent(intentId, planId, new anchor.BN(planPrice * 2), new anchor.BN(expiresAt))
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc({ commitment: "confirmed" });
+
+    const event = await onlyEvent(signature, "IntentCreated");
+    expect(event.intent.toString()).to.equal(paymentIntentPda.toString());
+    expect(event.intentId).to.equal(intentId);
+    expect(event.plan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(event.amount.toNumber()).to.equal(planPrice * 2);
+    expect(event.expiresAt.toNumber()).to.equal(expiresAt);
+  });
+
+  it("Emits SubscriptionCancelled", async () => {
+    const signature = await program.methods
+      .cancelSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+
+    const event = await onlyEvent(signature, "SubscriptionCancelled");
+    expect(event.subscription.toString()).to.equal(userSubscriptionPda.toString());
+    expect(event.user.toString()).to.equal(user.publicKey.toString());
+    expect(event.mint.toString()).to.equal(mint.toString());
+    expect(event.refundedAmount.toNumber()).to.equal(0);
+  });
+});
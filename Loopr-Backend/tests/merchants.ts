@@ -0,0 +1,312 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  periodDuration,
+  airdrop,
+  initializeGlobalState,
+  setupMint,
+  findGlobalStatePda,
+  findMerchantPda,
+  findSubscriptionPlanPda,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Merchants", () => {
+  const planId = "merchant-plan";
+  const planPrice = 100;
+
+  let authority: Keypair;
+  let payoutWallet: Keypair;
+  let user: Keypair;
+  let lateUser: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let authorityTokenAccount: PublicKey;
+  let payoutTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const subscriptionPda = (subscriber: PublicKey) =>
+    findUserSubscriptionPda(subscriber, subscriptionPlanPda);
+
+  const subscribe = (subscriber: Keypair) =>
+    program.methods
+      .createSubscription("merchant-sub")
+      .accounts({
+        userSubscription: subscriptionPda(subscriber.publicKey),
+        paymentLedger: findPaymentLedgerPda(subscriptionPda(subscriber.publicKey)),
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: subscriber.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, subscriber.publicKey),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([subscriber])
+      .rpc();
+
+  const pay = async (planTokenAccount: PublicKey) =>
+    program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+  const setMerchantStatus = (status: { active: {} } | { suspended: {} }) =>
+    program.methods
+      .setMerchantStatus(status)
+      .accounts({
+        merchant: merchantPda,
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+
+  const balance = async (account: PublicKey) =>
+    Number((await getAccount(provider.connection, account)).amount);
+
+  before(async () => {
+    authority = Keypair.generate();
+    payoutWallet = Keypair.generate();
+    user = Keypair.generate();
+    lateUser = Keypair.generate();
+    await airdrop(authority.publicKey);
+    await airdrop(user.publicKey);
+    await airdrop(lateUser.publicKey);
+
+    globalStatePda = findGlobalStatePda();
+    merchantPda = findMerchantPda(authority.publicKey);
+    subscriptionPlanPda = findSubscriptionPlanPda(planId);
+    userSubscriptionPda = subscriptionPda(user.publicKey);
+
+    await initializeGlobalState();
+
+    ({ mint, planTokenAccount: authorityTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    payoutTokenAccount = await createAccount(provider.connection, authority, mint, payoutWallet.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+  });
+
+  it("Refuses plans from wallets that are not registered merchants", async () => {
+    try {
+      await program.methods
+        .initializeSubscriptionPlan(
+          planId,
+          "Merchant plan",
+          "Plan sold under a merchant profile",
+          new anchor.BN(planPrice),
+          new anchor.BN(periodDuration),
+          null
+        )
+        .accounts({
+          subscriptionPlan: subscriptionPlanPda,
+          authority: authority.publicKey,
+          merchant: merchantPda,
+          acceptedMint: mint,
+          globalState: globalStatePda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed without a merchant account");
+    } catch (error) {
+      expect(error.message).to.include("AccountNotInitialized");
+    }
+  });
+
+  it("Registers a merchant profile", async () => {
+    await program.methods
+      .registerMerchant("Acme Streaming", "https://acme.example/logo.png", "help@acme.example")
+      .accounts({
+        merchant: merchantPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(merchant.authority.toString()).to.equal(authority.publicKey.toString());
+    expect(Buffer.from(merchant.name).toString().replace(/\0+$/, "")).to.equal("Acme Streaming");
+    expect(merchant.status).to.deep.equal({ active: {} });
+    expect(merchant.payoutWallets).to.be.empty;
+    expect(merchant.totalPlans.toNumber()).to.equal(0);
+  });
+
+  it("Rejects a profile field that is too long", async () => {
+    try {
+      await program.methods
+        .updateMerchant(null, null, "x".repeat(65))
+        .accounts({
+          merchant: merchantPda,
+          authority: authority.publicKey,
+          globalState: globalStatePda,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with support contact too long");
+    } catch (error) {
+      expect(error.message).to.include("SupportContactTooLong");
+    }
+  });
+
+  it("Updates only the profile fields given", async () => {
+    await program.methods
+      .updateMerchant(null, "https://acme.example/new-logo.png", null)
+      .accounts({
+        merchant: merchantPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(Buffer.from(merchant.name).toString().replace(/\0+$/, "")).to.equal("Acme Streaming");
+    expect(Buffer.from(merchant.logoUri).toString().replace(/\0+$/, "")).to.equal(
+      "https://acme.example/new-logo.png"
+    );
+    expect(Buffer.from(merchant.supportContact).toString().replace(/\0+$/, "")).to.equal("help@acme.example");
+  });
+
+  it("Counts the merchant's plans and subscribers", async () => {
+    await program.methods
+      .initializeSubscriptionPlan(
+        planId,
+        "Merchant plan",
+        "Plan sold under a merchant profile",
+        new anchor.BN(planPrice),
+        new anchor.BN(periodDuration),
+        null
+      )
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        merchant: merchantPda,
+        acceptedMint: mint,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+    await subscribe(user);
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.merchant.toString()).to.equal(merchantPda.toString());
+
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(merchant.totalPlans.toNumber()).to.equal(1);
+    // The subscriber only counts once their first payment goes through
+    expect(merchant.currentSubscribers.toNumber()).to.equal(0);
+  });
+
+  it("Pays the authority until a payout wallet is configured", async () => {
+    await pay(authorityTokenAccount);
+    expect(await balance(authorityTokenAccount)).to.equal(planPrice);
+
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(merchant.totalRevenue.toNumber()).to.equal(planPrice);
+    expect(merchant.currentSubscribers.toNumber()).to.equal(1);
+  });
+
+  it("Routes payments to the mint's payout wallet", async () => {
+    await program.methods
+      .setPayoutWallet(mint, payoutWallet.publicKey)
+      .accounts({
+        merchant: merchantPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await pay(authorityTokenAccount);
+      expect.fail("Should have failed with invalid payout wallet");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPayoutWallet");
+    }
+
+    await pay(payoutTokenAccount);
+    expect(await balance(payoutTokenAccount)).to.equal(planPrice);
+
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(merchant.payoutWallets).to.have.length(1);
+    expect(merchant.totalRevenue.toNumber()).to.equal(planPrice * 2);
+  });
+
+  it("Sends payments back to the authority once the payout wallet is cleared", async () => {
+    await program.methods
+      .setPayoutWallet(mint, null)
+      .accounts({
+        merchant: merchantPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(merchant.payoutWallets).to.be.empty;
+  });
+
+  it("Only lets the protocol authority suspend a merchant", async () => {
+    try {
+      await program.methods
+        .setMerchantStatus({ suspended: {} })
+        .accounts({
+          merchant: merchantPda,
+          globalState: globalStatePda,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Stops a suspended merchant taking new subscribers", async () => {
+    await setMerchantStatus({ suspended: {} });
+
+    try {
+      await subscribe(lateUser);
+      expect.fail("Should have failed with merchant not active");
+    } catch (error) {
+      expect(error.message).to.include("MerchantNotActive");
+    }
+
+    await setMerchantStatus({ active: {} });
+    await subscribe(lateUser);
+
+    // Subscribed but not yet paid, so the late subscriber takes no place
+    const merchant = await program.account.merchant.fetch(merchantPda);
+    expect(merchant.currentSubscribers.toNumber()).to.equal(1);
+  });
+});
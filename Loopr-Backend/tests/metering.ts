@@ -0,0 +1,210 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Metered billing", () => {
+  const planId = "metered-plan";
+  const basePrice = 100;
+  const unitPrice = 2;
+  const includedUnits = 10;
+
+  let authority: Keypair;
+  let reporter: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const usageRecordPda = (reportId: number, subscription = userSubscriptionPda) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("usage_record"), subscription.toBuffer(), new anchor.BN(reportId).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  const reportUsage = async (
+    signer: Keypair,
+    reportId: number,
+    units: number,
+    subscription = userSubscriptionPda,
+    plan = subscriptionPlanPda
+  ) => {
+    await program.methods
+      .reportUsage(new anchor.BN(reportId), new anchor.BN(units))
+      .accounts({
+        userSubscription: subscription,
+        subscriptionPlan: plan,
+        usageRecord: usageRecordPda(reportId, subscription),
+        reporter: signer.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+  };
+
+  const setMetering = async (metering: { unitPrice: number; includedUnits: number; reporter: PublicKey }) => {
+    await program.methods
+      .setMetering({
+        unitPrice: new anchor.BN(metering.unitPrice),
+        includedUnits: new anchor.BN(metering.includedUnits),
+        reporter: metering.reporter,
+      })
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  const pay = async (amount: number, subscription = userSubscriptionPda, plan = subscriptionPlanPda) => {
+    await program.methods
+      .processPayment(new anchor.BN(amount))
+      .accounts({
+        userSubscription: subscription,
+        subscriptionPlan: plan,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(subscription),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, basePrice, {
+      mint,
+      name: "Metered API plan",
+      description: "Base fee plus per-call pricing",
+    });
+
+    reporter = Keypair.generate();
+    user = Keypair.generate();
+    await airdrop(reporter.publicKey);
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, 1_000);
+  });
+
+  it("Rejects usage on a plan that is not metered", async () => {
+    const unmeteredPlanPda = await createPlan(authority, "unmetered-plan", basePrice, {
+      mint,
+      name: "Unmetered plan",
+      description: "Flat plan with no usage pricing",
+    });
+    const unmeteredSubscriptionPda = await createSubscription(user, unmeteredPlanPda, "unmetered-sub");
+    // Usage is only taken from a subscription that has started
+    await pay(basePrice, unmeteredSubscriptionPda, unmeteredPlanPda);
+
+    try {
+      await reportUsage(authority, 0, 5, unmeteredSubscriptionPda, unmeteredPlanPda);
+      expect.fail("Should have failed with plan not metered");
+    } catch (error) {
+      expect(error.message).to.include("PlanNotMetered");
+    }
+  });
+
+  it("Configures metering with a delegated reporter", async () => {
+    await setMetering({ unitPrice, includedUnits, reporter: reporter.publicKey });
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.metering.unitPrice.toNumber()).to.equal(unitPrice);
+    expect(plan.metering.reporter.toString()).to.equal(reporter.publicKey.toString());
+  });
+
+  it("Keeps the usage rates once the plan has subscribers", async () => {
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "metered-sub");
+
+    // The first period is paid up front at the flat price
+    await pay(basePrice);
+
+    try {
+      await setMetering({ unitPrice: unitPrice * 2, includedUnits, reporter: reporter.publicKey });
+      expect.fail("Should have failed with plan has subscribers");
+    } catch (error) {
+      expect(error.message).to.include("PlanHasSubscribers");
+    }
+
+    // Only the reporter may still change, so restating the rates is accepted
+    await setMetering({ unitPrice, includedUnits, reporter: reporter.publicKey });
+  });
+
+  it("Accepts usage from the plan authority and the delegated reporter", async () => {
+    await reportUsage(authority, 1, 5);
+    await reportUsage(reporter, 2, 20);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.periodUsage.toNumber()).to.equal(25);
+
+    const record = await program.account.usageRecord.fetch(usageRecordPda(2));
+    expect(record.reporter.toString()).to.equal(reporter.publicKey.toString());
+    expect(record.units.toNumber()).to.equal(20);
+  });
+
+  it("Counts each report id only once", async () => {
+    try {
+      await reportUsage(reporter, 2, 20);
+      expect.fail("Should have failed reporting the same id twice");
+    } catch (error) {
+      expect(error.message).to.include("already in use");
+    }
+  });
+
+  it("Rejects usage from anyone else", async () => {
+    const stranger = Keypair.generate();
+    await airdrop(stranger.publicKey);
+
+    try {
+      await reportUsage(stranger, 3, 1);
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Bills usage beyond the included units with the next payment", async () => {
+    // 25 units reported, 10 included: 100 + 15 * 2 = 130
+    const expected = basePrice + (25 - includedUnits) * unitPrice;
+
+    try {
+      await pay(basePrice);
+      expect.fail("Should have failed charging only the flat price");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPaymentAmount");
+    }
+
+    await pay(expected);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.periodUsage.toNumber()).to.equal(0);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(2);
+  });
+});
@@ -0,0 +1,148 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import {
+  NATIVE_MINT,
+  TOKEN_PROGRAM_ID,
+  createAccount,
+  createWrappedNativeAccount,
+  getOrCreateAssociatedTokenAccount,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  sleep,
+  setupMerchant,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Pause and resume", () => {
+  const planId = "pausable-plan";
+  const planPrice = 0.05 * LAMPORTS_PER_SOL;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+
+  const subscriptionAccounts = () => ({
+    userSubscription: userSubscriptionPda,
+    subscriptionPlan: subscriptionPlanPda,
+    user: user.publicKey,
+    globalState: globalStatePda,
+  });
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      name: "Pausable plan",
+      description: "Plan allowing a single pause per year",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+
+    // One pause per year so the limit is reachable in a test
+    await program.methods
+      .updateSubscriptionPlan(null, null, null, null, null, null, null, null, 1, null, null)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "pausable-sub");
+
+    // Settle the first period so the subscription is in good standing; native plans settle in wrapped SOL
+    const userTokenAccount = await createWrappedNativeAccount(provider.connection, user, user.publicKey, planPrice);
+    const planTokenAccount = await createAccount(provider.connection, authority, NATIVE_MINT, authority.publicKey);
+    const treasuryTokenAccount = await getOrCreateAssociatedTokenAccount(
+      provider.connection, authority, NATIVE_MINT, provider.wallet.publicKey
+    );
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        merchant: merchantPda,
+        ...subscriptionAccounts(),
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        userTokenAccount,
+        planTokenAccount,
+        mint: NATIVE_MINT,
+        treasuryTokenAccount: treasuryTokenAccount.address,
+        feeStats: findFeeStatsPda(NATIVE_MINT),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  });
+
+  it("Applies the plan's pause defaults", async () => {
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.maxPauseDuration.toNumber()).to.equal(30 * 24 * 60 * 60);
+    expect(plan.maxPausesPerYear).to.equal(1);
+  });
+
+  it("Pauses a subscription", async () => {
+    await program.methods
+      .pauseSubscription()
+      .accounts(subscriptionAccounts())
+      .signers([user])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ paused: {} });
+    expect(subscription.pausedAt).to.not.be.null;
+    expect(subscription.pausesInWindow).to.equal(1);
+  });
+
+  it("Shifts the next payment by the paused duration on resume", async () => {
+    const before = await program.account.userSubscription.fetch(userSubscriptionPda);
+    await sleep(2000);
+
+    await program.methods
+      .resumeSubscription()
+      .accounts(subscriptionAccounts())
+      .signers([user])
+      .rpc();
+
+    const after = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(after.status).to.deep.equal({ active: {} });
+    expect(after.pausedAt).to.be.null;
+    expect(after.nextPaymentDue.toNumber()).to.be.greaterThan(before.nextPaymentDue.toNumber());
+  });
+
+  it("Rejects resuming a subscription that is not paused", async () => {
+    try {
+      await program.methods
+        .resumeSubscription()
+        .accounts(subscriptionAccounts())
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with subscription not paused");
+    } catch (error) {
+      expect(error.message).to.include("SubscriptionNotPaused");
+    }
+  });
+
+  it("Enforces the plan's pauses per year", async () => {
+    try {
+      await program.methods
+        .pauseSubscription()
+        .accounts(subscriptionAccounts())
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with pause limit reached");
+    } catch (error) {
+      expect(error.message).to.include("PauseLimitReached");
+    }
+  });
+});
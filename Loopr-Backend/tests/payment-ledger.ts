@@ -0,0 +1,169 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, mintTo } from "@solana/spl-token";
+import { createHash } from "crypto";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  sleep,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Payment ledger", () => {
+  const planId = "ledger-plan";
+  const planPrice = 10;
+  // Mirrors PaymentLedger::CAPACITY
+  const capacity = 16;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let paymentLedgerPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const pay = async () => {
+    // Identical transactions sharing a blockhash would be dropped as duplicates
+    await sleep(500);
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: paymentLedgerPda,
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  // Chain entries the way PaymentLedger::append does
+  const chain = (hash: Buffer, entries: any[]) =>
+    entries.reduce(
+      (acc, entry) =>
+        createHash("sha256").update(acc).update(program.coder.types.encode("PaymentEntry", entry)).digest(),
+      hash
+    );
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant("Ledger Merchant"));
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Ledger plan",
+      description: "Plan paid often enough to fill a ledger",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+    paymentLedgerPda = findPaymentLedgerPda(userSubscriptionPda);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * (capacity + 1));
+  });
+
+  it("Creates an empty ledger with the subscription", async () => {
+    await program.methods
+      .createSubscription("ledger-sub")
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        paymentLedger: paymentLedgerPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: user.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, user.publicKey),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const ledger = await program.account.paymentLedger.fetch(paymentLedgerPda);
+    expect(ledger.subscription.toString()).to.equal(userSubscriptionPda.toString());
+    expect(ledger.nextSequence.toNumber()).to.equal(0);
+    expect(ledger.entries).to.be.empty;
+    expect(Buffer.from(ledger.historyHash).equals(Buffer.alloc(32))).to.be.true;
+  });
+
+  it("Numbers payments consecutively", async () => {
+    await pay();
+    await pay();
+
+    const ledger = await program.account.paymentLedger.fetch(paymentLedgerPda);
+    expect(ledger.nextSequence.toNumber()).to.equal(2);
+    expect(ledger.entries.map((entry) => entry.sequence.toNumber())).to.deep.equal([0, 1]);
+  });
+
+  it("Chains every appended entry into the history hash", async () => {
+    for (let i = 2; i < capacity; i++) {
+      await pay();
+    }
+
+    const ledger = await program.account.paymentLedger.fetch(paymentLedgerPda);
+    expect(ledger.entries).to.have.length(capacity);
+    expect(Buffer.from(ledger.historyHash).equals(chain(Buffer.alloc(32), ledger.entries))).to.be.true;
+  });
+
+  it("Overwrites the oldest payment once full", async () => {
+    const before = await program.account.paymentLedger.fetch(paymentLedgerPda);
+
+    await pay();
+
+    const ledger = await program.account.paymentLedger.fetch(paymentLedgerPda);
+    expect(ledger.nextSequence.toNumber()).to.equal(capacity + 1);
+    expect(ledger.entries).to.have.length(capacity);
+    expect(ledger.entries[0].sequence.toNumber()).to.equal(capacity);
+    expect(ledger.entries[1].sequence.toNumber()).to.equal(1);
+
+    // The evicted entry is still covered by the hash
+    const expected = chain(Buffer.from(before.historyHash), [ledger.entries[0]]);
+    expect(Buffer.from(ledger.historyHash).equals(expected)).to.be.true;
+  });
+
+  it("Can no longer refund a payment that rolled out", async () => {
+    try {
+      await program.methods
+        .refundPayment(new anchor.BN(0), new anchor.BN(1))
+        .accounts({
+          paymentLedger: paymentLedgerPda,
+          userSubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          authority: authority.publicKey,
+          payoutWallet: authority.publicKey,
+          payoutTokenAccount: planTokenAccount,
+          userTokenAccount,
+          mint,
+          globalState: globalStatePda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with payment not found");
+    } catch (error) {
+      expect(error.message).to.include("PaymentNotFound");
+    }
+  });
+});
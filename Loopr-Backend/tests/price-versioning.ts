@@ -0,0 +1,196 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Price versioning", () => {
+  const planId = "versioned-plan";
+  const consentThresholdBps = 1_000; // Increases above 10% need the subscriber's consent
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const setPriceChangePolicy = async (noticePeriod: number | null, thresholdBps: number) => {
+    await program.methods
+      .setPriceChangePolicy(noticePeriod === null ? null : new anchor.BN(noticePeriod), thresholdBps)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  const setPrice = async (price: number) => {
+    await program.methods
+      .updateSubscriptionPlan(null, null, new anchor.BN(price), null, null, null, null, null, null, null, null)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  const acceptPriceChange = async (priceVersion: number) => {
+    await program.methods
+      .acceptPriceChange(priceVersion)
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        user: user.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  const pay = async (amount: number) => {
+    await program.methods
+      .processPayment(new anchor.BN(amount))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  const expectPaymentRejected = async (amount: number) => {
+    try {
+      await pay(amount);
+      expect.fail(`Should have rejected a payment of ${amount}`);
+    } catch (error) {
+      expect(error.message).to.include("InvalidPaymentAmount");
+    }
+  };
+
+  const pinnedVersion = async () =>
+    (await program.account.userSubscription.fetch(userSubscriptionPda)).priceVersion;
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, 100, {
+      mint,
+      name: "Versioned plan",
+      description: "Plan whose price changes under existing subscribers",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, 10_000);
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "versioned-sub");
+
+    await pay(100);
+  });
+
+  it("Rejects a negative notice period", async () => {
+    try {
+      await setPriceChangePolicy(-1, consentThresholdBps);
+      expect.fail("Should have failed with invalid notice period");
+    } catch (error) {
+      expect(error.message).to.include("InvalidNoticePeriod");
+    }
+  });
+
+  it("Keeps subscribers on their version until the notice period runs out", async () => {
+    // The default notice period is 30 days
+    await setPrice(105);
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.priceVersion).to.equal(2);
+    expect(await pinnedVersion()).to.equal(1);
+
+    await expectPaymentRejected(105);
+    await pay(100);
+    expect(await pinnedVersion()).to.equal(1);
+  });
+
+  it("Moves subscribers onto a small increase once notice has been given", async () => {
+    await setPriceChangePolicy(0, consentThresholdBps);
+
+    await expectPaymentRejected(100);
+    await pay(105);
+    expect(await pinnedVersion()).to.equal(2);
+  });
+
+  it("Requires consent for an increase above the threshold", async () => {
+    await setPrice(200);
+
+    // Still billed at version 2 even though notice has been given
+    await pay(105);
+    expect(await pinnedVersion()).to.equal(2);
+  });
+
+  it("Only accepts the version the subscriber reviewed", async () => {
+    try {
+      await acceptPriceChange(2);
+      expect.fail("Should have failed with price version mismatch");
+    } catch (error) {
+      expect(error.message).to.include("PriceVersionMismatch");
+    }
+
+    await acceptPriceChange(3);
+    expect(await pinnedVersion()).to.equal(3);
+    await pay(200);
+  });
+
+  it("Rejects consent when there is nothing new to accept", async () => {
+    try {
+      await acceptPriceChange(3);
+      expect.fail("Should have failed with no pending price change");
+    } catch (error) {
+      expect(error.message).to.include("NoPendingPriceChange");
+    }
+  });
+
+  it("Grandfathers subscribers indefinitely without a notice period", async () => {
+    await setPriceChangePolicy(null, consentThresholdBps);
+    await setPrice(210);
+
+    await pay(200);
+    expect(await pinnedVersion()).to.equal(3);
+  });
+
+  it("Passes price cuts on straight away", async () => {
+    await setPrice(150);
+
+    await pay(150);
+    expect(await pinnedVersion()).to.equal(5);
+  });
+});
@@ -0,0 +1,157 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+} from "./setup";
+
+describe("Protocol fee", () => {
+  const planId = "protocol-fee-plan";
+  const decimals = 6;
+  const planPrice = 10_000_000; // 10 tokens
+  const feeBps = 250; // 2.5%
+  const expectedFee = (planPrice * feeBps) / 10_000;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let feeStatsPda: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const setProtocolFee = async (bps: number) => {
+    await program.methods
+      .updateGlobalConfig(null, bps, null, null, null)
+      .accounts({
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    // A fresh mint keeps this suite's fee stats isolated
+    ({ mint, planTokenAccount, treasuryTokenAccount, feeStats: feeStatsPda } = await setupMint(authority, decimals));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Protocol fee plan",
+      description: "Plan used to exercise the protocol fee split",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "protocol-fee-sub");
+  });
+
+  after(async () => {
+    // Other suites assume payments settle in full to the merchant
+    await setProtocolFee(0);
+  });
+
+  it("Defaults the treasury to the global authority", async () => {
+    const globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.treasury.toString()).to.equal(globalState.authority.toString());
+  });
+
+  it("Rejects a protocol fee above the cap", async () => {
+    try {
+      await setProtocolFee(1_001);
+      expect.fail("Should have failed with protocol fee too high");
+    } catch (error) {
+      expect(error.message).to.include("ProtocolFeeTooHigh");
+    }
+  });
+
+  it("Rejects fee changes from anyone but the global authority", async () => {
+    try {
+      await program.methods
+        .updateGlobalConfig(null, feeBps, null, null, null)
+        .accounts({
+          globalState: globalStatePda,
+          authority: authority.publicKey,
+        })
+        .signers([authority])
+        .rpc();
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Splits a payment between the merchant and the treasury", async () => {
+    await setProtocolFee(feeBps);
+
+    const userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice);
+
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: feeStatsPda,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const merchant = await getAccount(provider.connection, planTokenAccount);
+    const treasury = await getAccount(provider.connection, treasuryTokenAccount);
+    expect(Number(merchant.amount)).to.equal(planPrice - expectedFee);
+    expect(Number(treasury.amount)).to.equal(expectedFee);
+
+    const [record] = (await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda))).entries;
+    expect(record.amount.toNumber()).to.equal(planPrice);
+    expect(record.protocolFee.toNumber()).to.equal(expectedFee);
+    expect(record.netAmount.toNumber()).to.equal(planPrice - expectedFee);
+
+    const stats = await program.account.feeStats.fetch(feeStatsPda);
+    expect(stats.mint.toString()).to.equal(mint.toString());
+    expect(stats.totalFees.toNumber()).to.equal(expectedFee);
+    expect(stats.totalVolume.toNumber()).to.equal(planPrice);
+    expect(stats.totalPayments.toNumber()).to.equal(1);
+  });
+
+  it("Rotates the treasury", async () => {
+    const newTreasury = Keypair.generate().publicKey;
+    const setTreasury = async (treasury: PublicKey) =>
+      program.methods
+        .updateGlobalConfig(null, null, treasury, null, null)
+        .accounts({
+          globalState: globalStatePda,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
+
+    await setTreasury(newTreasury);
+    let globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.treasury.toString()).to.equal(newTreasury.toString());
+
+    // Restore the default so later suites can keep paying the wallet's treasury accounts
+    await setTreasury(provider.wallet.publicKey);
+    globalState = await program.account.globalState.fetch(globalStatePda);
+    expect(globalState.treasury.toString()).to.equal(provider.wallet.publicKey.toString());
+  });
+});
@@ -0,0 +1,205 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+} from "./setup";
+
+describe("Refunds", () => {
+  const planId = "refund-plan";
+  const planPrice = 100;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let paymentLedger: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+  let feeStats: PublicKey;
+  let netAmount: number;
+
+  // The subscription's only payment is the first entry in its ledger
+  const refund = async (
+    signer: Keypair,
+    fromTokenAccount: PublicKey,
+    amount: number,
+    sequence = 0,
+    payoutWallet = signer
+  ) => {
+    await program.methods
+      .refundPayment(new anchor.BN(sequence), new anchor.BN(amount))
+      .accounts({
+        paymentLedger,
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        authority: signer.publicKey,
+        payoutWallet: payoutWallet.publicKey,
+        payoutTokenAccount: fromTokenAccount,
+        userTokenAccount,
+        mint,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers(payoutWallet === signer ? [signer] : [signer, payoutWallet])
+      .rpc();
+  };
+
+  const setPayoutWallet = async (wallet: PublicKey | null) => {
+    await program.methods
+      .setPayoutWallet(mint, wallet)
+      .accounts({
+        merchant: merchantPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount, feeStats } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Refundable plan",
+      description: "Plan whose payments get refunded",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice);
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "refund-sub");
+    paymentLedger = findPaymentLedgerPda(userSubscriptionPda);
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger,
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    // Refunds are capped at what reached the merchant after the protocol fee
+    const [record] = (await program.account.paymentLedger.fetch(paymentLedger)).entries;
+    netAmount = record.netAmount.toNumber();
+  });
+
+  it("Only lets the plan authority refund", async () => {
+    const stranger = Keypair.generate();
+    await airdrop(stranger.publicKey);
+    const strangerTokenAccount = await createAccount(provider.connection, stranger, mint, stranger.publicKey);
+
+    try {
+      await refund(stranger, strangerTokenAccount, 10);
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Rejects refunding a payment the ledger does not hold", async () => {
+    try {
+      await refund(authority, planTokenAccount, 10, 1);
+      expect.fail("Should have failed with payment not found");
+    } catch (error) {
+      expect(error.message).to.include("PaymentNotFound");
+    }
+  });
+
+  it("Refunds from the mint's payout wallet", async () => {
+    const payoutWallet = Keypair.generate();
+    await airdrop(payoutWallet.publicKey);
+    const payoutTokenAccount = await createAccount(provider.connection, payoutWallet, mint, payoutWallet.publicKey);
+    await mintTo(provider.connection, authority, mint, payoutTokenAccount, authority, 10);
+    await setPayoutWallet(payoutWallet.publicKey);
+
+    try {
+      await refund(authority, planTokenAccount, 10);
+      expect.fail("Should have failed with invalid payout wallet");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPayoutWallet");
+    }
+
+    await refund(authority, payoutTokenAccount, 10, 0, payoutWallet);
+    expect(await balance(payoutTokenAccount)).to.equal(0);
+    expect(await balance(userTokenAccount)).to.equal(10);
+
+    await setPayoutWallet(null);
+  });
+
+  it("Refunds part of a payment", async () => {
+    const globalBefore = await program.account.globalState.fetch(globalStatePda);
+
+    await refund(authority, planTokenAccount, 30);
+
+    expect(await balance(userTokenAccount)).to.equal(40);
+
+    const [record] = (await program.account.paymentLedger.fetch(paymentLedger)).entries;
+    expect(record.status).to.deep.equal({ partiallyRefunded: {} });
+    expect(record.refundedAmount.toNumber()).to.equal(40);
+    expect(record.netAmount.toNumber()).to.equal(netAmount - 40);
+
+    const globalAfter = await program.account.globalState.fetch(globalStatePda);
+    expect(globalAfter.totalRefunded.sub(globalBefore.totalRefunded).toNumber()).to.equal(30);
+    expect(globalBefore.totalVolume.sub(globalAfter.totalVolume).toNumber()).to.equal(30);
+  });
+
+  it("Rejects refunding more than the merchant received", async () => {
+    try {
+      await refund(authority, planTokenAccount, netAmount - 40 + 1);
+      expect.fail("Should have failed with invalid refund amount");
+    } catch (error) {
+      expect(error.message).to.include("InvalidRefundAmount");
+    }
+  });
+
+  it("Marks the payment refunded once all of it is returned", async () => {
+    await refund(authority, planTokenAccount, netAmount - 40);
+
+    expect(await balance(userTokenAccount)).to.equal(netAmount);
+
+    const [record] = (await program.account.paymentLedger.fetch(paymentLedger)).entries;
+    expect(record.status).to.deep.equal({ refunded: {} });
+    expect(record.refundedAmount.toNumber()).to.equal(netAmount);
+    expect(record.netAmount.toNumber()).to.equal(0);
+  });
+
+  it("Rejects refunds on a fully refunded payment", async () => {
+    try {
+      await refund(authority, planTokenAccount, 1);
+      expect.fail("Should have failed with payment not refundable");
+    } catch (error) {
+      expect(error.message).to.include("PaymentNotRefundable");
+    }
+  });
+});
@@ -0,0 +1,158 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Revenue splits", () => {
+  const planId = "revenue-split-plan";
+  const planPrice = 1_000; // Small enough that the bps shares do not divide evenly
+
+  let authority: Keypair;
+  let user: Keypair;
+  let partnerA: Keypair;
+  let partnerB: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let partnerATokenAccount: PublicKey;
+  let partnerBTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const setRevenueSplit = async (recipients: { wallet: PublicKey; shareBps: number }[]) => {
+    await program.methods
+      .setRevenueSplit(recipients)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  // Split recipients' token accounts go first in the remaining accounts, in split order
+  const pay = async (recipientAccounts: PublicKey[]) => {
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .remainingAccounts(recipientAccounts.map((pubkey) => ({ pubkey, isWritable: true, isSigner: false })))
+      .signers([user])
+      .rpc();
+  };
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Co-sold plan",
+      description: "Plan whose revenue is shared with partners",
+    });
+
+    user = Keypair.generate();
+    partnerA = Keypair.generate();
+    partnerB = Keypair.generate();
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    partnerATokenAccount = await createAccount(provider.connection, authority, mint, partnerA.publicKey);
+    partnerBTokenAccount = await createAccount(provider.connection, authority, mint, partnerB.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, planPrice * 10);
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "revenue-split-sub");
+  });
+
+  it("Rejects shares adding up to more than 100%", async () => {
+    try {
+      await setRevenueSplit([
+        { wallet: partnerA.publicKey, shareBps: 6_000 },
+        { wallet: partnerB.publicKey, shareBps: 4_001 },
+      ]);
+      expect.fail("Should have failed with invalid split shares");
+    } catch (error) {
+      expect(error.message).to.include("InvalidSplitShares");
+    }
+  });
+
+  it("Rejects the same partner listed twice", async () => {
+    try {
+      await setRevenueSplit([
+        { wallet: partnerA.publicKey, shareBps: 1_000 },
+        { wallet: partnerA.publicKey, shareBps: 1_000 },
+      ]);
+      expect.fail("Should have failed with invalid split shares");
+    } catch (error) {
+      expect(error.message).to.include("InvalidSplitShares");
+    }
+  });
+
+  it("Stores the split on the plan", async () => {
+    await setRevenueSplit([
+      { wallet: partnerA.publicKey, shareBps: 3_333 },
+      { wallet: partnerB.publicKey, shareBps: 3_333 },
+    ]);
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.revenueSplit.length).to.equal(2);
+    expect(plan.revenueSplit[0].wallet.toString()).to.equal(partnerA.publicKey.toString());
+    expect(plan.revenueSplit[0].shareBps).to.equal(3_333);
+  });
+
+  it("Requires every split recipient's account", async () => {
+    try {
+      await pay([partnerATokenAccount]);
+      expect.fail("Should have failed with missing split recipients");
+    } catch (error) {
+      expect(error.message).to.include("MissingSplitRecipients");
+    }
+  });
+
+  it("Rejects recipient accounts out of split order", async () => {
+    try {
+      await pay([partnerBTokenAccount, partnerATokenAccount]);
+      expect.fail("Should have failed with invalid split recipient");
+    } catch (error) {
+      expect(error.message).to.include("InvalidSplitRecipient");
+    }
+  });
+
+  it("Rounds partner shares down and pays the dust to the authority", async () => {
+    await pay([partnerATokenAccount, partnerBTokenAccount]);
+
+    // 1000 * 33.33% = 333.3 -> 333 each; the authority gets 1000 - 666 = 334
+    expect(await balance(partnerATokenAccount)).to.equal(333);
+    expect(await balance(partnerBTokenAccount)).to.equal(333);
+    expect(await balance(planTokenAccount)).to.equal(334);
+  });
+});
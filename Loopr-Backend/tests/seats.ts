@@ -0,0 +1,220 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findSubscriptionPlanPda,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Seats and pricing models", () => {
+  const seatPrice = 10;
+  // Seats 1-5 at 10 each, every seat after that at 6
+  const tiers = [
+    { upTo: new anchor.BN(5), unitPrice: new anchor.BN(10) },
+    { upTo: null, unitPrice: new anchor.BN(6) },
+  ];
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const subscriptionPda = (plan: PublicKey) =>
+    findUserSubscriptionPda(user.publicKey, plan);
+
+  const setPricingModel = async (plan: PublicKey, pricingModel: object, priceTiers: object[]) => {
+    await program.methods
+      .setPricingModel(pricingModel as any, priceTiers as any)
+      .accounts({
+        subscriptionPlan: plan,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  const pay = async (plan: PublicKey, amount: number) => {
+    await program.methods
+      .processPayment(new anchor.BN(amount))
+      .accounts({
+        userSubscription: subscriptionPda(plan),
+        subscriptionPlan: plan,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(subscriptionPda(plan)),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  const changeQuantity = async (plan: PublicKey, quantity: number) => {
+    await program.methods
+      .changeQuantity(quantity)
+      .accounts({
+        userSubscription: subscriptionPda(plan),
+        subscriptionPlan: plan,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(subscriptionPda(plan)),
+        user: user.publicKey,
+        mint,
+        userTokenAccount,
+        planTokenAccount,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  // Creates a plan under `pricingModel` and a paid-up single-seat subscription to it
+  const setupPlan = async (planId: string, pricingModel: object, priceTiers: object[]) => {
+    const plan = await createPlan(authority, planId, seatPrice, {
+      mint,
+      name: planId,
+      description: "Plan used to exercise seat pricing",
+    });
+
+    await setPricingModel(plan, pricingModel, priceTiers);
+
+    await createSubscription(user, plan, `${planId}-sub`);
+
+    await pay(plan, seatPrice);
+    return plan;
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, 10_000);
+  });
+
+  it("Rejects tiers without an open-ended top tier", async () => {
+    const plan = await createPlan(authority, "seats-invalid", seatPrice, {
+      mint,
+      name: "seats-invalid",
+      description: "Plan used to exercise seat pricing",
+    });
+
+    try {
+      await setPricingModel(plan, { graduated: {} }, [{ upTo: new anchor.BN(5), unitPrice: new anchor.BN(10) }]);
+      expect.fail("Should have failed with invalid price tiers");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPriceTiers");
+    }
+  });
+
+  it("Keeps the pricing model once the plan has subscribers", async () => {
+    const plan = await setupPlan("seats-locked", { flat: {} }, []);
+
+    try {
+      await setPricingModel(plan, { perUnit: {} }, []);
+      expect.fail("Should have failed with plan has subscribers");
+    } catch (error) {
+      expect(error.message).to.include("PlanHasSubscribers");
+    }
+  });
+
+  it("Charges the prorated difference when seats are added mid-period", async () => {
+    const plan = await setupPlan("seats-per-unit", { perUnit: {} }, []);
+    const before = Number((await getAccount(provider.connection, planTokenAccount)).amount);
+
+    await changeQuantity(plan, 3);
+
+    const charged = Number((await getAccount(provider.connection, planTokenAccount)).amount) - before;
+    expect(charged).to.be.greaterThan(0);
+    expect(charged).to.be.at.most(2 * seatPrice);
+
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda(plan));
+    expect(subscription.quantity).to.equal(3);
+    expect(subscription.creditBalance.toNumber()).to.equal(0);
+
+    // The charge is a payment like any other, after the protocol fee
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(subscriptionPda(plan)));
+    expect(ledger.entries).to.have.length(2);
+    expect(ledger.entries[1].netAmount.toNumber()).to.equal(charged);
+    expect(ledger.entries[1].amount.toNumber()).to.equal(charged + ledger.entries[1].protocolFee.toNumber());
+  });
+
+  it("Credits the unused difference when seats are removed", async () => {
+    const plan = findSubscriptionPlanPda("seats-per-unit");
+
+    await changeQuantity(plan, 1);
+
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda(plan));
+    expect(subscription.quantity).to.equal(1);
+    expect(subscription.creditBalance.toNumber()).to.be.greaterThan(0);
+  });
+
+  it("Rejects a zero quantity", async () => {
+    try {
+      await changeQuantity(findSubscriptionPlanPda("seats-per-unit"), 0);
+      expect.fail("Should have failed with invalid quantity");
+    } catch (error) {
+      expect(error.message).to.include("InvalidQuantity");
+    }
+  });
+
+  it("Rejects a quantity above the maximum", async () => {
+    try {
+      await changeQuantity(findSubscriptionPlanPda("seats-per-unit"), 1_000_001);
+      expect.fail("Should have failed with quantity too large");
+    } catch (error) {
+      expect(error.message).to.include("QuantityTooLarge");
+    }
+  });
+
+  it("Prices each seat by its own tier under graduated pricing", async () => {
+    const plan = await setupPlan("seats-graduated", { graduated: {} }, tiers);
+    await changeQuantity(plan, 8);
+
+    // 5 * 10 + 3 * 6 = 68
+    try {
+      await pay(plan, 8 * seatPrice);
+      expect.fail("Should have failed charging per unit");
+    } catch (error) {
+      expect(error.message).to.include("InvalidPaymentAmount");
+    }
+    await pay(plan, 68);
+  });
+
+  it("Prices every seat by the quantity's tier under volume pricing", async () => {
+    const plan = await setupPlan("seats-volume", { volume: {} }, tiers);
+    await changeQuantity(plan, 8);
+
+    // 8 seats fall in the second tier: 8 * 6 = 48
+    await pay(plan, 48);
+
+    const subscription = await program.account.userSubscription.fetch(subscriptionPda(plan));
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(2);
+  });
+});
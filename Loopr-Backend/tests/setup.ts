@@ -0,0 +1,155 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { createAccount, createMint, getOrCreateAssociatedTokenAccount } from "@solana/spl-token";
+import { LooprSubscription } from "../target/types/loopr_subscription";
+
+// Shared setup for the test suites: the provider, PDA helpers and the
+// merchant/plan/mint scaffolding most suites start from
+
+export const provider = anchor.AnchorProvider.env();
+anchor.setProvider(provider);
+export const program = anchor.workspace.LooprSubscription as Program<LooprSubscription>;
+
+export const periodDuration = 30 * 24 * 60 * 60;
+
+export const airdrop = async (key: PublicKey) => {
+  await provider.connection.confirmTransaction(
+    await provider.connection.requestAirdrop(key, 10 * LAMPORTS_PER_SOL)
+  );
+};
+
+export const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+const pda = (seeds: (Buffer | Uint8Array)[]) =>
+  PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+
+export const findGlobalStatePda = () => pda([Buffer.from("global_state")]);
+
+export const findMerchantPda = (authority: PublicKey) => pda([Buffer.from("merchant"), authority.toBuffer()]);
+
+export const findSubscriptionPlanPda = (planId: string) => pda([Buffer.from("subscription_plan"), Buffer.from(planId)]);
+
+export const findUserSubscriptionPda = (user: PublicKey, plan: PublicKey) =>
+  pda([Buffer.from("user_subscription"), user.toBuffer(), plan.toBuffer()]);
+
+export const findPaymentLedgerPda = (subscription: PublicKey) =>
+  pda([Buffer.from("payment_ledger"), subscription.toBuffer()]);
+
+export const findTrialRecordPda = (plan: PublicKey, wallet: PublicKey) =>
+  pda([Buffer.from("trial_record"), plan.toBuffer(), wallet.toBuffer()]);
+
+export const findPaymentIntentPda = (intentId: string) => pda([Buffer.from("payment_intent"), Buffer.from(intentId)]);
+
+export const findFeeStatsPda = (mint: PublicKey) => pda([Buffer.from("fee_stats"), mint.toBuffer()]);
+
+export const findAutopayDelegatePda = () => pda([Buffer.from("autopay_delegate")]);
+
+export const findEscrowAuthorityPda = () => pda([Buffer.from("escrow_authority")]);
+
+export const findEscrowVaultPda = (plan: PublicKey) => pda([Buffer.from("escrow_vault"), plan.toBuffer()]);
+
+// Global state is shared by every suite, so only the first one to run creates it
+export const initializeGlobalState = async () => {
+  try {
+    await program.methods
+      .initializeGlobalState()
+      .accounts({
+        globalState: findGlobalStatePda(),
+        authority: provider.wallet.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+  } catch (error) {
+    console.log("Global state already initialized");
+  }
+};
+
+// Funds a fresh authority and registers it as a merchant
+export const setupMerchant = async (name = "Test merchant") => {
+  const authority = Keypair.generate();
+  await airdrop(authority.publicKey);
+  await initializeGlobalState();
+
+  const merchant = findMerchantPda(authority.publicKey);
+  await program.methods
+    .registerMerchant(name, "https://example.com/logo.png", "support@example.com")
+    .accounts({
+      merchant,
+      authority: authority.publicKey,
+      globalState: findGlobalStatePda(),
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([authority])
+    .rpc();
+
+  return { authority, merchantPda: merchant, globalStatePda: findGlobalStatePda() };
+};
+
+type PlanOptions = {
+  mint?: PublicKey;
+  name?: string;
+  description?: string;
+  period?: number;
+  maxSubscribers?: number | null;
+};
+
+// Publishes a plan priced in `mint`, or in native SOL when no mint is given
+export const createPlan = async (authority: Keypair, planId: string, price: number, options: PlanOptions = {}) => {
+  const plan = findSubscriptionPlanPda(planId);
+  await program.methods
+    .initializeSubscriptionPlan(
+      planId,
+      options.name ?? "Test plan",
+      options.description ?? "Plan created by the test setup",
+      new anchor.BN(price),
+      new anchor.BN(options.period ?? periodDuration),
+      options.maxSubscribers ?? null
+    )
+    .accounts({
+      subscriptionPlan: plan,
+      merchant: findMerchantPda(authority.publicKey),
+      authority: authority.publicKey,
+      acceptedMint: options.mint ?? null,
+      globalState: findGlobalStatePda(),
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([authority])
+    .rpc();
+
+  return plan;
+};
+
+// Creates a mint owned by `authority` with a payout account for the merchant
+// and the protocol treasury's account for fees
+export const setupMint = async (authority: Keypair, decimals = 0) => {
+  const mint = await createMint(provider.connection, authority, authority.publicKey, null, decimals);
+  const planTokenAccount = await createAccount(provider.connection, authority, mint, authority.publicKey);
+  const treasuryTokenAccount = (
+    await getOrCreateAssociatedTokenAccount(provider.connection, authority, mint, provider.wallet.publicKey)
+  ).address;
+
+  return { mint, planTokenAccount, treasuryTokenAccount, feeStats: findFeeStatsPda(mint) };
+};
+
+// Opens a plain subscription with no trial or autopay
+export const createSubscription = async (user: Keypair, plan: PublicKey, subscriptionId: string) => {
+  const subscription = findUserSubscriptionPda(user.publicKey, plan);
+  const planAccount = await program.account.subscriptionPlan.fetch(plan);
+  await program.methods
+    .createSubscription(subscriptionId)
+    .accounts({
+      userSubscription: subscription,
+      paymentLedger: findPaymentLedgerPda(subscription),
+      subscriptionPlan: plan,
+      merchant: findMerchantPda(planAccount.authority),
+      user: user.publicKey,
+      trialRecord: findTrialRecordPda(plan, user.publicKey),
+      globalState: findGlobalStatePda(),
+      systemProgram: SystemProgram.programId,
+    })
+    .signers([user])
+    .rpc();
+
+  return subscription;
+};
@@ -0,0 +1,240 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  periodDuration,
+  airdrop,
+  sleep,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Streaming subscriptions", () => {
+  const planId = "streaming-plan";
+  // One token per second, so accrual is visible within a test run
+  const planPrice = periodDuration;
+  const deposit = 1_000;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let streamAuthorityPda: PublicKey;
+  let streamVaultPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  const depositStream = async (amount: number) => {
+    await program.methods
+      .depositStream(new anchor.BN(amount))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: user.publicKey,
+        userTokenAccount,
+        mint,
+        streamAuthority: streamAuthorityPda,
+        streamVault: streamVaultPda,
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  const withdrawStream = async (signer: Keypair = authority) => {
+    await program.methods
+      .withdrawStream()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        authority: signer.publicKey,
+        streamAuthority: streamAuthorityPda,
+        streamVault: streamVaultPda,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([signer])
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Streaming plan",
+      description: "Plan paid by the second from a prepaid vault",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, deposit);
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "streaming-sub");
+    [streamAuthorityPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("stream_authority")],
+      program.programId
+    );
+    [streamVaultPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("stream_vault"), userSubscriptionPda.toBuffer()],
+      program.programId
+    );
+  });
+
+  it("Funds the stream and extends access by what the deposit covers", async () => {
+    await depositStream(deposit);
+
+    expect(await balance(streamVaultPda)).to.equal(deposit);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.stream).to.not.be.null;
+    expect(subscription.status).to.deep.equal({ active: {} });
+    // The first deposit is what takes the subscriber's place on the plan
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.currentSubscribers).to.equal(1);
+    // At one token per second the deposit pays for `deposit` seconds
+    const covered = subscription.nextPaymentDue.sub(subscription.stream.accruedAt).toNumber();
+    expect(covered).to.equal(deposit);
+  });
+
+  it("Rejects regular payments while streaming", async () => {
+    try {
+      await program.methods
+        .processPayment(new anchor.BN(planPrice))
+        .accounts({
+          userSubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+          user: user.publicKey,
+          userTokenAccount,
+          planTokenAccount,
+          mint,
+          treasuryTokenAccount,
+          feeStats: findFeeStatsPda(mint),
+          globalState: globalStatePda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with subscription streaming");
+    } catch (error) {
+      expect(error.message).to.include("SubscriptionStreaming");
+    }
+  });
+
+  it("Takes no coupons while streaming", async () => {
+    const couponCode = "STREAM-FOREVER";
+    const [couponPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("coupon"), authority.publicKey.toBuffer(), Buffer.from(couponCode)],
+      program.programId
+    );
+    await program.methods
+      .createCoupon(couponCode, { percent: {} }, new anchor.BN(5_000), { forever: {} }, null, null, subscriptionPlanPda, mint)
+      .accounts({
+        coupon: couponPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    // Time already streamed was priced without it, so even a forever coupon waits
+    try {
+      await program.methods
+        .applyCoupon()
+        .accounts({
+          userSubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          coupon: couponPda,
+          user: user.publicKey,
+          globalState: globalStatePda,
+        })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with subscription streaming");
+    } catch (error) {
+      expect(error.message).to.include("SubscriptionStreaming");
+    }
+  });
+
+  it("Only lets the plan authority withdraw", async () => {
+    try {
+      await withdrawStream(user);
+      expect.fail("Should have failed with unauthorized");
+    } catch (error) {
+      expect(error.message).to.include("Unauthorized");
+    }
+  });
+
+  it("Pays the merchant what has accrued so far", async () => {
+    await sleep(3_000);
+    await withdrawStream();
+
+    const withdrawn = await balance(planTokenAccount);
+    expect(withdrawn).to.be.greaterThan(0);
+    expect(withdrawn).to.be.lessThan(deposit);
+    expect(await balance(streamVaultPda)).to.equal(deposit - withdrawn);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.stream.accrued.toNumber()).to.equal(0);
+  });
+
+  it("Refunds the unaccrued remainder on cancel", async () => {
+    await sleep(2_000);
+    await program.methods
+      .cancelSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: user.publicKey,
+        globalState: globalStatePda,
+        mint,
+        userTokenAccount,
+        streamAuthority: streamAuthorityPda,
+        streamVault: streamVaultPda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ cancelled: {} });
+
+    // Only the merchant's unwithdrawn accrual stays behind
+    const accrued = subscription.stream.accrued.toNumber();
+    expect(accrued).to.be.greaterThan(0);
+    expect(await balance(streamVaultPda)).to.equal(accrued);
+    expect(await balance(userTokenAccount)).to.equal(deposit - (await balance(planTokenAccount)) - accrued);
+
+    await withdrawStream();
+    expect(await balance(streamVaultPda)).to.equal(0);
+  });
+});
@@ -0,0 +1,221 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { NATIVE_MINT } from "@solana/spl-token";
+import { expect } from "chai";
+import v1QrSubscription from "./fixtures/v1-qr-subscription.json";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  createPlan,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+  findPaymentIntentPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Subscription addresses", () => {
+  const planId = "address-plan";
+  const intentId = "address-intent";
+  const subscriptionId = "address-qr-sub";
+  const planPrice = 0.05 * LAMPORTS_PER_SOL;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant("Address Merchant"));
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      name: "Address plan",
+      description: "Plan subscribed to through a QR code",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+  });
+
+  it("Gives a QR subscription the same address as a direct one", async () => {
+    const paymentIntentPda = findPaymentIntentPda(intentId);
+
+    await program.methods
+      .createPaymentIntent(intentId, planId, new anchor.BN(planPrice), new anchor.BN(Math.floor(Date.now() / 1000) + 3600))
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    await program.methods
+      .subscribeAndPay(subscriptionId, 100)
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        userSubscription: userSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        trialRecord: findTrialRecordPda(subscriptionPlanPda, user.publicKey),
+        authority: authority.publicKey,
+        treasury: provider.wallet.publicKey,
+        feeStats: findFeeStatsPda(NATIVE_MINT),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.subscriptionPlan.toString()).to.equal(subscriptionPlanPda.toString());
+    expect(Buffer.from(subscription.subscriptionId).toString().replace(/\0+$/, "")).to.equal(subscriptionId);
+
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(userSubscriptionPda));
+    expect(ledger.subscription.toString()).to.equal(userSubscriptionPda.toString());
+    expect(ledger.nextSequence.toNumber()).to.equal(1);
+  });
+
+  it("Refuses a second subscription to the same plan", async () => {
+    try {
+      await program.methods
+        .createSubscription("address-direct-sub")
+        .accounts({
+          userSubscription: userSubscriptionPda,
+          paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          user: user.publicKey,
+          trialRecord: findTrialRecordPda(subscriptionPlanPda, user.publicKey),
+          globalState: globalStatePda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with the subscription already in use");
+    } catch (error) {
+      expect(error.message).to.not.include("Should have failed");
+    }
+  });
+
+  it("Moves a v1 QR subscription to its canonical address", async () => {
+    // The fixture was written by the first subscribe_and_pay, under its subscription id
+    const legacyUser = Keypair.fromSeed(Buffer.alloc(32, "legacy"));
+    await airdrop(legacyUser.publicKey);
+    const legacyPlanPda = await createPlan(authority, "legacy-qr-plan", planPrice, {
+      name: "Legacy plan",
+      description: "Plan a v1 subscription was paid for through a QR code",
+    });
+    const legacySubscription = new PublicKey(v1QrSubscription.pubkey);
+    const migratedPda = findUserSubscriptionPda(legacyUser.publicKey, legacyPlanPda);
+
+    await program.methods
+      .migrateUserSubscription()
+      .accounts({
+        legacySubscription,
+        subscriptionPlan: legacyPlanPda,
+        userSubscription: migratedPda,
+        paymentLedger: findPaymentLedgerPda(migratedPda),
+        user: legacyUser.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([legacyUser])
+      .rpc();
+
+    expect(await provider.connection.getAccountInfo(legacySubscription)).to.be.null;
+
+    const subscription = await program.account.userSubscription.fetch(migratedPda);
+    expect(Buffer.from(subscription.subscriptionId).toString().replace(/\0+$/, "")).to.equal("legacy-qr-sub");
+    expect(subscription.status).to.deep.equal({ active: {} });
+    expect(subscription.lastPaymentDate.toNumber()).to.equal(1_700_000_000);
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(2);
+    // The v1 payment thread is not carried over as an autopay mandate
+    expect(subscription.autoPayEnabled).to.be.false;
+    expect(subscription.pricePerPeriod.toNumber()).to.equal(planPrice);
+    expect(subscription.version).to.equal(2);
+
+    const ledger = await program.account.paymentLedger.fetch(findPaymentLedgerPda(migratedPda));
+    expect(ledger.subscription.toString()).to.equal(migratedPda.toString());
+    expect(ledger.nextSequence.toNumber()).to.equal(0);
+    expect(ledger.entries).to.be.empty;
+  });
+
+  it("Only migrates subscriptions in the v1 layout", async () => {
+    try {
+      await program.methods
+        .migrateUserSubscription()
+        .accounts({
+          legacySubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          userSubscription: userSubscriptionPda,
+          paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+          user: user.publicKey,
+          globalState: globalStatePda,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with the account already migrated");
+    } catch (error) {
+      expect(error.message).to.not.include("Should have failed");
+    }
+  });
+
+  const confirmPayment = () =>
+    program.methods
+      .confirmPayment()
+      .accounts({
+        paymentIntent: findPaymentIntentPda(intentId),
+        subscriptionPlan: subscriptionPlanPda,
+        userSubscription: userSubscriptionPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+
+  it("Lets the merchant confirm a QR payment", async () => {
+    await confirmPayment();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ active: {} });
+  });
+
+  it("Lets a QR subscription be cancelled", async () => {
+    await program.methods
+      .cancelSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ cancelled: {} });
+  });
+
+  it("Refuses to confirm a payment for a subscription that has lapsed", async () => {
+    try {
+      await confirmPayment();
+      expect.fail("Should have failed with subscription not active");
+    } catch (error) {
+      expect(error.message).to.include("SubscriptionNotActive");
+    }
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ cancelled: {} });
+  });
+});
@@ -0,0 +1,206 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, Transaction, sendAndConfirmTransaction } from "@solana/web3.js";
+import {
+  ExtensionType,
+  TOKEN_2022_PROGRAM_ID,
+  createAccount,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  createMint,
+  getAccount,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  createPlan,
+  createSubscription,
+  findSubscriptionPlanPda,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Token-2022 plans", () => {
+  const decimals = 6;
+  const planPrice = 10_000_000; // 10 tokens
+  const feeBasisPoints = 100; // 1%
+  const maxFee = BigInt(1_000_000);
+
+  let authority: Keypair;
+  let user: Keypair;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+
+  // Protocol fees go to the treasury, which defaults to the global state authority
+  const treasuryTokenAccount = async (mint: PublicKey) =>
+    (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection, authority, mint, provider.wallet.publicKey, false, undefined, undefined, TOKEN_2022_PROGRAM_ID
+      )
+    ).address;
+
+  const createFeeMint = async (): Promise<PublicKey> => {
+    const mint = Keypair.generate();
+    const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+    const lamports = await provider.connection.getMinimumBalanceForRentExemption(mintLen);
+    const tx = new Transaction().add(
+      SystemProgram.createAccount({
+        fromPubkey: authority.publicKey,
+        newAccountPubkey: mint.publicKey,
+        space: mintLen,
+        lamports,
+        programId: TOKEN_2022_PROGRAM_ID,
+      }),
+      createInitializeTransferFeeConfigInstruction(
+        mint.publicKey,
+        authority.publicKey,
+        authority.publicKey,
+        feeBasisPoints,
+        maxFee,
+        TOKEN_2022_PROGRAM_ID
+      ),
+      createInitializeMintInstruction(
+        mint.publicKey,
+        decimals,
+        authority.publicKey,
+        null,
+        TOKEN_2022_PROGRAM_ID
+      )
+    );
+    await sendAndConfirmTransaction(provider.connection, tx, [authority, mint]);
+    return mint.publicKey;
+  };
+
+  // Creates a plan in `mint`, subscribes `user` and pays one period manually
+  const subscribeAndPay = async (planId: string, mint: PublicKey) => {
+    const userTokenAccount = await createAccount(
+      provider.connection, user, mint, user.publicKey, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+    const planTokenAccount = await createAccount(
+      provider.connection, authority, mint, authority.publicKey, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+    await mintTo(
+      provider.connection, authority, mint, userTokenAccount, authority, planPrice * 3, [], undefined, TOKEN_2022_PROGRAM_ID
+    );
+
+    const subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      mint,
+      name: "Token-2022 plan",
+      description: "Plan billed in a Token-2022 mint",
+    });
+    const userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, `${planId}-sub`);
+
+    await program.methods
+      .processPayment(new anchor.BN(planPrice))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount: await treasuryTokenAccount(mint),
+        feeStats: findFeeStatsPda(mint),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_2022_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    return { paymentLedger: findPaymentLedgerPda(userSubscriptionPda), planTokenAccount, subscriptionPlanPda };
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+  });
+
+  it("Bills a plain Token-2022 mint with transfer_checked", async () => {
+    const mint = await createMint(
+      provider.connection, authority, authority.publicKey, null, decimals, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+
+    const { paymentLedger, planTokenAccount, subscriptionPlanPda } = await subscribeAndPay("t22-plain", mint);
+
+    const plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+    expect(plan.acceptedMint.toString()).to.equal(mint.toString());
+    expect(plan.mintDecimals).to.equal(decimals);
+
+    const [record] = (await program.account.paymentLedger.fetch(paymentLedger)).entries;
+    expect(record.amount.toNumber()).to.equal(planPrice);
+    expect(record.feeAmount.toNumber()).to.equal(0);
+    expect(record.netAmount.toNumber()).to.equal(planPrice);
+    expect(record.decimals).to.equal(decimals);
+
+    const merchant = await getAccount(provider.connection, planTokenAccount, undefined, TOKEN_2022_PROGRAM_ID);
+    expect(Number(merchant.amount)).to.equal(planPrice);
+  });
+
+  it("Records the merchant's net amount for a transfer-fee mint", async () => {
+    const mint = await createFeeMint();
+    const expectedFee = Math.min((planPrice * feeBasisPoints) / 10_000, Number(maxFee));
+
+    const { paymentLedger, planTokenAccount } = await subscribeAndPay("t22-fee", mint);
+
+    const [record] = (await program.account.paymentLedger.fetch(paymentLedger)).entries;
+    expect(record.amount.toNumber()).to.equal(planPrice);
+    expect(record.feeAmount.toNumber()).to.equal(expectedFee);
+    expect(record.netAmount.toNumber()).to.equal(planPrice - expectedFee);
+    expect(record.mint.toString()).to.equal(mint.toString());
+
+    const merchant = await getAccount(provider.connection, planTokenAccount, undefined, TOKEN_2022_PROGRAM_ID);
+    expect(Number(merchant.amount)).to.equal(planPrice - expectedFee);
+  });
+
+  it("Rejects token accounts from a different mint", async () => {
+    const otherMint = await createMint(
+      provider.connection, authority, authority.publicKey, null, decimals, undefined, undefined, TOKEN_2022_PROGRAM_ID
+    );
+
+    const subscriptionPlanPda = findSubscriptionPlanPda("t22-plain");
+    const userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+    const userTokenAccount = await createAccount(
+      provider.connection, user, otherMint, user.publicKey, Keypair.generate(), undefined, TOKEN_2022_PROGRAM_ID
+    );
+    const planTokenAccount = await createAccount(
+      provider.connection, authority, otherMint, authority.publicKey, Keypair.generate(), undefined, TOKEN_2022_PROGRAM_ID
+    );
+
+    try {
+      await program.methods
+        .processPayment(new anchor.BN(planPrice))
+        .accounts({
+          userSubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          merchant: merchantPda,
+          paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+          user: user.publicKey,
+          userTokenAccount,
+          planTokenAccount,
+          mint: otherMint,
+          treasuryTokenAccount: await treasuryTokenAccount(otherMint),
+          feeStats: findFeeStatsPda(otherMint),
+          globalState: globalStatePda,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .signers([user])
+        .rpc();
+
+      expect.fail("Should have failed with invalid mint");
+    } catch (error) {
+      expect(error.message).to.include("InvalidMint");
+    }
+  });
+});
@@ -0,0 +1,155 @@
+import * as anchor from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL } from "@solana/web3.js";
+import { NATIVE_MINT } from "@solana/spl-token";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  createPlan,
+  findUserSubscriptionPda,
+  findPaymentLedgerPda,
+  findTrialRecordPda,
+  findPaymentIntentPda,
+  findFeeStatsPda,
+} from "./setup";
+
+describe("Free trials", () => {
+  const planId = "trial-plan";
+  const planPrice = 0.1 * LAMPORTS_PER_SOL;
+  const trialDuration = 7 * 24 * 60 * 60;
+
+  let authority: Keypair;
+  let user: Keypair;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let trialRecordPda: PublicKey;
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    subscriptionPlanPda = await createPlan(authority, planId, planPrice, {
+      name: "Trial plan",
+      description: "Plan with a seven day free trial",
+    });
+
+    user = Keypair.generate();
+    await airdrop(user.publicKey);
+    trialRecordPda = findTrialRecordPda(subscriptionPlanPda, user.publicKey);
+
+    await program.methods
+      .updateSubscriptionPlan(null, null, null, null, new anchor.BN(trialDuration), null, null, null, null, null, null)
+      .accounts({
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  });
+
+  it("Starts a zero-charge trial with the first payment due at trial end", async () => {
+    const userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+
+    await program.methods
+      .createSubscription("trial-sub")
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        user: user.publicKey,
+        trialRecord: trialRecordPda,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ trialing: {} });
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(0);
+
+    const trial = await program.account.trialRecord.fetch(trialRecordPda);
+    expect(trial.user.toString()).to.equal(user.publicKey.toString());
+    expect(subscription.nextPaymentDue.toNumber()).to.equal(trial.startedAt.toNumber() + trialDuration);
+  });
+
+  it("Charges in full when the wallet already used its trial", async () => {
+    const intentId = "trial-repeat-intent";
+    const subscriptionId = "trial-repeat-sub";
+    const paymentIntentPda = findPaymentIntentPda(intentId);
+    const userSubscriptionPda = findUserSubscriptionPda(user.publicKey, subscriptionPlanPda);
+
+    // A wallet holds one subscription per plan, so the trial one is closed
+    // first; the trial record outlives it
+    await program.methods
+      .cancelSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+    await program.methods
+      .closeUserSubscription()
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+
+    await program.methods
+      .createPaymentIntent(
+        intentId,
+        planId,
+        new anchor.BN(planPrice),
+        new anchor.BN(Math.floor(Date.now() / 1000) + 3600)
+      )
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    const balanceBefore = await provider.connection.getBalance(authority.publicKey);
+
+    await program.methods
+      .subscribeAndPay(subscriptionId, 250)
+      .accounts({
+        paymentIntent: paymentIntentPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        userSubscription: userSubscriptionPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        trialRecord: trialRecordPda,
+        authority: authority.publicKey,
+        treasury: provider.wallet.publicKey,
+        feeStats: findFeeStatsPda(NATIVE_MINT),
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+
+    const balanceAfter = await provider.connection.getBalance(authority.publicKey);
+    expect(balanceAfter - balanceBefore).to.equal(planPrice);
+
+    const subscription = await program.account.userSubscription.fetch(userSubscriptionPda);
+    expect(subscription.status).to.deep.equal({ active: {} });
+    expect(subscription.totalPaymentsMade.toNumber()).to.equal(1);
+    expect(subscription.maxSlippageBps).to.equal(250);
+  });
+});
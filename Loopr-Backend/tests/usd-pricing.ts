@@ -0,0 +1,326 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, getAccount, mintTo } from "@solana/spl-token";
+import { MockPriceFeed } from "../target/types/mock_price_feed";
+import { expect } from "chai";
+import {
+  provider,
+  program,
+  airdrop,
+  setupMerchant,
+  setupMint,
+  createPlan,
+  createSubscription,
+  findPaymentLedgerPda,
+  findFeeStatsPda,
+} from "./setup";
+
+// Size of a Pyth v2 price account; only the header and aggregate price are written
+const PRICE_ACCOUNT_SIZE = 3312;
+const PRICE_HEADER_SIZE = 240;
+const PRICE_EXPO = -8;
+
+describe("USD-priced plans", () => {
+  const mockPriceFeed = anchor.workspace.MockPriceFeed as Program<MockPriceFeed>;
+
+  const planId = "usd-plan";
+  const usdPrice = 100_000_000; // $100 in micro-USD
+
+  let authority: Keypair;
+  let user: Keypair;
+  let priceFeed: Keypair;
+  let product: PublicKey;
+  let priceOracle: PublicKey;
+  let mint: PublicKey;
+  let globalStatePda: PublicKey;
+  let merchantPda: PublicKey;
+  let subscriptionPlanPda: PublicKey;
+  let unsubscribedPlanPda: PublicKey;
+  let userSubscriptionPda: PublicKey;
+  let userTokenAccount: PublicKey;
+  let planTokenAccount: PublicKey;
+  let treasuryTokenAccount: PublicKey;
+
+  const now = async () => provider.connection.getBlockTime(await provider.connection.getSlot());
+
+  const balance = async (tokenAccount: PublicKey) =>
+    Number((await getAccount(provider.connection, tokenAccount)).amount);
+
+  // Dollar amounts are scaled by 10^-PRICE_EXPO, as Pyth publishes them
+  const setPrice = async ({
+    price,
+    conf = 0,
+    emaPrice = price,
+    age = 0,
+  }: {
+    price: number;
+    conf?: number;
+    emaPrice?: number;
+    age?: number;
+  }) => {
+    const scale = 10 ** -PRICE_EXPO;
+    const publishTime = BigInt((await now()) - age);
+    const data = Buffer.alloc(PRICE_HEADER_SIZE);
+    data.writeUInt32LE(0xa1b2c3d4, 0); // magic
+    data.writeUInt32LE(2, 4); // version
+    data.writeUInt32LE(3, 8); // price account
+    data.writeUInt32LE(PRICE_ACCOUNT_SIZE, 12);
+    data.writeUInt32LE(1, 16); // price type
+    data.writeInt32LE(PRICE_EXPO, 20);
+    product.toBuffer().copy(data, 112);
+    data.writeBigInt64LE(BigInt(Math.round(emaPrice * scale)), 48);
+    data.writeBigInt64LE(publishTime, 96);
+    data.writeBigInt64LE(BigInt(Math.round(price * scale)), 184); // previous price
+    data.writeBigInt64LE(publishTime, 200);
+    data.writeBigInt64LE(BigInt(Math.round(price * scale)), 208); // aggregate price
+    data.writeBigUInt64LE(BigInt(Math.round(conf * scale)), 216);
+    data.writeUInt32LE(1, 224); // trading
+    await mockPriceFeed.methods
+      .write(0, data)
+      .accounts({ priceAccount: priceFeed.publicKey })
+      .rpc();
+  };
+
+  const usdPricing = (overrides: object = {}) => ({
+    priceFeed: priceFeed.publicKey,
+    product,
+    maxPriceAge: new anchor.BN(60),
+    maxConfidenceBps: 100,
+    ...overrides,
+  });
+
+  const setUsdPricing = async (pricing: { priceFeed: PublicKey } | null, plan = subscriptionPlanPda) => {
+    await program.methods
+      .setUsdPricing(pricing as any)
+      .accounts({
+        subscriptionPlan: plan,
+        authority: authority.publicKey,
+        priceFeed: pricing ? pricing.priceFeed : null,
+        globalState: globalStatePda,
+      })
+      .signers([authority])
+      .rpc();
+  };
+
+  const pay = async (amount: number, withPriceFeed = true) => {
+    await program.methods
+      .processPayment(new anchor.BN(amount))
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        subscriptionPlan: subscriptionPlanPda,
+        merchant: merchantPda,
+        paymentLedger: findPaymentLedgerPda(userSubscriptionPda),
+        user: user.publicKey,
+        userTokenAccount,
+        planTokenAccount,
+        mint,
+        treasuryTokenAccount,
+        feeStats: findFeeStatsPda(mint),
+        ...(withPriceFeed ? { priceFeed: priceFeed.publicKey } : {}),
+        globalState: globalStatePda,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  const expectPaymentError = async (amount: number, error: string) => {
+    try {
+      await pay(amount);
+      expect.fail(`Should have failed with ${error}`);
+    } catch (e) {
+      expect(e.message).to.include(error);
+    }
+  };
+
+  const expectPricingError = async (pricing: { priceFeed: PublicKey } | null, plan: PublicKey, error: string) => {
+    try {
+      await setUsdPricing(pricing, plan);
+      expect.fail(`Should have failed with ${error}`);
+    } catch (e) {
+      expect(e.message).to.include(error);
+    }
+  };
+
+  const setPriceOracle = async (oracle: PublicKey) => {
+    await program.methods
+      .updateGlobalConfig(null, null, null, null, oracle)
+      .accounts({
+        globalState: globalStatePda,
+        authority: provider.wallet.publicKey,
+      })
+      .rpc();
+  };
+
+  before(async () => {
+    ({ authority, merchantPda, globalStatePda } = await setupMerchant());
+    ({ mint, planTokenAccount, treasuryTokenAccount } = await setupMint(authority));
+    user = Keypair.generate();
+    priceFeed = Keypair.generate();
+    product = Keypair.generate().publicKey;
+    await airdrop(user.publicKey);
+
+    // Price feeds must belong to the oracle program; here that is the mock
+    ({ priceOracle } = await program.account.globalState.fetch(globalStatePda));
+    await setPriceOracle(mockPriceFeed.programId);
+
+    // The mock program owns the price account so the test can write it
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: provider.wallet.publicKey,
+          newAccountPubkey: priceFeed.publicKey,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(PRICE_ACCOUNT_SIZE),
+          space: PRICE_ACCOUNT_SIZE,
+          programId: mockPriceFeed.programId,
+        })
+      ),
+      [priceFeed]
+    );
+    await setPrice({ price: 20 });
+
+    userTokenAccount = await createAccount(provider.connection, user, mint, user.publicKey);
+    await mintTo(provider.connection, authority, mint, userTokenAccount, authority, 1_000);
+
+    subscriptionPlanPda = await createPlan(authority, planId, usdPrice, {
+      mint,
+      name: "USD plan",
+      description: "Plan priced in dollars and paid in tokens",
+    });
+
+    await setUsdPricing(usdPricing());
+
+    userSubscriptionPda = await createSubscription(user, subscriptionPlanPda, "usd-sub");
+
+    unsubscribedPlanPda = await createPlan(authority, "usd-plan-unsubscribed", usdPrice, {
+      mint,
+      name: "Unsubscribed USD plan",
+      description: "Plan left without subscribers to try price feeds on",
+    });
+  });
+
+  after(async () => {
+    // Other suites expect Pyth's oracle program
+    await setPriceOracle(priceOracle);
+  });
+
+  it("Defaults the price oracle to Pyth", async () => {
+    expect(priceOracle.toString()).to.equal("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+  });
+
+  it("Only accepts price feeds owned by the price oracle", async () => {
+    // Same header, but in an account the oracle doesn't own
+    const forgedFeed = Keypair.generate();
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: provider.wallet.publicKey,
+          newAccountPubkey: forgedFeed.publicKey,
+          lamports: await provider.connection.getMinimumBalanceForRentExemption(PRICE_ACCOUNT_SIZE),
+          space: PRICE_ACCOUNT_SIZE,
+          programId: SystemProgram.programId,
+        })
+      ),
+      [forgedFeed]
+    );
+    await expectPricingError(usdPricing({ priceFeed: forgedFeed.publicKey }), unsubscribedPlanPda, "InvalidPriceFeed");
+  });
+
+  it("Only accepts a price feed published under the pricing's product", async () => {
+    const otherProduct = Keypair.generate().publicKey;
+    await expectPricingError(usdPricing({ product: otherProduct }), unsubscribedPlanPda, "InvalidPriceFeed");
+  });
+
+  it("Rejects fixed-amount coupons on a USD plan", async () => {
+    // A fixed amount is in token units, which the plan's prices aren't
+    const couponCode = "USD-FIXED";
+    const [couponPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("coupon"), authority.publicKey.toBuffer(), Buffer.from(couponCode)],
+      program.programId
+    );
+    await program.methods
+      .createCoupon(couponCode, { fixed: {} }, new anchor.BN(1), { forever: {} }, null, null, subscriptionPlanPda, mint)
+      .accounts({
+        coupon: couponPda,
+        authority: authority.publicKey,
+        globalState: globalStatePda,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([authority])
+      .rpc();
+
+    try {
+      await program.methods
+        .applyCoupon()
+        .accounts({
+          userSubscription: userSubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          coupon: couponPda,
+          user: user.publicKey,
+          globalState: globalStatePda,
+        })
+        .signers([user])
+        .rpc();
+      expect.fail("Should have failed with invalid coupon");
+    } catch (error) {
+      expect(error.message).to.include("InvalidCoupon");
+    }
+  });
+
+  it("Requires the price feed to charge a USD plan", async () => {
+    try {
+      await pay(5, false);
+      expect.fail("Should have failed with missing price feed");
+    } catch (error) {
+      expect(error.message).to.include("MissingPriceFeed");
+    }
+  });
+
+  it("Charges the USD price converted at the feed's rate", async () => {
+    // $100 at $20 per token
+    await pay(10);
+    expect(await balance(planTokenAccount)).to.equal(5);
+  });
+
+  it("Refuses to charge more than the subscriber allowed", async () => {
+    await setPrice({ price: 10 });
+    await expectPaymentError(5, "SlippageExceeded");
+    await pay(10);
+    expect(await balance(planTokenAccount)).to.equal(15);
+  });
+
+  it("Rejects a stale price", async () => {
+    await setPrice({ price: 10, age: 600 });
+    await expectPaymentError(10, "StalePrice");
+  });
+
+  it("Rejects a price with too wide a confidence interval", async () => {
+    await setPrice({ price: 10, conf: 0.5 });
+    await expectPaymentError(10, "PriceTooUncertain");
+  });
+
+  it("Holds the rate to the subscriber's slippage cap against the moving average", async () => {
+    // Spot has dropped 50% below the average; the default cap is 1%
+    await setPrice({ price: 10, emaPrice: 20 });
+    await expectPaymentError(10, "SlippageExceeded");
+
+    await program.methods
+      .setMaxSlippage(5_000)
+      .accounts({
+        userSubscription: userSubscriptionPda,
+        user: user.publicKey,
+        globalState: globalStatePda,
+      })
+      .signers([user])
+      .rpc();
+    await pay(10);
+  });
+
+  it("Locks the pricing while the plan has subscribers", async () => {
+    await expectPricingError(null, subscriptionPlanPda, "PlanHasSubscribers");
+    // Subscribers agreed to pay against this feed and its terms too
+    await expectPricingError(usdPricing({ maxPriceAge: new anchor.BN(3_600) }), subscriptionPlanPda, "PlanHasSubscribers");
+  });
+});
@@ -1,5 +1,36 @@
-export async function convertUsdToSol(usd: number): Promise<number> {
-  // In production, fetch real-time price from CoinGecko, Jupiter Aggregator, etc.
-  const SOL_PRICE_IN_USD = 25.0; // Replace this with actual fetch later
-  return usd / SOL_PRICE_IN_USD;
-}
\ No newline at end of file
+import { Connection, PublicKey, clusterApiUrl } from '@solana/web3.js';
+
+// Pyth SOL/USD price accounts, the same feeds USD-priced plans are charged against on-chain
+export const SOL_USD_PRICE_FEEDS = {
+  devnet: new PublicKey('J83w4HKfqxwcq3BEMMkPFSppX3gqekLyLJBexebFVkix'),
+  'mainnet-beta': new PublicKey('H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG'),
+};
+
+const PYTH_MAGIC = 0xa1b2c3d4;
+const PYTH_STATUS_TRADING = 1;
+
+// Reads the aggregate price, in USD, from a Pyth-format price account
+export async function getUsdPrice(connection: Connection, priceFeed: PublicKey): Promise<number> {
+  const account = await connection.getAccountInfo(priceFeed);
+  if (!account || account.data.length < 240 || account.data.readUInt32LE(0) !== PYTH_MAGIC) {
+    throw new Error(`Not a price account: ${priceFeed.toBase58()}`);
+  }
+
+  const data = account.data;
+  const expo = data.readInt32LE(20);
+  const trading = data.readUInt32LE(224) === PYTH_STATUS_TRADING;
+  // Fall back to the last trading price while the feed is not trading
+  const price = Number(data.readBigInt64LE(trading ? 208 : 184));
+  if (price <= 0) {
+    throw new Error(`No price published on ${priceFeed.toBase58()}`);
+  }
+  return price * 10 ** expo;
+}
+
+export async function convertUsdToSol(
+  usd: number,
+  connection: Connection = new Connection(clusterApiUrl('devnet')),
+  priceFeed: PublicKey = SOL_USD_PRICE_FEEDS.devnet
+): Promise<number> {
+  return usd / (await getUsdPrice(connection, priceFeed));
+}